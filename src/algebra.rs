@@ -0,0 +1,281 @@
+//! Set algebra over any pair of set representations.
+//!
+//! The operations take two sets supporting [`Rank`] and [`IterableSet`], which
+//! may use different representations, and produce a new set of whatever
+//! representation the caller chooses via [`FromSorted`]. The two sets are
+//! usually merged by iterating over both, but when one set is much smaller than
+//! the other, the elements of the smaller set are probed in the larger one with
+//! `rank`, rather than merging the two sets element by element.
+
+use crate::{iter::IterableSet, rank::Rank};
+
+/// When one set has more than this many times as many elements as the other,
+/// probe the larger set with the elements of the smaller one rather than
+/// merging them.
+static PROBE_RATIO: usize = 16;
+
+/// Construction of a set from its elements, for use as the result of set algebra.
+pub trait FromSorted: Sized {
+    /// Create a set over the domain `[0, size)` from elements in strictly
+    /// increasing order.
+    ///
+    /// Representations with a fixed domain, such as [`Sparse`](crate::sparse::Sparse),
+    /// may round `size` up.
+    fn from_sorted(size: u64, elements: &[u64]) -> Self;
+}
+
+/// Return the number of bits required to represent every element of `[0, size)`.
+pub(crate) fn domain_bits(size: u64) -> usize {
+    (u64::BITS - size.saturating_sub(1).leading_zeros()) as usize
+}
+
+/// Merge the two sets, keeping the elements for which `keep(in_a, in_b)` is true.
+fn merge<A, B, F>(a: &A, b: &B, keep: F) -> Vec<u64>
+where
+    A: Rank + IterableSet,
+    B: Rank + IterableSet,
+    F: Fn(bool, bool) -> bool,
+{
+    let mut res: Vec<u64> = Vec::new();
+    let (mut xs, mut ys) = (a.iter(), b.iter());
+    let (mut x, mut y) = (xs.next(), ys.next());
+    while let (Some(u), Some(v)) = (x, y) {
+        if u < v {
+            if keep(true, false) {
+                res.push(u);
+            }
+            x = xs.next();
+        } else if v < u {
+            if keep(false, true) {
+                res.push(v);
+            }
+            y = ys.next();
+        } else {
+            if keep(true, true) {
+                res.push(u);
+            }
+            x = xs.next();
+            y = ys.next();
+        }
+    }
+    if keep(true, false) {
+        res.extend(x.into_iter().chain(xs));
+    }
+    if keep(false, true) {
+        res.extend(y.into_iter().chain(ys));
+    }
+    res
+}
+
+/// Return true if the set `a` is small enough relative to `b` that its elements
+/// should be probed in `b`.
+fn probe<A: Rank, B: Rank>(a: &A, b: &B) -> bool {
+    a.count().saturating_mul(PROBE_RATIO) < b.count()
+}
+
+/// Return the elements of `a` for which `b.contains(x) == wanted`.
+fn filter<A, B>(a: &A, b: &B, wanted: bool) -> Vec<u64>
+where
+    A: Rank + IterableSet,
+    B: Rank + IterableSet,
+{
+    a.iter()
+        .filter(|x| (*x < b.size() && b.contains(*x)) == wanted)
+        .collect()
+}
+
+/// Return the number of elements of `a` that are also in `b`.
+fn count_in<A, B>(a: &A, b: &B) -> usize
+where
+    A: Rank + IterableSet,
+    B: Rank + IterableSet,
+{
+    a.iter().filter(|x| *x < b.size() && b.contains(*x)).count()
+}
+
+fn intersection_elements<A, B>(a: &A, b: &B) -> Vec<u64>
+where
+    A: Rank + IterableSet,
+    B: Rank + IterableSet,
+{
+    if probe(a, b) {
+        filter(a, b, true)
+    } else if probe(b, a) {
+        filter(b, a, true)
+    } else {
+        merge(a, b, |x, y| x && y)
+    }
+}
+
+/// Return the set of elements in either `a` or `b`.
+///
+/// #Examples
+///
+/// ```
+/// use crate::ransel::algebra::union;
+/// use crate::ransel::dense64::Dense64;
+/// use crate::ransel::iter::IterableSet;
+/// use crate::ransel::sorted::Sorted;
+/// use crate::ransel::sparse::Sparse;
+///
+/// let a = Sparse::new(10, &[1, 5, 9, 700]);
+/// let b = Dense64::from_positions(1000, &[2, 5, 999]);
+/// let c: Sorted = union(&a, &b);
+/// assert_eq!(c.iter().collect::<Vec<u64>>(), vec![1, 2, 5, 9, 700, 999]);
+/// ```
+pub fn union<A, B, S>(a: &A, b: &B) -> S
+where
+    A: Rank + IterableSet,
+    B: Rank + IterableSet,
+    S: FromSorted,
+{
+    S::from_sorted(a.size().max(b.size()), &merge(a, b, |x, y| x || y))
+}
+
+/// Return the set of elements in both `a` and `b`.
+///
+/// #Examples
+///
+/// ```
+/// use crate::ransel::algebra::intersection;
+/// use crate::ransel::dense64::Dense64;
+/// use crate::ransel::iter::IterableSet;
+/// use crate::ransel::sparse::Sparse;
+///
+/// let a = Sparse::new(10, &[1, 5, 9, 700]);
+/// let b = Dense64::from_positions(1000, &[2, 5, 700, 999]);
+/// let c: Sparse = intersection(&a, &b);
+/// assert_eq!(c.iter().collect::<Vec<u64>>(), vec![5, 700]);
+/// ```
+pub fn intersection<A, B, S>(a: &A, b: &B) -> S
+where
+    A: Rank + IterableSet,
+    B: Rank + IterableSet,
+    S: FromSorted,
+{
+    S::from_sorted(a.size().min(b.size()), &intersection_elements(a, b))
+}
+
+/// Return the set of elements in `a` but not in `b`.
+pub fn difference<A, B, S>(a: &A, b: &B) -> S
+where
+    A: Rank + IterableSet,
+    B: Rank + IterableSet,
+    S: FromSorted,
+{
+    let elements = if probe(a, b) {
+        filter(a, b, false)
+    } else {
+        merge(a, b, |x, y| x && !y)
+    };
+    S::from_sorted(a.size(), &elements)
+}
+
+/// Return the set of elements in exactly one of `a` and `b`.
+pub fn symmetric_difference<A, B, S>(a: &A, b: &B) -> S
+where
+    A: Rank + IterableSet,
+    B: Rank + IterableSet,
+    S: FromSorted,
+{
+    S::from_sorted(a.size().max(b.size()), &merge(a, b, |x, y| x != y))
+}
+
+/// Return the number of elements in both `a` and `b`, without constructing
+/// their intersection.
+pub fn intersection_count<A, B>(a: &A, b: &B) -> usize
+where
+    A: Rank + IterableSet,
+    B: Rank + IterableSet,
+{
+    if probe(a, b) {
+        return count_in(a, b);
+    }
+    if probe(b, a) {
+        return count_in(b, a);
+    }
+    let (mut xs, mut ys) = (a.iter(), b.iter());
+    let (mut x, mut y) = (xs.next(), ys.next());
+    let mut count = 0;
+    while let (Some(u), Some(v)) = (x, y) {
+        if u < v {
+            x = xs.next();
+        } else if v < u {
+            y = ys.next();
+        } else {
+            count += 1;
+            x = xs.next();
+            y = ys.next();
+        }
+    }
+    count
+}
+
+/// Return the Jaccard similarity of `a` and `b`: the size of their intersection
+/// divided by the size of their union. Two empty sets have a similarity of 1.
+pub fn jaccard<A, B>(a: &A, b: &B) -> f64
+where
+    A: Rank + IterableSet,
+    B: Rank + IterableSet,
+{
+    let both = intersection_count(a, b);
+    let either = a.count() + b.count() - both;
+    if either == 0 {
+        1.0
+    } else {
+        both as f64 / either as f64
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::{dense64::Dense64, rrr::Rrr, sorted::Sorted, sparse::Sparse};
+    use random::Source;
+
+    fn make_set(size: u64, n: usize, seed: u64) -> Vec<u64> {
+        let mut rng = random::default(seed);
+        let mut xs: Vec<u64> = (0..n).map(|_i| rng.read_u64() % size).collect();
+        xs.sort();
+        xs.dedup();
+        xs
+    }
+
+    fn naive(xs: &[u64], ys: &[u64], keep: fn(bool, bool) -> bool) -> Vec<u64> {
+        let mut zs: Vec<u64> = xs.iter().chain(ys.iter()).copied().collect();
+        zs.sort();
+        zs.dedup();
+        zs.retain(|z| keep(xs.binary_search(z).is_ok(), ys.binary_search(z).is_ok()));
+        zs
+    }
+
+    #[test]
+    fn test_algebra_1() {
+        let size: u64 = 1 << 16;
+        // Similar sizes merge, and skewed sizes probe.
+        for (n, m) in [(5000, 4000), (100, 20000), (20000, 100), (0, 100)] {
+            let xs = make_set(size, n, 19);
+            let ys = make_set(size, m, 23);
+            let a = Sparse::new(16, &xs);
+            let b = Dense64::from_positions(size, &ys);
+
+            let u: Sorted = union(&a, &b);
+            assert_eq!(u.iter().collect::<Vec<u64>>(), naive(&xs, &ys, |x, y| x || y));
+            let i: Dense64 = intersection(&a, &b);
+            assert_eq!(i.iter().collect::<Vec<u64>>(), naive(&xs, &ys, |x, y| x && y));
+            let d: Rrr = difference(&a, &b);
+            assert_eq!(d.iter().collect::<Vec<u64>>(), naive(&xs, &ys, |x, y| x && !y));
+            let d: Sparse = difference(&b, &a);
+            assert_eq!(d.iter().collect::<Vec<u64>>(), naive(&xs, &ys, |x, y| !x && y));
+            let s: Sorted = symmetric_difference(&a, &b);
+            assert_eq!(s.iter().collect::<Vec<u64>>(), naive(&xs, &ys, |x, y| x != y));
+
+            let c = naive(&xs, &ys, |x, y| x && y).len();
+            assert_eq!(intersection_count(&a, &b), c);
+            assert_eq!(intersection_count(&b, &a), c);
+            let j = c as f64 / naive(&xs, &ys, |x, y| x || y).len() as f64;
+            assert!((jaccard(&a, &b) - j).abs() < 1e-12);
+        }
+    }
+}
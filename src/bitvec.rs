@@ -1,7 +1,28 @@
 //! A bit vector represented as a vector of 64 bit words.
 
+use crate::{
+    error::{check_index, check_param, check_value, Result},
+    persist::{check_params, invalid_data, load_vec_u64, save_vec, to_usize, Persistent},
+    words::get_bits,
+};
+
+/// Sets which may be constructed from a bit vector, where bit `i` is set if `i` is
+/// an element. This allows [`SparseBuilder`](crate::sparse::SparseBuilder) to
+/// construct the high bits of a [`Sparse`](crate::sparse::Sparse) set with any
+/// dense representation.
+pub trait FromBitVec: Sized {
+    /// Create the set from a bit vector, taking ownership of its words.
+    fn from_bitvec(bits: BitVec) -> Self;
+}
+
+/// Sets represented by a plain bit vector, which expose its words.
+pub trait AsWords {
+    /// Return the underlying words of the bit vector.
+    fn as_words(&self) -> &[u64];
+}
+
 /// A bit vector represented by a vector of 64 bit words.
-#[derive(Clone)]
+#[derive(Clone, Default)]
 pub struct BitVec {
     size: usize,
     words: Vec<u64>
@@ -13,11 +34,24 @@ impl BitVec {
         BitVec {size: 0, words: Vec::new() }
     }
 
+    /// Return a new bit vector with room for `bits` bits before reallocating.
+    pub fn with_capacity(bits: usize) -> BitVec {
+        BitVec {
+            size: 0,
+            words: Vec::with_capacity(bits.div_ceil(64)),
+        }
+    }
+
     /// Return the length of the bit vector.
     pub fn len(&self) -> usize {
         self.size
     }
 
+    /// Return true if the bit vector is empty.
+    pub fn is_empty(&self) -> bool {
+        self.size == 0
+    }
+
     /// Retrieve a bit from the bit vector.
     pub fn get(&self, index: usize) -> bool {
         let w = index >> 6;
@@ -45,8 +79,152 @@ impl BitVec {
         self.set(index, bit);
     }
 
+    /// Append the low `width` bits of `value` to the end of the bit vector,
+    /// least significant bit first.
+    pub fn push_bits(&mut self, value: u64, width: usize) {
+        assert!(width <= 64);
+        if width == 0 {
+            return;
+        }
+        let index = self.size;
+        self.size += width;
+        while self.words.len() * 64 < self.size {
+            self.words.push(0);
+        }
+        let value = if width < 64 { value & ((1u64 << width) - 1) } else { value };
+        let w = index >> 6;
+        let b = index & 63;
+        self.words[w] |= value << b;
+        if b + width > 64 {
+            self.words[w + 1] |= value >> (64 - b);
+        }
+    }
+
+    /// Retrieve `width` bits starting at `index`, with the first bit in the
+    /// least significant position.
+    pub fn get_bits(&self, index: usize, width: usize) -> u64 {
+        assert!(width <= 64 && index + width <= self.size);
+        get_bits(&self.words, index, width)
+    }
+
+    /// Retrieve a bit, or return an error if `index` is out of range.
+    pub fn try_get(&self, index: usize) -> Result<bool> {
+        check_index(index, self.size)?;
+        Ok(self.get(index))
+    }
+
+    /// Set a bit, or return an error if `index` is out of range.
+    pub fn try_set(&mut self, index: usize, bit: bool) -> Result<()> {
+        check_index(index, self.size)?;
+        self.set(index, bit);
+        Ok(())
+    }
+
+    /// Append the low `width` bits of `value`, or return an error if `width`
+    /// is greater than 64 or `value` does not fit in `width` bits.
+    pub fn try_push_bits(&mut self, value: u64, width: usize) -> Result<()> {
+        check_param("width", width as u64, 0, 64)?;
+        check_value(value, width)?;
+        self.push_bits(value, width);
+        Ok(())
+    }
+
     /// Return the underlying vector of words.
     pub fn as_words(&self) -> &[u64] {
         &self.words
     }
-}
\ No newline at end of file
+
+    /// Consume the bit vector, returning the underlying vector of words.
+    pub fn into_words(self) -> Vec<u64> {
+        self.words
+    }
+}
+
+impl AsWords for BitVec {
+    fn as_words(&self) -> &[u64] {
+        &self.words
+    }
+}
+
+impl Persistent for BitVec {
+    const TYPE_TAG: [u8; 4] = *b"BVEC";
+
+    fn params(&self) -> Vec<u64> {
+        vec![self.size as u64]
+    }
+
+    fn save_body<Sink>(&self, sink: &mut Sink) -> std::io::Result<()>
+    where
+        Sink: std::io::Write,
+    {
+        save_vec(sink, &self.words)
+    }
+
+    fn load_body<Source>(params: &[u64], source: &mut Source) -> std::io::Result<Self>
+    where
+        Source: std::io::Read,
+    {
+        check_params(&Self::TYPE_TAG, params, 1)?;
+        let size: usize = to_usize(params[0])?;
+        let words: Vec<u64> = load_vec_u64(source)?;
+        if words.len() != size.div_ceil(64) {
+            return Err(invalid_data(String::from("inconsistent BitVec length")));
+        }
+        Ok(BitVec { size, words })
+    }
+}
+
+#[cfg(feature = "serde")]
+impl serde::Serialize for BitVec {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> std::result::Result<S::Ok, S::Error> {
+        use serde::ser::SerializeStruct;
+        let mut state = serializer.serialize_struct("BitVec", 2)?;
+        state.serialize_field("size", &(self.size as u64))?;
+        state.serialize_field("words", &self.words)?;
+        state.end()
+    }
+}
+
+#[cfg(feature = "serde")]
+#[derive(serde::Deserialize)]
+#[serde(rename = "BitVec")]
+struct BitVecData {
+    size: u64,
+    words: Vec<u64>,
+}
+
+#[cfg(feature = "serde")]
+impl<'de> serde::Deserialize<'de> for BitVec {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> std::result::Result<Self, D::Error> {
+        use serde::de::Error;
+        let data = BitVecData::deserialize(deserializer)?;
+        let size = usize::try_from(data.size).map_err(D::Error::custom)?;
+        if data.words.len() != size.div_ceil(64) || !crate::words::clear_from(&data.words, data.size) {
+            return Err(D::Error::custom("inconsistent BitVec length"));
+        }
+        Ok(BitVec {
+            size,
+            words: data.words,
+        })
+    }
+}
+
+#[cfg(all(test, feature = "serde"))]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_serde_1() {
+        let mut bits = BitVec::new();
+        for i in 0..100 {
+            bits.push(i % 3 == 0);
+        }
+        let json = serde_json::to_string(&bits).expect("serialize failed");
+        let other: BitVec = serde_json::from_str(&json).expect("deserialize failed");
+        assert_eq!(other.len(), 100);
+        assert_eq!(other.as_words(), bits.as_words());
+
+        assert!(serde_json::from_str::<BitVec>(r#"{"size":100,"words":[0]}"#).is_err());
+        assert!(serde_json::from_str::<BitVec>(r#"{"size":10,"words":[1024]}"#).is_err());
+    }
+}
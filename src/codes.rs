@@ -0,0 +1,870 @@
+//! Universal codes for integers, and sequences of coded integers with sampled access.
+//!
+//! The codes are described in many places, for example:
+//!
+//! > Elias, P., 1975. Universal codeword sets and representations of the integers.
+//! > IEEE transactions on information theory, 21(2), pp. 194-203.
+//!
+//! > Boldi, P. and Vigna, S., 2005. Codes for the world wide web. Internet
+//! > mathematics, 2(4), pp. 407-429.
+//!
+//! All the codes here encode the non-negative integers. The Elias and zeta codes,
+//! which are defined on the positive integers, encode `x` as the code for `x + 1`.
+
+use crate::{
+    algebra::domain_bits,
+    bitvec::BitVec,
+    error::{check_domain, check_elements, check_index, check_param, Result},
+    intvec::IntVec,
+    iter::IterableSet,
+    persist::{check_params, invalid_data, to_usize, Persistent},
+    rank::Rank,
+    select::{Select, Successor},
+    set::ImpliedSet,
+    words::get_bits,
+};
+
+/// An integer code.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Code {
+    /// `x` zero bits followed by a one bit.
+    Unary,
+    /// The length of `x + 1` in unary, followed by its bits after the leading 1.
+    Gamma,
+    /// The length of `x + 1` in gamma code, followed by its bits after the leading 1.
+    Delta,
+    /// Golomb-Rice code with parameter `k`: `x >> k` in unary, followed by the
+    /// low `k` bits of `x`.
+    Rice(usize),
+    /// Zeta code with parameter `k`: for `x + 1` in `[2^(hk), 2^((h+1)k))`, `h` in
+    /// unary, followed by the minimal binary code for `x + 1 - 2^(hk)`.
+    Zeta(usize),
+}
+
+impl Code {
+    /// Return the code and parameter as a pair of integers, for persistence.
+    fn to_params(self) -> [u64; 2] {
+        match self {
+            Code::Unary => [0, 0],
+            Code::Gamma => [1, 0],
+            Code::Delta => [2, 0],
+            Code::Rice(k) => [3, k as u64],
+            Code::Zeta(k) => [4, k as u64],
+        }
+    }
+
+    /// Check the parameter of a Golomb-Rice or zeta code is supported.
+    fn check(self) -> Result<()> {
+        match self {
+            Code::Rice(k) => check_param("k", k as u64, 0, 63),
+            Code::Zeta(k) => check_param("k", k as u64, 1, 63),
+            _ => Ok(()),
+        }
+    }
+
+    fn from_params(kind: u64, k: u64) -> std::io::Result<Code> {
+        match (kind, k) {
+            (0, _) => Ok(Code::Unary),
+            (1, _) => Ok(Code::Gamma),
+            (2, _) => Ok(Code::Delta),
+            (3, k) if k < 64 => Ok(Code::Rice(k as usize)),
+            (4, k) if (1..64).contains(&k) => Ok(Code::Zeta(k as usize)),
+            _ => Err(invalid_data(format!("invalid code {} with parameter {}", kind, k))),
+        }
+    }
+}
+
+/// Return the bounds `2^(hk)` and `2^((h+1)k)` of the interval containing `x`
+/// for the zeta code with parameter `k`.
+fn zeta_interval(k: usize, h: usize) -> (u128, u128) {
+    let lo = 1u128 << (h * k).min(127);
+    let hi = 1u128 << ((h + 1) * k).min(127);
+    (lo, hi)
+}
+
+/// Return the width and threshold of the minimal binary code for `z` values.
+fn minimal_binary(z: u128) -> (usize, u128) {
+    let s = (u128::BITS - (z - 1).leading_zeros()) as usize;
+    (s, (1u128 << s) - z)
+}
+
+/// Writes coded integers to the end of a [`BitVec`].
+///
+/// #Examples
+///
+/// ```
+/// use crate::ransel::codes::{BitReader, BitWriter, Code};
+///
+/// let mut w = BitWriter::new();
+/// w.write(Code::Gamma, 4);
+/// w.write(Code::Rice(3), 100);
+/// w.write_unary(2);
+/// let bits = w.finish();
+/// assert_eq!(bits.len(), 5 + 16 + 3);
+/// let mut r = BitReader::new(bits.as_words());
+/// assert_eq!(r.read(Code::Gamma), 4);
+/// assert_eq!(r.read(Code::Rice(3)), 100);
+/// assert_eq!(r.read_unary(), 2);
+/// ```
+pub struct BitWriter {
+    bits: BitVec,
+}
+
+impl BitWriter {
+    /// Create a writer with an empty bit vector.
+    pub fn new() -> BitWriter {
+        BitWriter { bits: BitVec::new() }
+    }
+
+    /// Create a writer appending to an existing bit vector.
+    pub fn from_bitvec(bits: BitVec) -> BitWriter {
+        BitWriter { bits }
+    }
+
+    /// Return the number of bits written so far.
+    pub fn len(&self) -> usize {
+        self.bits.len()
+    }
+
+    /// Return true if no bits have been written.
+    pub fn is_empty(&self) -> bool {
+        self.bits.is_empty()
+    }
+
+    /// Write the low `width` bits of `value`.
+    pub fn write_bits(&mut self, value: u64, width: usize) {
+        self.bits.push_bits(value, width);
+    }
+
+    /// Write `x` in unary.
+    pub fn write_unary(&mut self, x: u64) {
+        let mut x = x;
+        while x >= 64 {
+            self.bits.push_bits(0, 64);
+            x -= 64;
+        }
+        self.bits.push_bits(1u64 << x, x as usize + 1);
+    }
+
+    /// Write `x`, which must be less than `u64::MAX` for the Elias and zeta codes.
+    pub fn write(&mut self, code: Code, x: u64) {
+        match code {
+            Code::Unary => self.write_unary(x),
+            Code::Gamma => {
+                let n = x.checked_add(1).expect("value too large for gamma code");
+                let l = n.ilog2() as usize;
+                self.write_unary(l as u64);
+                self.write_bits(n, l);
+            }
+            Code::Delta => {
+                let n = x.checked_add(1).expect("value too large for delta code");
+                let l = n.ilog2() as usize;
+                self.write(Code::Gamma, l as u64);
+                self.write_bits(n, l);
+            }
+            Code::Rice(k) => {
+                assert!(k < 64);
+                self.write_unary(x >> k);
+                self.write_bits(x, k);
+            }
+            Code::Zeta(k) => {
+                assert!((1..64).contains(&k));
+                let n = x.checked_add(1).expect("value too large for zeta code") as u128;
+                let h = n.ilog2() as usize / k;
+                let (lo, hi) = zeta_interval(k, h);
+                let (s, m) = minimal_binary(hi - lo);
+                let v = n - lo;
+                self.write_unary(h as u64);
+                if v < m {
+                    self.write_msb_first(v, s - 1);
+                } else {
+                    self.write_msb_first(v + m, s);
+                }
+            }
+        }
+    }
+
+    /// Write the low `width` bits of `value`, most significant first, so a
+    /// reader may decide after the first `width - 1` whether to read the last.
+    fn write_msb_first(&mut self, value: u128, width: usize) {
+        for i in (0..width).rev() {
+            self.bits.push((value >> i) & 1 == 1);
+        }
+    }
+
+    /// Return the bit vector written.
+    pub fn finish(self) -> BitVec {
+        self.bits
+    }
+}
+
+impl Default for BitWriter {
+    fn default() -> Self {
+        BitWriter::new()
+    }
+}
+
+/// Reads coded integers from the words of a bit vector.
+pub struct BitReader<'a> {
+    words: &'a [u64],
+    pos: usize,
+}
+
+impl<'a> BitReader<'a> {
+    /// Create a reader starting at the beginning of `words`.
+    pub fn new(words: &'a [u64]) -> BitReader<'a> {
+        BitReader { words, pos: 0 }
+    }
+
+    /// Return the position of the next bit to be read.
+    pub fn position(&self) -> usize {
+        self.pos
+    }
+
+    /// Move to bit position `pos`.
+    pub fn seek(&mut self, pos: usize) {
+        self.pos = pos;
+    }
+
+    /// Read `width` bits.
+    pub fn read_bits(&mut self, width: usize) -> u64 {
+        let x = get_bits(self.words, self.pos, width);
+        self.pos += width;
+        x
+    }
+
+    /// Read a number written in unary.
+    pub fn read_unary(&mut self) -> u64 {
+        let mut x = 0;
+        loop {
+            let w = get_bits(self.words, self.pos, 64);
+            if w != 0 {
+                let z = w.trailing_zeros() as u64;
+                self.pos += z as usize + 1;
+                return x + z;
+            }
+            assert!(self.pos < 64 * self.words.len(), "unterminated unary code");
+            x += 64;
+            self.pos += 64;
+        }
+    }
+
+    /// Read a number written with `code`.
+    pub fn read(&mut self, code: Code) -> u64 {
+        match code {
+            Code::Unary => self.read_unary(),
+            Code::Gamma => {
+                let l = self.read_unary() as usize;
+                ((1u64 << l) | self.read_bits(l)) - 1
+            }
+            Code::Delta => {
+                let l = self.read(Code::Gamma) as usize;
+                ((1u64 << l) | self.read_bits(l)) - 1
+            }
+            Code::Rice(k) => {
+                let q = self.read_unary();
+                (q << k) | self.read_bits(k)
+            }
+            Code::Zeta(k) => {
+                let h = self.read_unary() as usize;
+                let (lo, hi) = zeta_interval(k, h);
+                let (s, m) = minimal_binary(hi - lo);
+                let mut v = self.read_msb_first(s.saturating_sub(1));
+                if s > 0 && v >= m {
+                    v = ((v << 1) | self.read_bits(1) as u128) - m;
+                }
+                (lo + v - 1) as u64
+            }
+        }
+    }
+
+    fn read_msb_first(&mut self, width: usize) -> u128 {
+        let mut v = 0;
+        for _i in 0..width {
+            v = (v << 1) | self.read_bits(1) as u128;
+        }
+        v
+    }
+}
+
+/// A sequence of integers stored with an integer [`Code`], with the position
+/// of every `k`-th code sampled so `get` decodes at most `k` codes.
+///
+/// #Examples
+///
+/// ```
+/// use crate::ransel::codes::{Code, CodedSeq};
+///
+/// let xs: Vec<u64> = vec![0, 1, 1, 2, 3, 5, 8, 13, 21, 34, 55, 89, 144];
+/// let s = CodedSeq::new(Code::Delta, 4, &xs);
+/// assert_eq!(s.get(9), 34);
+/// assert_eq!(s.iter().collect::<Vec<u64>>(), xs);
+/// ```
+pub struct CodedSeq {
+    code: Code,
+    k: usize,
+    n: usize,
+    bits: BitVec,
+    pointers: IntVec,
+}
+
+impl CodedSeq {
+    /// Create a new sequence storing `values` with `code`, sampling every `k`-th position.
+    pub fn new(code: Code, k: usize, values: &[u64]) -> CodedSeq {
+        assert!(k >= 1);
+        let mut w = BitWriter::new();
+        let mut positions: Vec<u64> = Vec::with_capacity(values.len().div_ceil(k));
+        for (i, x) in values.iter().enumerate() {
+            if i % k == 0 {
+                positions.push(w.len() as u64);
+            }
+            w.write(code, *x);
+        }
+        let bits = w.finish();
+        let mut pointers = IntVec::with_capacity(domain_bits(bits.len() as u64 + 1), positions.len());
+        for p in positions.iter() {
+            pointers.push(*p);
+        }
+        CodedSeq {
+            code,
+            k,
+            n: values.len(),
+            bits,
+            pointers,
+        }
+    }
+
+    /// Create a new sequence, or return an error if `k` is zero or the
+    /// parameter of `code` is not supported.
+    pub fn try_new(code: Code, k: usize, values: &[u64]) -> Result<CodedSeq> {
+        code.check()?;
+        check_param("k", k as u64, 1, u64::MAX)?;
+        Ok(CodedSeq::new(code, k, values))
+    }
+
+    /// Return the number of values.
+    pub fn len(&self) -> usize {
+        self.n
+    }
+
+    /// Return true if there are no values.
+    pub fn is_empty(&self) -> bool {
+        self.n == 0
+    }
+
+    /// Return the number of bits used by the codes.
+    pub fn code_bits(&self) -> usize {
+        self.bits.len()
+    }
+
+    /// Return a reader positioned at the start of sampled block `j`.
+    fn block(&self, j: usize) -> BitReader<'_> {
+        let mut r = BitReader::new(self.bits.as_words());
+        r.seek(self.pointers.get(j) as usize);
+        r
+    }
+
+    /// Return the value at position `i`.
+    pub fn get(&self, i: usize) -> u64 {
+        assert!(i < self.n);
+        let mut r = self.block(i / self.k);
+        for _j in 0..i % self.k {
+            r.read(self.code);
+        }
+        r.read(self.code)
+    }
+
+    /// Return the value at position `i`, or an error if `i` is out of range.
+    pub fn try_get(&self, i: usize) -> Result<u64> {
+        check_index(i, self.n)?;
+        Ok(self.get(i))
+    }
+
+    /// Return an iterator over the values.
+    pub fn iter(&self) -> CodedSeqIter<'_> {
+        CodedSeqIter {
+            seq: self,
+            reader: BitReader::new(self.bits.as_words()),
+            index: 0,
+        }
+    }
+}
+
+/// An iterator over the values of a [`CodedSeq`].
+pub struct CodedSeqIter<'a> {
+    seq: &'a CodedSeq,
+    reader: BitReader<'a>,
+    index: usize,
+}
+
+impl<'a> Iterator for CodedSeqIter<'a> {
+    type Item = u64;
+
+    fn next(&mut self) -> Option<u64> {
+        if self.index >= self.seq.n {
+            return None;
+        }
+        self.index += 1;
+        Some(self.reader.read(self.seq.code))
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        let remaining = self.seq.n - self.index;
+        (remaining, Some(remaining))
+    }
+}
+
+impl<'a> ExactSizeIterator for CodedSeqIter<'a> {}
+
+impl Persistent for CodedSeq {
+    const TYPE_TAG: [u8; 4] = *b"CSEQ";
+
+    fn params(&self) -> Vec<u64> {
+        let [kind, param] = self.code.to_params();
+        vec![kind, param, self.k as u64, self.n as u64]
+    }
+
+    fn save_body<Sink>(&self, sink: &mut Sink) -> std::io::Result<()>
+    where
+        Sink: std::io::Write,
+    {
+        self.bits.save(sink)?;
+        self.pointers.save(sink)?;
+        Ok(())
+    }
+
+    fn load_body<Source>(params: &[u64], source: &mut Source) -> std::io::Result<Self>
+    where
+        Source: std::io::Read,
+    {
+        check_params(&Self::TYPE_TAG, params, 4)?;
+        let code = Code::from_params(params[0], params[1])?;
+        let k: usize = to_usize(params[2])?;
+        let n: usize = to_usize(params[3])?;
+        let bits: BitVec = *(BitVec::load(source)?);
+        let pointers: IntVec = *(IntVec::load(source)?);
+        if k == 0 || pointers.len() != n.div_ceil(k) {
+            return Err(invalid_data(String::from("inconsistent coded sequence")));
+        }
+        let mut prev = 0;
+        for j in 0..pointers.len() {
+            let p = pointers.get(j);
+            if p < prev || p > bits.len() as u64 {
+                return Err(invalid_data(format!("coded sequence pointer {} out of order or beyond {} bits", p, bits.len())));
+            }
+            prev = p;
+        }
+        Ok(CodedSeq {
+            code,
+            k,
+            n,
+            bits,
+            pointers,
+        })
+    }
+}
+
+/// A set stored as the gaps between its elements, each stored with an integer
+/// [`Code`], such as a posting list.
+///
+/// Every `k`-th element is sampled along with the position of its code, so
+/// `rank` is a binary search over the samples followed by decoding at most `k`
+/// gaps, and `select` decodes at most `k` gaps. When the gaps are roughly
+/// geometric, as for randomly placed elements, a Golomb-Rice code with the
+/// parameter near `log2` of the mean gap is close to optimal, and smaller than
+/// a [`Sparse`](crate::sparse::Sparse) set.
+///
+/// #Examples
+///
+/// ```
+/// use crate::ransel::codes::{Code, CodedSet};
+/// use crate::ransel::iter::IterableSet;
+/// use crate::ransel::rank::Rank;
+/// use crate::ransel::select::Select;
+///
+/// let xs: Vec<u64> = vec![3, 9, 10, 40, 41, 42, 100, 1000];
+/// let s = CodedSet::new(1 << 10, Code::Gamma, 3, &xs);
+/// assert_eq!(s.rank(41), 4);
+/// assert_eq!(s.select(6), 100);
+/// assert_eq!(s.iter_from(50).collect::<Vec<u64>>(), vec![100, 1000]);
+/// ```
+pub struct CodedSet {
+    size_: u64,
+    gaps: CodedSeq,
+    samples: IntVec,
+}
+
+impl CodedSet {
+    /// Create a new set over the domain `[0, size_)` from strictly increasing
+    /// `elements`, storing the gaps with `code` and sampling every `k`-th element.
+    pub fn new(size_: u64, code: Code, k: usize, elements: &[u64]) -> CodedSet {
+        let mut gaps: Vec<u64> = Vec::with_capacity(elements.len());
+        let mut samples = IntVec::with_capacity(domain_bits(size_), elements.len().div_ceil(k));
+        for i in 0..elements.len() {
+            assert!(elements[i] < size_);
+            if i % k == 0 {
+                samples.push(elements[i]);
+            }
+            // The gap before a sampled element is not needed, but keeps the
+            // codes aligned with the positions of the elements.
+            gaps.push(match i {
+                0 => elements[0],
+                _ => {
+                    assert!(elements[i - 1] < elements[i]);
+                    elements[i] - elements[i - 1] - 1
+                }
+            });
+        }
+        CodedSet {
+            size_,
+            gaps: CodedSeq::new(code, k, &gaps),
+            samples,
+        }
+    }
+
+    /// Create a new set, or return an error if `elements` are not strictly
+    /// increasing values less than `size_`, `k` is zero, or the parameter of
+    /// `code` is not supported.
+    pub fn try_new(size_: u64, code: Code, k: usize, elements: &[u64]) -> Result<CodedSet> {
+        code.check()?;
+        check_param("k", k as u64, 1, u64::MAX)?;
+        check_elements(elements, 64)?;
+        if let Some(x) = elements.last() {
+            check_domain(*x, size_)?;
+        }
+        Ok(CodedSet::new(size_, code, k, elements))
+    }
+
+    /// Return the number of bits used by the codes of the gaps.
+    pub fn code_bits(&self) -> usize {
+        self.gaps.code_bits()
+    }
+}
+
+impl ImpliedSet for CodedSet {
+    fn count(&self) -> usize {
+        self.gaps.len()
+    }
+
+    fn size(&self) -> u64 {
+        self.size_
+    }
+}
+
+impl Rank for CodedSet {
+    fn rank(&self, value: u64) -> usize {
+        // The number of samples no greater than `value`.
+        let mut first = 0;
+        let mut count = self.samples.len();
+        while count > 0 {
+            let step = count / 2;
+            let j = first + step;
+            if self.samples.get(j) <= value {
+                first = j + 1;
+                count -= step + 1;
+            } else {
+                count = step;
+            }
+        }
+        if first == 0 {
+            return 0;
+        }
+        let j = first - 1;
+        let k = self.gaps.k;
+        let mut r = self.gaps.block(j);
+        r.read(self.gaps.code);
+        let mut x = self.samples.get(j);
+        let mut i = j * k;
+        while x < value {
+            i += 1;
+            if i == self.count() || i.is_multiple_of(k) {
+                break;
+            }
+            x += r.read(self.gaps.code) + 1;
+        }
+        i
+    }
+}
+
+impl Select for CodedSet {
+    fn select(&self, index: usize) -> u64 {
+        assert!(index < self.count());
+        let k = self.gaps.k;
+        let mut r = self.gaps.block(index / k);
+        r.read(self.gaps.code);
+        let mut x = self.samples.get(index / k);
+        for _j in 0..index % k {
+            x += r.read(self.gaps.code) + 1;
+        }
+        x
+    }
+}
+
+impl Successor for CodedSet {}
+
+/// An iterator over the elements of a [`CodedSet`].
+pub struct CodedSetIter<'a> {
+    reader: BitReader<'a>,
+    code: Code,
+    remaining: usize,
+    next: u64,
+}
+
+impl<'a> Iterator for CodedSetIter<'a> {
+    type Item = u64;
+
+    fn next(&mut self) -> Option<u64> {
+        if self.remaining == 0 {
+            return None;
+        }
+        let x = self.next;
+        self.remaining -= 1;
+        if self.remaining > 0 {
+            self.next += self.reader.read(self.code) + 1;
+        }
+        Some(x)
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (self.remaining, Some(self.remaining))
+    }
+}
+
+impl<'a> ExactSizeIterator for CodedSetIter<'a> {}
+
+impl IterableSet for CodedSet {
+    type Iter<'a> = CodedSetIter<'a>;
+
+    fn iter_from(&self, value: u64) -> Self::Iter<'_> {
+        let i = self.rank(value);
+        let mut iter = CodedSetIter {
+            reader: BitReader::new(self.gaps.bits.as_words()),
+            code: self.gaps.code,
+            remaining: self.count() - i,
+            next: 0,
+        };
+        if i < self.count() {
+            // Decode up to element `i`, leaving the reader at the next gap.
+            let k = self.gaps.k;
+            iter.reader = self.gaps.block(i / k);
+            iter.reader.read(self.gaps.code);
+            iter.next = self.samples.get(i / k);
+            for _j in 0..i % k {
+                iter.next += iter.reader.read(self.gaps.code) + 1;
+            }
+        }
+        iter
+    }
+}
+
+impl Persistent for CodedSet {
+    const TYPE_TAG: [u8; 4] = *b"CSET";
+
+    fn params(&self) -> Vec<u64> {
+        vec![self.size_]
+    }
+
+    fn save_body<Sink>(&self, sink: &mut Sink) -> std::io::Result<()>
+    where
+        Sink: std::io::Write,
+    {
+        self.gaps.save(sink)?;
+        self.samples.save(sink)?;
+        Ok(())
+    }
+
+    fn load_body<Source>(params: &[u64], source: &mut Source) -> std::io::Result<Self>
+    where
+        Source: std::io::Read,
+    {
+        check_params(&Self::TYPE_TAG, params, 1)?;
+        let size_: u64 = params[0];
+        let gaps: CodedSeq = *(CodedSeq::load(source)?);
+        let samples: IntVec = *(IntVec::load(source)?);
+        if samples.len() != gaps.len().div_ceil(gaps.k) {
+            return Err(invalid_data(String::from("inconsistent coded set")));
+        }
+        for j in 0..samples.len() {
+            let x = samples.get(j);
+            if x >= size_ || (j > 0 && x <= samples.get(j - 1)) {
+                return Err(invalid_data(format!("coded set sample {} out of order or beyond {}", x, size_)));
+            }
+        }
+        Ok(CodedSet { size_, gaps, samples })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::sparse::Sparse;
+    use random::Source;
+
+    #[test]
+    fn test_codes_1() {
+        let mut rng = random::default(19);
+        let codes = [
+            Code::Unary,
+            Code::Gamma,
+            Code::Delta,
+            Code::Rice(0),
+            Code::Rice(5),
+            Code::Zeta(1),
+            Code::Zeta(3),
+        ];
+        let mut xs: Vec<u64> = (0..300).collect();
+        xs.extend((0..300).map(|_i| rng.read_u64() >> (rng.read_u64() % 64)));
+        xs.push(u64::MAX - 1);
+        for code in codes {
+            let ys: Vec<u64> = match code {
+                Code::Unary | Code::Rice(0) => xs.iter().copied().filter(|x| *x < 1000).collect(),
+                Code::Rice(k) => xs.iter().copied().filter(|x| *x >> k < 1000).collect(),
+                _ => xs.clone(),
+            };
+            let mut w = BitWriter::new();
+            for y in ys.iter() {
+                w.write(code, *y);
+            }
+            let bits = w.finish();
+            let mut r = BitReader::new(bits.as_words());
+            for y in ys.iter() {
+                assert_eq!(r.read(code), *y, "{:?}", code);
+            }
+            assert_eq!(r.position(), bits.len());
+        }
+        // Gamma and zeta with k = 1 have the same lengths.
+        let mut w = BitWriter::new();
+        w.write(Code::Gamma, 1234);
+        let mut v = BitWriter::new();
+        v.write(Code::Zeta(1), 1234);
+        assert_eq!(w.len(), v.len());
+    }
+
+    #[test]
+    fn test_coded_seq_1() {
+        let mut rng = random::default(23);
+        let xs: Vec<u64> = (0..5000).map(|_i| rng.read_u64() % 1000).collect();
+        let s = CodedSeq::new(Code::Zeta(3), 16, &xs);
+        for (i, &x) in xs.iter().enumerate() {
+            assert_eq!(s.get(i), x);
+        }
+        assert_eq!(s.iter().collect::<Vec<u64>>(), xs);
+        assert!(s.try_get(5000).is_err());
+
+        let mut bytes: Vec<u8> = Vec::new();
+        s.save(&mut bytes).expect("save failed");
+        let t = CodedSeq::load(&mut bytes.as_slice()).expect("load failed");
+        assert_eq!(t.iter().collect::<Vec<u64>>(), xs);
+    }
+
+    #[test]
+    fn test_coded_set_1() {
+        // Geometric gaps with a mean of about 64.
+        let mut rng = random::default(29);
+        let mut xs: Vec<u64> = Vec::new();
+        let mut x: u64 = 0;
+        for _i in 0..20000 {
+            x += 1 + (-64.0 * ((rng.read_u64() >> 11) as f64 / (1u64 << 53) as f64 + 1e-12).ln()) as u64;
+            xs.push(x);
+        }
+        let size = x + 1;
+        let s = CodedSet::new(size, Code::Rice(6), 32, &xs);
+        for i in (0..xs.len()).step_by(7) {
+            assert_eq!(s.select(i), xs[i]);
+            assert_eq!(s.rank(xs[i]), i);
+            assert_eq!(s.rank(xs[i] + 1), i + 1);
+        }
+        assert_eq!(s.rank(size), xs.len());
+        assert_eq!(s.iter().collect::<Vec<u64>>(), xs);
+        for i in (1..xs.len()).step_by(997) {
+            assert_eq!(s.iter_from(xs[i - 1] + 1).collect::<Vec<u64>>(), xs[i..]);
+        }
+
+        let t = Sparse::new(domain_bits(size), &xs);
+        let sparse_bits = t.count() * (2 + t.size().div_ceil(t.count() as u64).ilog2() as usize);
+        assert!(s.code_bits() < sparse_bits);
+
+        let mut bytes: Vec<u8> = Vec::new();
+        s.save(&mut bytes).expect("save failed");
+        let u = CodedSet::load(&mut bytes.as_slice()).expect("load failed");
+        assert_eq!(u.iter().collect::<Vec<u64>>(), xs);
+    }
+
+    #[test]
+    fn test_try_new_1() {
+        use crate::error::Error;
+
+        let xs: Vec<u64> = vec![3, 9, 10];
+        assert!(CodedSeq::try_new(Code::Gamma, 2, &xs).is_ok());
+        assert!(matches!(CodedSeq::try_new(Code::Gamma, 0, &xs), Err(Error::InvalidParameter { name: "k", .. })));
+        assert!(CodedSeq::try_new(Code::Rice(64), 2, &xs).is_err());
+        assert!(CodedSeq::try_new(Code::Zeta(0), 2, &xs).is_err());
+
+        assert!(CodedSet::try_new(11, Code::Gamma, 2, &xs).is_ok());
+        assert!(matches!(CodedSet::try_new(11, Code::Gamma, 0, &xs), Err(Error::InvalidParameter { name: "k", .. })));
+        assert_eq!(CodedSet::try_new(10, Code::Gamma, 2, &xs).err(), Some(Error::OutOfDomain { value: 10, size: 10 }));
+        // Not truncated to a smaller size, or a smaller element, by conversion to usize.
+        assert_eq!(
+            CodedSet::try_new((1 << 32) + 5, Code::Gamma, 2, &[(1 << 32) + 7]).err(),
+            Some(Error::OutOfDomain { value: (1 << 32) + 7, size: (1 << 32) + 5 })
+        );
+        assert_eq!(CodedSet::try_new(11, Code::Gamma, 2, &[3, 3]).err(), Some(Error::NotIncreasing { index: 1 }));
+    }
+
+    #[test]
+    fn test_load_corrupt_1() {
+        use crate::persist::save_header;
+
+        let s = CodedSeq::new(Code::Gamma, 2, &[3, 9, 10, 40, 41]);
+        let save = |ptrs: &[u64]| {
+            let mut pointers = IntVec::new(8);
+            for p in ptrs.iter() {
+                pointers.push(*p);
+            }
+            let mut bytes: Vec<u8> = Vec::new();
+            save_header(&mut bytes, b"CSEQ", &[1, 0, 2, 5]).unwrap();
+            s.bits.save(&mut bytes).unwrap();
+            pointers.save(&mut bytes).unwrap();
+            bytes
+        };
+        let good: Vec<u64> = (0..s.pointers.len()).map(|j| s.pointers.get(j)).collect();
+        let bytes = save(&good);
+        let t = CodedSeq::load(&mut bytes.as_slice()).expect("load failed");
+        assert_eq!(t.iter().collect::<Vec<u64>>(), vec![3, 9, 10, 40, 41]);
+        // Pointers out of order, or past the end of the codes.
+        let bytes = save(&[0, good[2], good[1]]);
+        assert!(CodedSeq::load(&mut bytes.as_slice()).is_err());
+        let bytes = save(&[0, good[1], s.bits.len() as u64 + 1]);
+        assert!(CodedSeq::load(&mut bytes.as_slice()).is_err());
+    }
+
+    #[test]
+    fn test_load_corrupt_2() {
+        use crate::persist::save_header;
+
+        let xs: Vec<u64> = vec![3, 9, 10, 40, 41];
+        let s = CodedSet::new(50, Code::Gamma, 2, &xs);
+        let save = |smps: &[u64]| {
+            let mut samples = IntVec::new(domain_bits(50));
+            for x in smps.iter() {
+                samples.push(*x);
+            }
+            let mut bytes: Vec<u8> = Vec::new();
+            save_header(&mut bytes, b"CSET", &[50]).unwrap();
+            s.gaps.save(&mut bytes).unwrap();
+            samples.save(&mut bytes).unwrap();
+            bytes
+        };
+        let bytes = save(&[3, 10, 41]);
+        let t = CodedSet::load(&mut bytes.as_slice()).expect("load failed");
+        assert_eq!(t.iter().collect::<Vec<u64>>(), xs);
+        // Samples out of order, beyond the domain, or too few for the gaps.
+        let bytes = save(&[3, 41, 10]);
+        assert!(CodedSet::load(&mut bytes.as_slice()).is_err());
+        let bytes = save(&[3, 10, 50]);
+        assert!(CodedSet::load(&mut bytes.as_slice()).is_err());
+        let bytes = save(&[3, 10]);
+        assert!(CodedSet::load(&mut bytes.as_slice()).is_err());
+    }
+}
@@ -0,0 +1,345 @@
+//! A compressed suffix array, based on the Sadakane 2003 paper:
+//!
+//! > Sadakane, K., 2003. New text indexing functionalities of the compressed
+//! > suffix arrays. Journal of Algorithms, 48(2), pp. 294-313.
+
+use crate::{
+    algebra::domain_bits,
+    dense64::Dense64,
+    intvec::IntVec,
+    persist::{check_params, invalid_data, load_vec_u64, save_vec, to_usize, Persistent},
+    rank::Rank,
+    sais::suffix_array,
+    select::Select,
+    set::ImpliedSet,
+    sparse::{Sparse, SparseBuilder},
+};
+
+/// The `Csa` data structure is a compressed suffix array of a text, which
+/// finds the occurrences of patterns and extracts substrings of the text.
+///
+/// The suffix array is represented by the function `psi`, which maps the row of
+/// the suffix starting at position `p` to the row of the suffix starting at
+/// `p + 1`. Within the rows of suffixes starting with the same symbol, `psi` is
+/// increasing, so with the symbol `c` of each row added in as `c * n + psi(i)`,
+/// it becomes a single increasing sequence, stored in a [`Sparse`] set. Each
+/// `select` on it gives both `psi(i)` and the first symbol of row `i`, so
+/// patterns are found by binary search over the rows, reading the suffixes
+/// forwards. As in the [`FmIndex`](crate::fmindex::FmIndex), the symbols are
+/// replaced by their ranks in the alphabet of the text, plus 1 to make room
+/// for the terminator 0, and every `rate`-th position of the suffix array and
+/// its inverse is sampled.
+///
+/// #Examples
+///
+/// ```
+/// use crate::ransel::csa::Csa;
+///
+/// let csa = Csa::new("mississippi".as_bytes(), 4);
+/// assert_eq!(csa.lookup("ssi".as_bytes()), 10..12);
+/// assert_eq!(csa.sa(10), 5);
+/// assert_eq!(csa.isa(5), 10);
+/// assert_eq!(csa.locate("ssi".as_bytes()), vec![2, 5]);
+/// assert_eq!(csa.extract(4..8), "issi".bytes().map(|c| c as u64).collect::<Vec<u64>>());
+/// ```
+pub struct Csa {
+    n: usize,
+    rate: usize,
+    alphabet: Vec<u64>,
+    psi: Sparse,
+    sampled: Dense64,
+    samples: IntVec,
+    inverse: IntVec,
+}
+
+impl Csa {
+    /// Create a compressed suffix array of `text`, sampling every `rate`-th position.
+    pub fn new<S: Copy + Into<u64>>(text: &[S], rate: usize) -> Csa {
+        assert!(rate >= 1);
+        let mut alphabet: Vec<u64> = text.iter().map(|c| (*c).into()).collect();
+        alphabet.sort();
+        alphabet.dedup();
+        let mut symbols: Vec<usize> = text
+            .iter()
+            .map(|c| alphabet.partition_point(|a| *a < (*c).into()) + 1)
+            .collect();
+        symbols.push(0);
+        let sigma = alphabet.len() + 1;
+        let sa = suffix_array(&symbols, sigma);
+        let n = symbols.len();
+        let mut isa = vec![0usize; n];
+        for (i, p) in sa.iter().enumerate() {
+            isa[*p] = i;
+        }
+
+        let mut psi = SparseBuilder::new(domain_bits((sigma * n) as u64), n);
+        for p in sa.iter() {
+            psi.push((symbols[*p] * n + isa[(p + 1) % n]) as u64);
+        }
+
+        let width = domain_bits(n as u64);
+        let mut sampled_rows: Vec<u64> = Vec::new();
+        let mut samples = IntVec::with_capacity(width, n.div_ceil(rate) + 1);
+        for (i, p) in sa.iter().enumerate() {
+            // The terminator is sampled too, so `sa` never walks past the end.
+            if p % rate == 0 || *p == n - 1 {
+                sampled_rows.push(i as u64);
+                samples.push(*p as u64);
+            }
+        }
+        let mut inverse = IntVec::with_capacity(width, n.div_ceil(rate));
+        for p in (0..n).step_by(rate) {
+            inverse.push(isa[p] as u64);
+        }
+
+        Csa {
+            n,
+            rate,
+            alphabet,
+            psi: psi.finish(),
+            sampled: Dense64::from_positions(n as u64, &sampled_rows),
+            samples,
+            inverse,
+        }
+    }
+
+    /// Return the length of the text.
+    pub fn len(&self) -> usize {
+        self.n - 1
+    }
+
+    /// Return true if the text is empty.
+    pub fn is_empty(&self) -> bool {
+        self.n == 1
+    }
+
+    /// Return the first symbol of the suffix in row `i`, and the row of the next suffix.
+    fn step(&self, i: usize) -> (u64, usize) {
+        let x = self.psi.select(i);
+        (x / self.n as u64, (x % self.n as u64) as usize)
+    }
+
+    /// Return the row of the suffix starting one position after the suffix in row `i`.
+    pub fn psi(&self, i: usize) -> usize {
+        self.step(i).1
+    }
+
+    /// Return the text position of the suffix in row `i` of the suffix array.
+    pub fn sa(&self, i: usize) -> usize {
+        let mut i = i;
+        let mut steps = 0;
+        while !self.sampled.contains(i as u64) {
+            i = self.psi(i);
+            steps += 1;
+        }
+        self.samples.get(self.sampled.rank(i as u64)) as usize - steps
+    }
+
+    /// Return the row of the suffix array for the suffix starting at position `p`.
+    pub fn isa(&self, p: usize) -> usize {
+        assert!(p < self.n);
+        let mut i = self.inverse.get(p / self.rate) as usize;
+        for _j in 0..p % self.rate {
+            i = self.psi(i);
+        }
+        i
+    }
+
+    /// Compare the suffix in row `i` with `pattern`, given as symbol ranks,
+    /// considering only as many symbols as are in the pattern.
+    fn compare(&self, i: usize, pattern: &[u64]) -> std::cmp::Ordering {
+        let mut i = i;
+        for c in pattern.iter() {
+            let (d, next) = self.step(i);
+            match d.cmp(c) {
+                std::cmp::Ordering::Equal => i = next,
+                ord => return ord,
+            }
+        }
+        std::cmp::Ordering::Equal
+    }
+
+    /// Return the range of rows of the suffix array whose suffixes start with `pattern`.
+    pub fn lookup<S: Copy + Into<u64>>(&self, pattern: &[S]) -> std::ops::Range<usize> {
+        let mut ranks: Vec<u64> = Vec::with_capacity(pattern.len());
+        for c in pattern.iter() {
+            match self.alphabet.binary_search(&(*c).into()) {
+                Ok(r) => ranks.push(r as u64 + 1),
+                Err(_) => return 0..0,
+            }
+        }
+        let lower = partition_point(self.n, |i| self.compare(i, &ranks).is_lt());
+        let upper = partition_point(self.n, |i| self.compare(i, &ranks).is_le());
+        lower..upper
+    }
+
+    /// Return the number of occurrences of `pattern` in the text.
+    pub fn count<S: Copy + Into<u64>>(&self, pattern: &[S]) -> usize {
+        self.lookup(pattern).len()
+    }
+
+    /// Return the positions of the occurrences of `pattern` in the text, in
+    /// increasing order.
+    pub fn locate<S: Copy + Into<u64>>(&self, pattern: &[S]) -> Vec<usize> {
+        let mut positions: Vec<usize> = self.lookup(pattern).map(|i| self.sa(i)).collect();
+        positions.sort();
+        positions
+    }
+
+    /// Return the symbols of the text in `range`.
+    pub fn extract(&self, range: std::ops::Range<usize>) -> Vec<u64> {
+        assert!(range.start <= range.end && range.end <= self.len());
+        let mut res: Vec<u64> = Vec::with_capacity(range.end - range.start);
+        let mut i = self.isa(range.start);
+        for _p in range {
+            let (c, next) = self.step(i);
+            res.push(self.alphabet[c as usize - 1]);
+            i = next;
+        }
+        res
+    }
+}
+
+/// Return the first index in `[0, n)` for which `pred` is false, given that
+/// `pred` is true for a prefix of the indexes and false for the rest.
+fn partition_point<F: Fn(usize) -> bool>(n: usize, pred: F) -> usize {
+    let mut first = 0;
+    let mut count = n;
+    while count > 0 {
+        let step = count / 2;
+        let i = first + step;
+        if pred(i) {
+            first = i + 1;
+            count -= step + 1;
+        } else {
+            count = step;
+        }
+    }
+    first
+}
+
+impl Persistent for Csa {
+    const TYPE_TAG: [u8; 4] = *b"CSAP";
+
+    fn params(&self) -> Vec<u64> {
+        vec![self.n as u64, self.rate as u64]
+    }
+
+    fn save_body<Sink>(&self, sink: &mut Sink) -> std::io::Result<()>
+    where
+        Sink: std::io::Write,
+    {
+        save_vec(sink, &self.alphabet)?;
+        self.psi.save(sink)?;
+        self.sampled.save(sink)?;
+        self.samples.save(sink)?;
+        self.inverse.save(sink)?;
+        Ok(())
+    }
+
+    fn load_body<Source>(params: &[u64], source: &mut Source) -> std::io::Result<Self>
+    where
+        Source: std::io::Read,
+    {
+        check_params(&Self::TYPE_TAG, params, 2)?;
+        let n: usize = to_usize(params[0])?;
+        let rate: usize = to_usize(params[1])?;
+        let alphabet = load_vec_u64(source)?;
+        let psi: Sparse = *(Sparse::load(source)?);
+        let sampled: Dense64 = *(Dense64::load(source)?);
+        let samples: IntVec = *(IntVec::load(source)?);
+        let inverse: IntVec = *(IntVec::load(source)?);
+        if n == 0
+            || rate == 0
+            || alphabet.windows(2).any(|w| w[0] >= w[1])
+            || psi.count() != n
+            || sampled.size() != n as u64
+            || samples.len() != sampled.count()
+            || inverse.len() != n.div_ceil(rate)
+        {
+            return Err(invalid_data(String::from("inconsistent compressed suffix array")));
+        }
+        Ok(Csa {
+            n,
+            rate,
+            alphabet,
+            psi,
+            sampled,
+            samples,
+            inverse,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use random::Source;
+
+    fn make_text(n: usize, seed: u64) -> Vec<u8> {
+        let mut rng = random::default(seed);
+        (0..n).map(|_i| b"ACGT"[(rng.read_u64() % 4) as usize]).collect()
+    }
+
+    #[test]
+    fn test_sa_isa_1() {
+        let text = make_text(2000, 19);
+        let csa = Csa::new(&text, 7);
+        let mut sa: Vec<usize> = (0..=text.len()).collect();
+        sa.sort_by(|a, b| text[*a..].cmp(&text[*b..]));
+        for (i, &p) in sa.iter().enumerate() {
+            assert_eq!(csa.sa(i), p);
+            assert_eq!(csa.isa(p), i);
+        }
+    }
+
+    #[test]
+    fn test_lookup_1() {
+        let text = make_text(5000, 23);
+        let csa = Csa::new(&text, 8);
+        assert_eq!(csa.len(), text.len());
+        for len in [1, 3, 8, 20] {
+            for p in (0..text.len() - len).step_by(89) {
+                let pattern = &text[p..p + len];
+                let expected: Vec<usize> = (0..=text.len() - len)
+                    .filter(|q| text[*q..].starts_with(pattern))
+                    .collect();
+                assert_eq!(csa.count(pattern), expected.len());
+                assert_eq!(csa.locate(pattern), expected);
+            }
+        }
+        assert_eq!(csa.count("ACGTN".as_bytes()), 0);
+        let expected: Vec<u64> = text.iter().map(|c| *c as u64).collect();
+        assert_eq!(csa.extract(0..text.len()), expected);
+        assert_eq!(csa.extract(1234..1300), expected[1234..1300]);
+    }
+
+    #[test]
+    fn test_large_symbols_1() {
+        // The alphabet is the distinct symbols, however large they are.
+        let text: Vec<u64> = vec![u64::MAX, 1 << 40, u64::MAX, 7, 1 << 40, u64::MAX];
+        let csa = Csa::new(&text, 2);
+        assert_eq!(csa.alphabet, vec![7, 1 << 40, u64::MAX]);
+        assert_eq!(csa.locate(&[u64::MAX]), vec![0, 2, 5]);
+        assert_eq!(csa.locate(&[1 << 40, u64::MAX]), vec![1, 4]);
+        assert_eq!(csa.count(&[8u64]), 0);
+        assert_eq!(csa.extract(0..6), text);
+    }
+
+    #[test]
+    fn test_load_and_save_1() {
+        let text = make_text(3000, 29);
+        let csa = Csa::new(&text, 16);
+        let mut bytes: Vec<u8> = Vec::new();
+        csa.save(&mut bytes).expect("save failed");
+        let other = Csa::load(&mut bytes.as_slice()).expect("load failed");
+        assert_eq!(other.len(), csa.len());
+        for i in 0..=text.len() {
+            assert_eq!(other.psi(i), csa.psi(i));
+            assert_eq!(other.sa(i), csa.sa(i));
+            assert_eq!(other.isa(i), csa.isa(i));
+        }
+        assert_eq!(other.lookup("GATTACA".as_bytes()), csa.lookup("GATTACA".as_bytes()));
+    }
+}
@@ -0,0 +1,326 @@
+//! A vector of variable length integers using Directly Addressable Codes, due to
+//! Brisaboa, Ladra and Navarro:
+//!
+//! > Brisaboa, N.R., Ladra, S. and Navarro, G., 2013. DACs: Bringing direct
+//! > access to variable-length codes. Information Processing & Management,
+//! > 49(1), pp. 392-404.
+
+use crate::{
+    bitvec::BitVec,
+    dense64::Dense64,
+    error::{check_index, Result},
+    intvec::IntVec,
+    persist::{check_params, invalid_data, to_usize, Persistent},
+    rank::Rank,
+    set::ImpliedSet,
+};
+
+/// One level of a [`Dac`]: a chunk of each value reaching this level, and
+/// which of them continue to the next level.
+struct Level {
+    chunks: IntVec,
+    more: Dense64,
+}
+
+/// A vector of unsigned integers, each stored in a number of bits close to its length.
+///
+/// Each value is split into chunks, from the least significant bits up, and the
+/// `k`-th chunks of all the values long enough to have one are stored together
+/// in level `k`. A bit vector at each level records which values continue to
+/// the next level, and `rank` over it gives a value's position in that level,
+/// so `get` takes one `rank` per chunk. The chunk widths are chosen to minimise
+/// the total space, so a few large values don't make every value wide, as they
+/// do in an [`IntVec`].
+///
+/// #Examples
+///
+/// ```
+/// use crate::ransel::dac::Dac;
+///
+/// let xs: Vec<u64> = vec![3, 0, 1, 2, 1 << 40, 7, 300, 1];
+/// let v = Dac::new(&xs);
+/// assert_eq!(v.len(), 8);
+/// for i in 0..xs.len() {
+///     assert_eq!(v.get(i), xs[i]);
+/// }
+/// assert_eq!(v.iter().collect::<Vec<u64>>(), xs);
+/// ```
+pub struct Dac {
+    n: usize,
+    levels: Vec<Level>,
+}
+
+/// Return the number of bits needed to represent `x`.
+fn bit_length(x: u64) -> usize {
+    (u64::BITS - x.leading_zeros()) as usize
+}
+
+/// Choose the chunk widths minimising the total space for `values`.
+fn optimal_widths(values: &[u64]) -> Vec<usize> {
+    let max_len = values.iter().map(|x| bit_length(*x)).max().unwrap_or(0).max(1);
+    // longer[s] is the number of values with a chunk starting at bit `s`.
+    let mut longer = vec![0usize; max_len + 1];
+    for x in values.iter() {
+        for count in longer[..bit_length(*x)].iter_mut() {
+            *count += 1;
+        }
+    }
+    longer[0] = values.len();
+    // best[s] is the cheapest encoding of the bits from `s` up, and next[s] the
+    // width of the first chunk in it.
+    let mut best = vec![0usize; max_len + 1];
+    let mut next = vec![0usize; max_len + 1];
+    for s in (0..max_len).rev() {
+        best[s] = usize::MAX;
+        for w in 1..=max_len - s {
+            let more = if s + w < max_len { 1 } else { 0 };
+            let cost = longer[s] * (w + more) + best[s + w];
+            if cost < best[s] {
+                best[s] = cost;
+                next[s] = w;
+            }
+        }
+    }
+    let mut widths: Vec<usize> = Vec::new();
+    let mut s = 0;
+    while s < max_len {
+        widths.push(next[s]);
+        s += next[s];
+    }
+    widths
+}
+
+impl Dac {
+    /// Create a new vector, choosing the chunk widths to minimise its size.
+    pub fn new(values: &[u64]) -> Dac {
+        Dac::with_widths(&optimal_widths(values), values)
+    }
+
+    /// Create a new vector with the given chunk widths, from the least
+    /// significant chunk up.
+    ///
+    /// The widths must be at least 1 and add up to at least the length of the
+    /// largest value.
+    pub fn with_widths(widths: &[usize], values: &[u64]) -> Dac {
+        assert!(widths.iter().all(|w| *w >= 1));
+        let mut levels: Vec<Level> = Vec::with_capacity(widths.len());
+        let mut current: Vec<u64> = Vec::from(values);
+        for (l, w) in widths.iter().enumerate() {
+            let last = l + 1 == widths.len();
+            let mut chunks = IntVec::with_capacity(*w, current.len());
+            let mut more = BitVec::with_capacity(if last { 0 } else { current.len() });
+            let mut rest: Vec<u64> = Vec::new();
+            for x in current.iter() {
+                let (low, high) = if *w >= 64 { (*x, 0) } else { (x & ((1u64 << w) - 1), x >> w) };
+                chunks.push(low);
+                if last {
+                    assert_eq!(high, 0);
+                } else {
+                    more.push(high != 0);
+                    if high != 0 {
+                        rest.push(high);
+                    }
+                }
+            }
+            levels.push(Level {
+                chunks,
+                more: Dense64::from_bitvec(more),
+            });
+            current = rest;
+        }
+        Dac {
+            n: values.len(),
+            levels,
+        }
+    }
+
+    /// Return the number of values.
+    pub fn len(&self) -> usize {
+        self.n
+    }
+
+    /// Return true if there are no values.
+    pub fn is_empty(&self) -> bool {
+        self.n == 0
+    }
+
+    /// Return the chunk width of each level.
+    pub fn widths(&self) -> Vec<usize> {
+        self.levels.iter().map(|level| level.chunks.width()).collect()
+    }
+
+    /// Return the number of bits used by the chunks and continuation bits.
+    pub fn bits(&self) -> usize {
+        self.levels
+            .iter()
+            .map(|level| level.chunks.len() * level.chunks.width() + level.more.size() as usize)
+            .sum()
+    }
+
+    /// Return the value at position `idx`.
+    pub fn get(&self, idx: usize) -> u64 {
+        assert!(idx < self.n);
+        let mut j = idx;
+        let mut value = 0;
+        let mut shift = 0;
+        for level in self.levels.iter() {
+            value |= level.chunks.get(j) << shift;
+            shift += level.chunks.width();
+            if (j as u64) >= level.more.size() || !level.more.contains(j as u64) {
+                break;
+            }
+            j = level.more.rank(j as u64);
+        }
+        value
+    }
+
+    /// Return the value at position `idx`, or an error if `idx` is out of range.
+    pub fn try_get(&self, idx: usize) -> Result<u64> {
+        check_index(idx, self.n)?;
+        Ok(self.get(idx))
+    }
+
+    /// Return an iterator over the values, which decodes them sequentially
+    /// without using `rank`.
+    pub fn iter(&self) -> DacIter<'_> {
+        DacIter {
+            dac: self,
+            index: 0,
+            cursors: vec![0; self.levels.len()],
+        }
+    }
+}
+
+/// An iterator over the values of a [`Dac`].
+pub struct DacIter<'a> {
+    dac: &'a Dac,
+    index: usize,
+    cursors: Vec<usize>,
+}
+
+impl<'a> Iterator for DacIter<'a> {
+    type Item = u64;
+
+    fn next(&mut self) -> Option<u64> {
+        if self.index >= self.dac.n {
+            return None;
+        }
+        self.index += 1;
+        let mut value = 0;
+        let mut shift = 0;
+        for (l, level) in self.dac.levels.iter().enumerate() {
+            let j = self.cursors[l];
+            self.cursors[l] += 1;
+            value |= level.chunks.get(j) << shift;
+            shift += level.chunks.width();
+            if (j as u64) >= level.more.size() || !level.more.contains(j as u64) {
+                break;
+            }
+        }
+        Some(value)
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        let remaining = self.dac.n - self.index;
+        (remaining, Some(remaining))
+    }
+}
+
+impl<'a> ExactSizeIterator for DacIter<'a> {}
+
+impl Persistent for Dac {
+    const TYPE_TAG: [u8; 4] = *b"DACV";
+
+    fn params(&self) -> Vec<u64> {
+        vec![self.n as u64, self.levels.len() as u64]
+    }
+
+    fn save_body<Sink>(&self, sink: &mut Sink) -> std::io::Result<()>
+    where
+        Sink: std::io::Write,
+    {
+        for level in self.levels.iter() {
+            level.chunks.save(sink)?;
+            level.more.save(sink)?;
+        }
+        Ok(())
+    }
+
+    fn load_body<Source>(params: &[u64], source: &mut Source) -> std::io::Result<Self>
+    where
+        Source: std::io::Read,
+    {
+        check_params(&Self::TYPE_TAG, params, 2)?;
+        let n: usize = to_usize(params[0])?;
+        let level_count: usize = to_usize(params[1])?;
+        let mut levels: Vec<Level> = Vec::new();
+        let mut expected = n;
+        for l in 0..level_count {
+            let chunks: IntVec = *(IntVec::load(source)?);
+            let more: Dense64 = *(Dense64::load(source)?);
+            let last = l + 1 == level_count;
+            if chunks.len() != expected || (!last && more.size() != expected as u64) {
+                return Err(invalid_data(String::from("inconsistent DAC levels")));
+            }
+            expected = more.count();
+            levels.push(Level { chunks, more });
+        }
+        Ok(Dac { n, levels })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use random::Source;
+
+    /// Make values skewed towards small numbers, with a few large ones.
+    fn make_values(n: usize, seed: u64) -> Vec<u64> {
+        let mut rng = random::default(seed);
+        (0..n)
+            .map(|_i| {
+                let x = rng.read_u64();
+                match x % 100 {
+                    0 => x >> 8,
+                    1..=10 => x % 5000,
+                    _ => x % 40,
+                }
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_get_1() {
+        let xs = make_values(10000, 19);
+        let v = Dac::new(&xs);
+        for (i, &x) in xs.iter().enumerate() {
+            assert_eq!(v.get(i), x);
+        }
+        assert_eq!(v.iter().collect::<Vec<u64>>(), xs);
+        assert_eq!(v.try_get(xs.len()).unwrap_err().to_string(), "index 10000 is out of range for length 10000");
+        // Much smaller than a fixed width vector.
+        assert!(2 * v.bits() < 56 * xs.len());
+
+        let w = Dac::with_widths(&[4, 4, 8, 64], &xs);
+        assert_eq!(w.widths(), vec![4, 4, 8, 64]);
+        assert_eq!(w.iter().collect::<Vec<u64>>(), xs);
+        let zeros = Dac::new(&[0, 0, 0]);
+        assert_eq!(zeros.widths(), vec![1]);
+        assert_eq!(zeros.iter().collect::<Vec<u64>>(), vec![0, 0, 0]);
+        assert_eq!(Dac::new(&[u64::MAX, 1]).get(0), u64::MAX);
+    }
+
+    #[test]
+    fn test_load_and_save_1() {
+        let xs = make_values(5000, 23);
+        let v = Dac::new(&xs);
+        let mut bytes: Vec<u8> = Vec::new();
+        v.save(&mut bytes).expect("save failed");
+        let w = Dac::load(&mut bytes.as_slice()).expect("load failed");
+        assert_eq!(w.widths(), v.widths());
+        for (i, &x) in xs.iter().enumerate() {
+            assert_eq!(w.get(i), x);
+        }
+    }
+}
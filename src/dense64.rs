@@ -1,7 +1,17 @@
+//! An indexed dense set based on a bit vector of 64 bit words.
+//!
+//! Unlike [`NaiveDense`](crate::naive_dense::NaiveDense), which scans the
+//! underlying words for every query, `Dense64` keeps a cumulative count for
+//! every word, and a sample of the word containing every 1024th element, so
+//! that `rank` is constant time and `select` is close to constant time.
+
 use crate::{
-    persist::{load_u64, load_vec_u32, load_vec_u64, save_vec, Persistent},
+    algebra::FromSorted,
+    bitvec::{AsWords, BitVec, FromBitVec},
+    iter::{BitIter, IterableSet},
+    persist::{check_params, invalid_data, load_vec_u32, load_vec_u64, save_vec, ByteReader, Persistent, View},
     rank::Rank,
-    select::{Select, Select0},
+    select::{Select, Select0, Successor, Successor0},
     set::ImpliedSet,
     words::{rank64, select64},
 };
@@ -36,27 +46,109 @@ static BLOCK_BITS: usize = 10;
 //static BLOCK_SIZE: usize = 1 << BLOCK_BITS;
 //static BLOCK_MASK: usize = BLOCK_SIZE - 1;
 
+/// A dense set represented by a bit vector with rank and select indexes.
+///
+/// The rank index (`randex`) records the number of elements preceding each
+/// word, and the select index (`seldex`) records the word containing every
+/// 1024th element. The optional select-0 index (`zeldex`), built by
+/// [`with_select_0_index`](Dense64::with_select_0_index), records the word
+/// containing every 1024th non-element, making `select_0` as fast as `select`.
+/// Without it, `select_0` binary searches the rank index.
+///
+/// The storage for the words and indexes is a type parameter, so the same
+/// structure can own its vectors or borrow them from a saved structure (see
+/// [`Dense64View`]).
+///
+/// #Examples
+///
+/// ```
+/// use crate::ransel::dense64::Dense64;
+/// use crate::ransel::rank::Rank;
+/// use crate::ransel::select::{Select, Select0};
+///
+/// let xs: Vec<u64> = vec![1, 3, 64, 100, 1000];
+/// let s = Dense64::from_positions(1024, &xs);
+/// for i in 0..xs.len() {
+///     assert_eq!(s.select(i), xs[i]);
+///     assert_eq!(s.rank(xs[i]), i);
+/// }
+/// assert_eq!(s.select_0(1), 2);
+///
+/// let t: Dense64 = [true, false, false, true].into_iter().collect();
+/// assert_eq!(t.select(1), 3);
+/// ```
 #[derive(Debug)]
-pub struct Dense64 {
+pub struct Dense64<W = Vec<u64>, I = Vec<u32>> {
     size_: u64,
-    words: Vec<u64>,
-    randex: Vec<u32>,
-    seldex: Vec<u32>,
+    words: W,
+    randex: I,
+    seldex: I,
+    zeldex: I,
 }
 
+/// A [`Dense64`] borrowing its words and indexes from a byte buffer, such as a
+/// memory mapped file, written by [`Persistent::save`].
+///
+/// #Examples
+///
+/// ```
+/// use crate::ransel::dense64::{Dense64, Dense64View};
+/// use crate::ransel::persist::{Persistent, View};
+/// use crate::ransel::select::Select;
+///
+/// let s = Dense64::from_positions(1024, &[1, 3, 64, 100, 1000]);
+/// let mut bytes: Vec<u8> = Vec::new();
+/// s.save(&mut bytes).expect("save failed");
+///
+/// // Views need an 8 byte aligned buffer, which a memory mapped file provides.
+/// let mut words: Vec<u64> = vec![0; bytes.len() / 8];
+/// for (i, b) in bytes.iter().enumerate() {
+///     words[i / 8] |= (*b as u64) << (8 * (i % 8));
+/// }
+/// let aligned = unsafe { std::slice::from_raw_parts(words.as_ptr() as *const u8, bytes.len()) };
+///
+/// let v = Dense64View::view(aligned).expect("view failed");
+/// assert_eq!(v.select(3), 100);
+/// ```
+pub type Dense64View<'a> = Dense64<&'a [u64], &'a [u32]>;
+
 impl Dense64 {
+    /// Create a new dense set over the domain `[0, size_)` from the words of a bit vector.
+    ///
+    /// Bit `j` of `words[i]` represents the element `64 * i + j`. Bits at or
+    /// beyond `size_` must be zero.
     pub fn new(size_: u64, words: &[u64]) -> Dense64 {
+        Dense64::from_words(size_, Vec::from(words))
+    }
+
+    /// Create a new dense set from a bit vector, taking ownership of its words.
+    pub fn from_bitvec(bits: BitVec) -> Dense64 {
+        let size_ = bits.len() as u64;
+        Dense64::from_words(size_, bits.into_words())
+    }
+
+    /// Create a new dense set over the domain `[0, size_)` from a sorted slice of positions.
+    ///
+    /// The positions must be in sorted order and less than `size_`.
+    pub fn from_positions(size_: u64, positions: &[u64]) -> Dense64 {
+        let mut words: Vec<u64> = vec![0; size_.div_ceil(64) as usize];
+        for x in positions.iter() {
+            assert!(*x < size_);
+            words[(*x / 64) as usize] |= 1u64 << (*x & 63);
+        }
+        Dense64::from_words(size_, words)
+    }
+
+    fn from_words(size_: u64, words: Vec<u64>) -> Dense64 {
         std::debug_assert!(size_ / 64 <= (words.len() as u64));
-        let words: Vec<u64> = Vec::from(words);
 
-        let mut randex: Vec<u32> = Vec::new();
-        randex.resize(words.len() + 1, 0);
+        let mut randex: Vec<u32> = vec![0; words.len() + 1];
         let mut count: u32 = 0;
         for i in 0..words.len() {
             let x = words[i];
             let m = x.count_ones();
             randex[i] = count;
-            count += m as u32;
+            count += m;
         }
         randex[words.len()] = count;
 
@@ -74,13 +166,98 @@ impl Dense64 {
             words,
             randex,
             seldex,
+            zeldex: Vec::new(),
+        }
+    }
+
+    /// Build the select-0 index, which makes `select_0` near constant time at
+    /// a cost of 32 bits per 1024 non-elements.
+    ///
+    /// #Examples
+    ///
+    /// ```
+    /// use crate::ransel::dense64::Dense64;
+    /// use crate::ransel::select::Select0;
+    ///
+    /// let s = Dense64::from_positions(1024, &[1, 3, 64, 100, 1000]).with_select_0_index();
+    /// assert_eq!(s.select_0(1), 2);
+    /// ```
+    pub fn with_select_0_index(mut self) -> Dense64 {
+        let mut zeldex: Vec<u32> = Vec::new();
+        let mut zeros: usize = 0;
+        for i in 0..self.words.len() {
+            let bits = (self.size_ - (64 * i as u64).min(self.size_)).min(64) as usize;
+            zeros += bits - self.words[i].count_ones() as usize;
+            while zeldex.len() << BLOCK_BITS < zeros {
+                zeldex.push(i as u32);
+            }
+        }
+        self.zeldex = zeldex;
+        self
+    }
+}
+
+impl FromIterator<bool> for Dense64 {
+    fn from_iter<I: IntoIterator<Item = bool>>(iter: I) -> Self {
+        let mut bits = BitVec::new();
+        for bit in iter {
+            bits.push(bit);
         }
+        Dense64::from_bitvec(bits)
     }
 }
 
-impl ImpliedSet for Dense64 {
+impl<W: AsRef<[u64]>, I: AsRef<[u32]>> Dense64<W, I> {
+    /// Return the underlying words of the bit vector.
+    pub fn as_words(&self) -> &[u64] {
+        self.words.as_ref()
+    }
+
+    /// Check that the words and indexes of a loaded set agree with its size,
+    /// so that queries stay in bounds. The rank index is always checked to
+    /// grow by at most 64 per word, and the select indexes to start at or
+    /// before the word they refer to, but the rank index is checked against
+    /// the words only if `counts` is true, since that reads every word.
+    fn check_loaded(&self, counts: bool) -> std::io::Result<()> {
+        let words = self.words.as_ref();
+        let randex = self.randex.as_ref();
+        let in_range = |xs: &[u32]| xs.iter().all(|i| (*i as usize) < words.len());
+        if words.len() as u64 != self.size_.div_ceil(64)
+            || randex.len() != words.len() + 1
+            || self.seldex.as_ref().len() as u64 != (randex[words.len()] as u64).div_ceil(1 << BLOCK_BITS)
+            || !in_range(self.seldex.as_ref())
+            || !in_range(self.zeldex.as_ref())
+        {
+            return Err(invalid_data(String::from("inconsistent Dense64 index")));
+        }
+        let zeros_before = |i: usize| 64 * i - randex[i] as usize;
+        if randex[0] != 0
+            || randex.windows(2).any(|w| w[1] < w[0] || w[1] - w[0] > 64)
+            || self.seldex.as_ref().iter().enumerate().any(|(k, i)| randex[*i as usize] as usize > k << BLOCK_BITS)
+            || self.zeldex.as_ref().iter().enumerate().any(|(k, i)| zeros_before(*i as usize) > k << BLOCK_BITS)
+        {
+            return Err(invalid_data(String::from("inconsistent Dense64 rank or select index")));
+        }
+        if counts {
+            let mut count: u64 = 0;
+            for (i, w) in words.iter().enumerate() {
+                if randex[i] as u64 != count {
+                    return Err(invalid_data(String::from("inconsistent Dense64 rank index")));
+                }
+                count += w.count_ones() as u64;
+            }
+            if randex[words.len()] as u64 != count {
+                return Err(invalid_data(String::from("inconsistent Dense64 rank index")));
+            }
+        }
+        Ok(())
+    }
+}
+
+impl<W: AsRef<[u64]>, I: AsRef<[u32]>> ImpliedSet for Dense64<W, I> {
     fn count(&self) -> usize {
-        self.randex[self.randex.len() - 1] as usize
+        let randex = self.randex.as_ref();
+        randex[randex.len() - 1] as usize
     }
 
     fn size(&self) -> u64 {
@@ -88,56 +265,269 @@ impl ImpliedSet for Dense64 {
     }
 }
 
-impl Rank for Dense64 {
+impl<W: AsRef<[u64]>, I: AsRef<[u32]>> Rank for Dense64<W, I> {
     fn rank(&self, value: u64) -> usize {
+        let randex = self.randex.as_ref();
         if value >= self.size_ {
-            return self.randex[self.randex.len() - 1] as usize;
+            return randex[randex.len() - 1] as usize;
         }
         let w = (value / 64) as usize;
         let b = value & 63;
-        self.randex[w] as usize + rank64(self.words[w], b) as usize
+        randex[w] as usize + rank64(self.words.as_ref()[w], b) as usize
     }
 }
 
-impl Select for Dense64 {
+impl<W: AsRef<[u64]>, I: AsRef<[u32]>> Select for Dense64<W, I> {
     fn select(&self, index: usize) -> u64 {
-        let mut i = self.seldex[index >> BLOCK_BITS] as usize;
-        while i + 1 < self.randex.len() && self.randex[i + 1] <= index as u32 {
+        let randex = self.randex.as_ref();
+        let mut i = self.seldex.as_ref()[index >> BLOCK_BITS] as usize;
+        while i + 1 < randex.len() && randex[i + 1] <= index as u32 {
             i += 1;
         }
-        let r0 = self.randex[i] as usize;
-        64 * i as u64 + select64(self.words[i], index - r0)
+        let r0 = randex[i] as usize;
+        64 * i as u64 + select64(self.words.as_ref()[i], index - r0)
     }
 }
 
-impl Select0 for Dense64 {}
+impl<W: AsRef<[u64]>, I: AsRef<[u32]>> Select0 for Dense64<W, I> {
+    fn select_0(&self, index: usize) -> u64 {
+        let words = self.words.as_ref();
+        let randex = self.randex.as_ref();
+        let zeldex = self.zeldex.as_ref();
+        let zeros_before = |i: usize| 64 * i - randex[i] as usize;
+
+        // Find the last word with at most `index` zeros before it, within the
+        // range given by the select-0 index, if there is one.
+        let k = index >> BLOCK_BITS;
+        let (mut first, last) = match (zeldex.get(k), zeldex.get(k + 1)) {
+            (Some(i), Some(j)) => (*i as usize, *j as usize + 1),
+            (Some(i), None) => (*i as usize, words.len()),
+            _ => (0, words.len()),
+        };
+        let mut count = last - first;
+        while count > 0 {
+            let step = count / 2;
+            let i = first + step;
+            if zeros_before(i) <= index {
+                first = i + 1;
+                count -= step + 1;
+            } else {
+                count = step;
+            }
+        }
+        let i = first - 1;
+        64 * i as u64 + select64(!words[i], index - zeros_before(i))
+    }
+}
+
+/// Scanning the word containing `value` answers most queries on dense sets
+/// without touching the index.
+impl<W: AsRef<[u64]>, I: AsRef<[u32]>> Successor for Dense64<W, I> {
+    fn successor(&self, value: u64) -> Option<u64> {
+        if value >= self.size_ {
+            return None;
+        }
+        let i = (value / 64) as usize;
+        let w = self.words.as_ref()[i] & (!0u64 << (value & 63));
+        if w != 0 {
+            return Some(64 * i as u64 + w.trailing_zeros() as u64);
+        }
+        let r = self.randex.as_ref()[i + 1] as usize;
+        if r < self.count() {
+            Some(self.select(r))
+        } else {
+            None
+        }
+    }
+
+    fn predecessor(&self, value: u64) -> Option<u64> {
+        let value = value.min(self.size_);
+        let i = (value / 64) as usize;
+        let b = value & 63;
+        if b > 0 {
+            let w = self.words.as_ref()[i] & ((1u64 << b) - 1);
+            if w != 0 {
+                return Some(64 * i as u64 + 63 - w.leading_zeros() as u64);
+            }
+        }
+        let r = self.randex.as_ref()[i] as usize;
+        if r > 0 {
+            Some(self.select(r - 1))
+        } else {
+            None
+        }
+    }
+}
+
+impl<W: AsRef<[u64]>, I: AsRef<[u32]>> Successor0 for Dense64<W, I> {
+    fn successor_0(&self, value: u64) -> Option<u64> {
+        if value >= self.size_ {
+            return None;
+        }
+        let i = (value / 64) as usize;
+        let end = self.size_ - 64 * i as u64;
+        let mut w = !self.words.as_ref()[i] & (!0u64 << (value & 63));
+        if end < 64 {
+            w &= (1u64 << end) - 1;
+        }
+        if w != 0 {
+            return Some(64 * i as u64 + w.trailing_zeros() as u64);
+        }
+        let r = self.rank_0(64 * (i as u64 + 1));
+        if r < self.size_ as usize - self.count() {
+            Some(self.select_0(r))
+        } else {
+            None
+        }
+    }
+
+    fn predecessor_0(&self, value: u64) -> Option<u64> {
+        let value = value.min(self.size_);
+        let i = (value / 64) as usize;
+        let b = value & 63;
+        if b > 0 {
+            let w = !self.words.as_ref()[i] & ((1u64 << b) - 1);
+            if w != 0 {
+                return Some(64 * i as u64 + 63 - w.leading_zeros() as u64);
+            }
+        }
+        let r = 64 * i - self.randex.as_ref()[i] as usize;
+        if r > 0 {
+            Some(self.select_0(r - 1))
+        } else {
+            None
+        }
+    }
+}
+
+impl FromBitVec for Dense64 {
+    fn from_bitvec(bits: BitVec) -> Self {
+        Dense64::from_bitvec(bits)
+    }
+}
+
+impl<W: AsRef<[u64]>, I: AsRef<[u32]>> AsWords for Dense64<W, I> {
+    fn as_words(&self) -> &[u64] {
+        self.words.as_ref()
+    }
+}
+
+impl FromSorted for Dense64 {
+    fn from_sorted(size: u64, elements: &[u64]) -> Self {
+        Dense64::from_positions(size, elements)
+    }
+}
+
+impl<W: AsRef<[u64]>, I: AsRef<[u32]>> IterableSet for Dense64<W, I> {
+    type Iter<'a> = BitIter<'a> where Self: 'a;
+
+    fn iter_from(&self, value: u64) -> Self::Iter<'_> {
+        let count = self.count() - self.rank(value);
+        BitIter::new(self.words.as_ref(), self.size_, value, count, true)
+    }
+}
+
+/// Return the size and whether there is a select-0 index.
+fn dense64_params(params: &[u64]) -> std::io::Result<(u64, bool)> {
+    check_params(&<Dense64 as Persistent>::TYPE_TAG, params, 2)?;
+    Ok((params[0], params[1] != 0))
+}
 
 impl Persistent for Dense64 {
-    fn save<Sink>(&self, sink: &mut Sink) -> std::io::Result<()>
+    const TYPE_TAG: [u8; 4] = *b"DN64";
+
+    fn params(&self) -> Vec<u64> {
+        vec![self.size_, !self.zeldex.is_empty() as u64]
+    }
+
+    fn save_body<Sink>(&self, sink: &mut Sink) -> std::io::Result<()>
     where
         Sink: std::io::Write,
     {
-        sink.write_all(&self.size_.to_ne_bytes())?;
         save_vec(sink, &self.words)?;
         save_vec(sink, &self.randex)?;
         save_vec(sink, &self.seldex)?;
+        if !self.zeldex.is_empty() {
+            save_vec(sink, &self.zeldex)?;
+        }
         Ok(())
     }
 
-    fn load<Source>(source: &mut Source) -> std::io::Result<Box<Self>>
+    fn load_body<Source>(params: &[u64], source: &mut Source) -> std::io::Result<Self>
     where
         Source: std::io::Read,
     {
-        let size_: u64 = load_u64(source)?;
+        let (size_, has_zeldex) = dense64_params(params)?;
         let words: Vec<u64> = load_vec_u64(source)?;
         let randex: Vec<u32> = load_vec_u32(source)?;
         let seldex: Vec<u32> = load_vec_u32(source)?;
-        Ok(Box::new(Dense64 {
+        let zeldex: Vec<u32> = if has_zeldex { load_vec_u32(source)? } else { Vec::new() };
+        let s = Dense64 {
+            size_,
+            words,
+            randex,
+            seldex,
+            zeldex,
+        };
+        s.check_loaded(true)?;
+        Ok(s)
+    }
+}
+
+impl<'a> View<'a> for Dense64View<'a> {
+    const TYPE_TAG: [u8; 4] = <Dense64 as Persistent>::TYPE_TAG;
+
+    fn view_body(params: &[u64], source: &mut ByteReader<'a>) -> std::io::Result<Self> {
+        let (size_, has_zeldex) = dense64_params(params)?;
+        let words: &'a [u64] = source.slice_u64()?;
+        let randex: &'a [u32] = source.slice_u32()?;
+        let seldex: &'a [u32] = source.slice_u32()?;
+        let zeldex: &'a [u32] = if has_zeldex { source.slice_u32()? } else { &[] };
+        let s = Dense64 {
             size_,
             words,
             randex,
             seldex,
-        }))
+            zeldex,
+        };
+        s.check_loaded(false)?;
+        Ok(s)
+    }
+}
+
+/// Only the words are serialized. The indexes are rebuilt from them when the
+/// set is deserialized, so they always agree with the words.
+#[cfg(feature = "serde")]
+impl serde::Serialize for Dense64 {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        use serde::ser::SerializeStruct;
+        let mut state = serializer.serialize_struct("Dense64", 3)?;
+        state.serialize_field("size", &self.size_)?;
+        state.serialize_field("words", &self.words)?;
+        state.serialize_field("select_0", &!self.zeldex.is_empty())?;
+        state.end()
+    }
+}
+
+#[cfg(feature = "serde")]
+#[derive(serde::Deserialize)]
+#[serde(rename = "Dense64")]
+struct Dense64Data {
+    size: u64,
+    words: Vec<u64>,
+    select_0: bool,
+}
+
+#[cfg(feature = "serde")]
+impl<'de> serde::Deserialize<'de> for Dense64 {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        use serde::de::Error;
+        let data = Dense64Data::deserialize(deserializer)?;
+        if data.words.len() as u64 != data.size.div_ceil(64) || !crate::words::clear_from(&data.words, data.size) {
+            return Err(D::Error::custom("inconsistent Dense64 length"));
+        }
+        let s = Dense64::from_words(data.size, data.words);
+        Ok(if data.select_0 { s.with_select_0_index() } else { s })
     }
 }
 
@@ -175,8 +565,7 @@ mod tests {
         let m = n * 64;
         let k = 100;
         let mut bits = Vec::new();
-        let mut words = Vec::new();
-        words.resize(n, 0);
+        let mut words = vec![0; n];
         let mut rng = MiniRng::new(0xfbdb8b2bcc6674b8u64);
         for _i in 0..k {
             let x: u64 = (rng.rnd() ^ (rng.rnd() << 32) ^ (rng.rnd() >> 32)) % (m as u64);
@@ -189,8 +578,7 @@ mod tests {
         println!("{:?}", bits);
 
         let r = Dense64::new(m as u64, &words);
-        for i in 0..k {
-            let x = bits[i];
+        for (i, &x) in bits.iter().enumerate() {
             println!("{} {}", i, x);
             let j = r.rank(x);
             assert_eq!(j, i);
@@ -207,8 +595,7 @@ mod tests {
         let m = n * 64;
         let k = 100;
         let mut bits = Vec::new();
-        let mut words = Vec::new();
-        words.resize(n, 0);
+        let mut words = vec![0; n];
         let mut rng = MiniRng::new(0xfbdb8b2bcc6674b8u64);
         for _i in 0..k {
             let x: u64 = (rng.rnd() ^ (rng.rnd() << 32) ^ (rng.rnd() >> 32)) % (m as u64);
@@ -221,8 +608,8 @@ mod tests {
         println!("{:?}", bits);
 
         let r = Dense64::new(m as u64, &words);
-        for i in 0..k {
-            assert_eq!(r.select(i), bits[i]);
+        for (i, &x) in bits.iter().enumerate() {
+            assert_eq!(r.select(i), x);
         }
     }
 
@@ -232,8 +619,7 @@ mod tests {
         let n = m / 64;
         let k = 65536;
         let mut bits = Vec::new();
-        let mut words = Vec::new();
-        words.resize(n, 0);
+        let mut words = vec![0; n];
         let mut rng = MiniRng::new(0xfbdb8b2bcc6674b8u64);
         for _i in 0..k {
             let x: u64 = (rng.rnd() ^ (rng.rnd() << 32) ^ (rng.rnd() >> 32)) % (m as u64);
@@ -246,8 +632,8 @@ mod tests {
         bits.dedup();
 
         let r = Dense64::new(m as u64, &words);
-        for i in 0..bits.len() {
-            assert_eq!(r.select(i), bits[i]);
+        for (i, &x) in bits.iter().enumerate() {
+            assert_eq!(r.select(i), x);
         }
     }
 
@@ -257,8 +643,7 @@ mod tests {
         let n = m / 64;
         let k = 1024;
         let mut bits = Vec::new();
-        let mut words = Vec::new();
-        words.resize(n, 0);
+        let mut words = vec![0; n];
         let mut rng = MiniRng::new(0xfbdb8b2bcc6674b8u64);
         for _i in 0..k {
             let x: u64 = (rng.rnd() ^ (rng.rnd() << 32) ^ (rng.rnd() >> 32)) % (m as u64);
@@ -272,9 +657,9 @@ mod tests {
         //println!("{:?}", bits);
 
         let r = Dense64::new(m as u64, &words);
-        for i in 0..bits.len() {
-            //println!("i={}, x={}", i, bits[i]);
-            assert_eq!(r.select(i), bits[i]);
+        for (i, &x) in bits.iter().enumerate() {
+            //println!("i={}, x={}", i, x);
+            assert_eq!(r.select(i), x);
         }
     }
 
@@ -297,14 +682,61 @@ mod tests {
         assert_eq!(r.select_0(1), 4);
     }
 
+    #[test]
+    fn test_successor_1() {
+        let mut rng = MiniRng::new(19);
+        for size in [1000u64, 1024] {
+            let mut bits = BitVec::new();
+            for _i in 0..size {
+                // Long runs of ones and zeros exercise the index fallbacks.
+                bits.push((rng.rnd() >> 40) % 200 < 3 || bits.len() % 300 < 70);
+            }
+            let s = Dense64::from_bitvec(bits.clone());
+            let xs: Vec<u64> = (0..size).filter(|x| bits.get(*x as usize)).collect();
+            let zs: Vec<u64> = (0..size).filter(|x| !bits.get(*x as usize)).collect();
+            for x in 0..=size + 1 {
+                assert_eq!(s.successor(x), xs.iter().copied().find(|y| *y >= x));
+                assert_eq!(s.predecessor(x), xs.iter().copied().rev().find(|y| *y < x));
+                assert_eq!(s.next_one_after(x), xs.iter().copied().find(|y| *y > x));
+                assert_eq!(s.successor_0(x), zs.iter().copied().find(|y| *y >= x));
+                assert_eq!(s.predecessor_0(x), zs.iter().copied().rev().find(|y| *y < x));
+                assert_eq!(s.next_zero_after(x), zs.iter().copied().find(|y| *y > x));
+            }
+        }
+    }
+
+    #[test]
+    fn test_constructors_1() {
+        let xs: Vec<u64> = vec![0, 2, 63, 64, 130, 199];
+        let mut bits = BitVec::new();
+        for i in 0..200 {
+            bits.push(xs.contains(&i));
+        }
+        let a = Dense64::from_positions(200, &xs);
+        let b = Dense64::from_bitvec(bits.clone());
+        let c: Dense64 = (0..200).map(|i| bits.get(i)).collect();
+        for s in [a, b, c] {
+            assert_eq!(s.size(), 200);
+            assert_eq!(s.count(), xs.len());
+            for (i, &x) in xs.iter().enumerate() {
+                assert_eq!(s.select(i), x);
+                assert_eq!(s.rank(x), i);
+            }
+            assert_eq!(s.select_0(0), 1);
+            assert_eq!(s.select_0(2), 4);
+            assert_eq!(s.iter().collect::<Vec<u64>>(), xs);
+            assert_eq!(s.iter_from(64).collect::<Vec<u64>>(), xs[3..]);
+            assert_eq!(s.iter_from(65).rev().collect::<Vec<u64>>(), vec![199, 130]);
+        }
+    }
+
     #[test]
     fn test_load_and_save_1() {
         let m = 1024 * 1024;
         let n = m / 64;
         let k = 1024;
         let mut bits = Vec::new();
-        let mut words = Vec::new();
-        words.resize(n, 0);
+        let mut words = vec![0; n];
         let mut rng = MiniRng::new(0xfbdb8b2bcc6674b8u64);
         for _i in 0..k {
             let x: u64 = (rng.rnd() ^ (rng.rnd() << 32) ^ (rng.rnd() >> 32)) % (m as u64);
@@ -325,8 +757,7 @@ mod tests {
         let mut cursor = Cursor::new(bytes);
         let s = Dense64::load(&mut cursor).expect("load failed");
 
-        for i in 0..k {
-            let x = bits[i];
+        for (i, &x) in bits.iter().enumerate() {
             println!("{} {}", i, x);
             let j = s.rank(x);
             assert_eq!(j, i);
@@ -334,4 +765,103 @@ mod tests {
             assert_eq!(y, x);
         }
     }
+
+    #[test]
+    fn test_select0_index_1() {
+        let mut rng = MiniRng::new(23);
+        for (size, density) in [(100_003u64, 10u64), (70_000, 250), (5000, 254)] {
+            let mut bits = BitVec::new();
+            for _i in 0..size {
+                bits.push((rng.rnd() >> 56) < density);
+            }
+            let zs: Vec<u64> = (0..size).filter(|x| !bits.get(*x as usize)).collect();
+            let plain = Dense64::from_bitvec(bits.clone());
+            let indexed = Dense64::from_bitvec(bits).with_select_0_index();
+            for (i, &z) in zs.iter().enumerate() {
+                assert_eq!(plain.select_0(i), z);
+                assert_eq!(indexed.select_0(i), z);
+            }
+
+            let mut bytes: Vec<u8> = Vec::new();
+            indexed.save(&mut bytes).expect("save failed");
+            let loaded = Dense64::load(&mut bytes.as_slice()).expect("load failed");
+            assert_eq!(loaded.zeldex, indexed.zeldex);
+            assert_eq!(loaded.select_0(zs.len() - 1), zs[zs.len() - 1]);
+        }
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn test_serde_1() {
+        let s = Dense64::from_positions(1000, &[3, 64, 100, 999]).with_select_0_index();
+        let json = serde_json::to_string(&s).expect("serialize failed");
+        let t: Dense64 = serde_json::from_str(&json).expect("deserialize failed");
+        assert_eq!(t.randex, s.randex);
+        assert_eq!(t.seldex, s.seldex);
+        assert_eq!(t.zeldex, s.zeldex);
+        assert_eq!(t.select(3), 999);
+
+        // A bit set beyond the size of the domain is rejected.
+        let mut value: serde_json::Value = serde_json::from_str(&json).unwrap();
+        value["words"][15] = serde_json::Value::from(1u64 << 40);
+        assert!(serde_json::from_value::<Dense64>(value).is_err());
+
+        // So is a vector of words too short for the domain.
+        let bad = r#"{"size":100,"words":[0],"select_0":false}"#;
+        assert!(serde_json::from_str::<Dense64>(bad).is_err());
+    }
+
+    #[test]
+    fn test_load_corrupt_1() {
+        use crate::persist::{save_header, save_vec};
+
+        let s = Dense64::from_positions(200, &[3, 64, 199]);
+        let save = |size: u64, words: &[u64], randex: &[u32], seldex: &[u32]| {
+            let mut bytes: Vec<u8> = Vec::new();
+            save_header(&mut bytes, b"DN64", &[size, 0]).unwrap();
+            save_vec(&mut bytes, words).unwrap();
+            save_vec(&mut bytes, randex).unwrap();
+            save_vec(&mut bytes, seldex).unwrap();
+            bytes
+        };
+        let bytes = save(200, &s.words, &s.randex, &s.seldex);
+        assert!(Dense64::load(&mut bytes.as_slice()).is_ok());
+        // Too few words for the size.
+        let bytes = save(300, &s.words, &s.randex, &s.seldex);
+        assert!(Dense64::load(&mut bytes.as_slice()).is_err());
+        // A rank index that does not match the words.
+        let bytes = save(200, &s.words, &[0, 1, 3, 3, 3], &s.seldex);
+        assert!(Dense64::load(&mut bytes.as_slice()).is_err());
+        // A select index pointing past the words.
+        let bytes = save(200, &s.words, &s.randex, &[7]);
+        assert!(Dense64::load(&mut bytes.as_slice()).is_err());
+    }
+
+    #[test]
+    fn test_view_corrupt_1() {
+        use crate::persist::{save_header, save_vec};
+
+        let s = Dense64::from_positions(200, &[3, 64, 199]);
+        let view_ok = |size: u64, randex: &[u32], seldex: &[u32]| {
+            let mut bytes: Vec<u8> = Vec::new();
+            save_header(&mut bytes, b"DN64", &[size, 0]).unwrap();
+            save_vec(&mut bytes, &s.words).unwrap();
+            save_vec(&mut bytes, randex).unwrap();
+            save_vec(&mut bytes, seldex).unwrap();
+            let words: Vec<u64> = bytes
+                .chunks(8)
+                .map(|w| u64::from_le_bytes(w.try_into().unwrap()))
+                .collect();
+            let aligned: &[u8] = unsafe { std::slice::from_raw_parts(words.as_ptr() as *const u8, bytes.len()) };
+            Dense64View::view(aligned).is_ok()
+        };
+        assert!(view_ok(200, &s.randex, &s.seldex));
+        assert!(!view_ok(300, &s.randex, &s.seldex));
+        // A rank index that decreases, or grows by more than 64 in a word.
+        assert_eq!(s.randex, vec![0, 1, 2, 2, 3]);
+        assert!(!view_ok(200, &[0, 2, 1, 1, 3], &s.seldex));
+        assert!(!view_ok(200, &[0, 1, 70, 70, 71], &s.seldex));
+        // A select index pointing past the word holding its element.
+        assert!(!view_ok(200, &s.randex, &[2]));
+    }
 }
@@ -0,0 +1,585 @@
+//! A dynamic bit vector, supporting insertion and removal of bits as well as
+//! rank and select, using a balanced tree of word-packed leaves.
+
+use crate::{
+    bitvec::{BitVec, FromBitVec},
+    rank::Rank,
+    select::{Select, Select0},
+    set::ImpliedSet,
+    words::{get_bits, select64},
+};
+
+/// The largest number of words in a leaf.
+const LEAF_WORDS: usize = 8;
+
+/// The largest number of bits in a leaf.
+const LEAF_BITS: usize = 64 * LEAF_WORDS;
+
+/// The largest number of children of an internal node.
+const MAX_CHILDREN: usize = 16;
+
+/// A run of up to [`LEAF_BITS`] bits. Bits beyond `len` are always zero.
+struct Leaf {
+    len: usize,
+    ones: usize,
+    words: Vec<u64>,
+}
+
+impl Leaf {
+    fn new() -> Leaf {
+        Leaf {
+            len: 0,
+            ones: 0,
+            words: Vec::with_capacity(LEAF_WORDS),
+        }
+    }
+
+    fn get(&self, i: usize) -> bool {
+        (self.words[i / 64] >> (i & 63)) & 1 == 1
+    }
+
+    /// Return the number of one bits before position `i`.
+    fn rank(&self, i: usize) -> usize {
+        let w = i / 64;
+        let r: usize = self.words[..w].iter().map(|x| x.count_ones() as usize).sum();
+        if i & 63 == 0 {
+            r
+        } else {
+            r + (self.words[w] & ((1u64 << (i & 63)) - 1)).count_ones() as usize
+        }
+    }
+
+    /// Return the position of the `k`-th one bit (or zero bit, if `ones` is false).
+    fn select(&self, k: usize, ones: bool) -> usize {
+        let mut k = k;
+        for (i, w) in self.words.iter().enumerate() {
+            let w = if ones { *w } else { !*w };
+            let c = w.count_ones() as usize;
+            if k < c {
+                return 64 * i + select64(w, k) as usize;
+            }
+            k -= c;
+        }
+        panic!("select out of range");
+    }
+
+    fn set(&mut self, i: usize, bit: bool) -> bool {
+        let old = self.get(i);
+        if old != bit {
+            self.words[i / 64] ^= 1u64 << (i & 63);
+            if bit {
+                self.ones += 1;
+            } else {
+                self.ones -= 1;
+            }
+        }
+        old
+    }
+
+    fn insert(&mut self, i: usize, bit: bool) {
+        if self.len == 64 * self.words.len() {
+            self.words.push(0);
+        }
+        let (w, b) = (i / 64, i & 63);
+        let mask = (1u64 << b) - 1;
+        let mut carry = self.words[w] >> 63;
+        self.words[w] = (self.words[w] & mask) | ((self.words[w] & !mask) << 1) | ((bit as u64) << b);
+        for j in w + 1..self.words.len() {
+            let next = self.words[j] >> 63;
+            self.words[j] = (self.words[j] << 1) | carry;
+            carry = next;
+        }
+        self.len += 1;
+        self.ones += bit as usize;
+    }
+
+    fn remove(&mut self, i: usize) -> bool {
+        let bit = self.get(i);
+        let (w, b) = (i / 64, i & 63);
+        let mask = (1u64 << b) - 1;
+        let n = self.words.len();
+        for j in w..n {
+            let shifted = if j == w {
+                (self.words[j] & mask) | ((self.words[j] >> 1) & !mask)
+            } else {
+                self.words[j] >> 1
+            };
+            let next = if j + 1 < n { self.words[j + 1] & 1 } else { 0 };
+            self.words[j] = shifted | (next << 63);
+        }
+        self.len -= 1;
+        self.ones -= bit as usize;
+        self.words.truncate(self.len.div_ceil(64));
+        bit
+    }
+
+    /// Append `width` bits of `value`.
+    fn push_bits(&mut self, value: u64, width: usize) {
+        if width == 0 {
+            return;
+        }
+        let b = self.len & 63;
+        if b == 0 {
+            self.words.push(value);
+        } else {
+            let last = self.words.len() - 1;
+            self.words[last] |= value << b;
+            if b + width > 64 {
+                self.words.push(value >> (64 - b));
+            }
+        }
+        self.len += width;
+        self.ones += value.count_ones() as usize;
+    }
+
+    /// Append the bits of `other`.
+    fn append(&mut self, other: &Leaf) {
+        let mut p = 0;
+        while p < other.len {
+            let width = (other.len - p).min(64);
+            self.push_bits(get_bits(&other.words, p, width), width);
+            p += width;
+        }
+    }
+
+    /// Split the leaf in two, returning the bits from position `at` on.
+    fn split_off(&mut self, at: usize) -> Leaf {
+        let mut rest = Leaf::new();
+        let mut p = at;
+        while p < self.len {
+            let width = (self.len - p).min(64);
+            rest.push_bits(get_bits(&self.words, p, width), width);
+            p += width;
+        }
+        self.len = at;
+        self.words.truncate(at.div_ceil(64));
+        if at & 63 != 0 {
+            let last = self.words.len() - 1;
+            self.words[last] &= (1u64 << (at & 63)) - 1;
+        }
+        self.ones -= rest.ones;
+        rest
+    }
+}
+
+/// An internal node, with the number of bits and one bits beneath each child.
+struct Internal {
+    lens: Vec<usize>,
+    ones: Vec<usize>,
+    children: Vec<Node>,
+}
+
+enum Node {
+    Leaf(Leaf),
+    Internal(Internal),
+}
+
+impl Node {
+    fn len(&self) -> usize {
+        match self {
+            Node::Leaf(leaf) => leaf.len,
+            Node::Internal(node) => node.lens.iter().sum(),
+        }
+    }
+
+    fn ones(&self) -> usize {
+        match self {
+            Node::Leaf(leaf) => leaf.ones,
+            Node::Internal(node) => node.ones.iter().sum(),
+        }
+    }
+
+    /// Return true if the node is too small, and should be merged with a sibling.
+    fn is_small(&self) -> bool {
+        match self {
+            Node::Leaf(leaf) => leaf.len < LEAF_BITS / 4,
+            Node::Internal(node) => node.children.len() < MAX_CHILDREN / 4,
+        }
+    }
+
+    /// Return true if the node is too big, and should be split.
+    fn is_full(&self) -> bool {
+        match self {
+            Node::Leaf(leaf) => leaf.len >= LEAF_BITS,
+            Node::Internal(node) => node.children.len() > MAX_CHILDREN,
+        }
+    }
+
+    /// Split the node in half, returning the second half.
+    fn split(&mut self) -> Node {
+        match self {
+            Node::Leaf(leaf) => Node::Leaf(leaf.split_off(leaf.len / 2)),
+            Node::Internal(node) => {
+                let at = node.children.len() / 2;
+                Node::Internal(Internal {
+                    lens: node.lens.split_off(at),
+                    ones: node.ones.split_off(at),
+                    children: node.children.split_off(at),
+                })
+            }
+        }
+    }
+
+    /// Append the contents of `other`, which must be at the same depth.
+    fn merge(&mut self, other: Node) {
+        match (self, other) {
+            (Node::Leaf(a), Node::Leaf(b)) => a.append(&b),
+            (Node::Internal(a), Node::Internal(b)) => {
+                a.lens.extend(b.lens);
+                a.ones.extend(b.ones);
+                a.children.extend(b.children);
+            }
+            _ => unreachable!(),
+        }
+    }
+
+    /// Return the child containing position `i`, and the position within it.
+    /// If `end` is true, a position just past the end of a child is in that child.
+    fn locate(node: &Internal, i: usize, end: bool) -> (usize, usize) {
+        let mut i = i;
+        let last = node.children.len() - 1;
+        for c in 0..last {
+            if i < node.lens[c] || (end && i == node.lens[c]) {
+                return (c, i);
+            }
+            i -= node.lens[c];
+        }
+        (last, i)
+    }
+
+    fn get(&self, i: usize) -> bool {
+        match self {
+            Node::Leaf(leaf) => leaf.get(i),
+            Node::Internal(node) => {
+                let (c, j) = Node::locate(node, i, false);
+                node.children[c].get(j)
+            }
+        }
+    }
+
+    fn rank(&self, i: usize) -> usize {
+        match self {
+            Node::Leaf(leaf) => leaf.rank(i),
+            Node::Internal(node) => {
+                let mut i = i;
+                let mut r = 0;
+                for c in 0..node.children.len() {
+                    if i < node.lens[c] {
+                        return r + node.children[c].rank(i);
+                    }
+                    i -= node.lens[c];
+                    r += node.ones[c];
+                }
+                r
+            }
+        }
+    }
+
+    fn select(&self, k: usize, ones: bool) -> usize {
+        match self {
+            Node::Leaf(leaf) => leaf.select(k, ones),
+            Node::Internal(node) => {
+                let mut k = k;
+                let mut p = 0;
+                for c in 0..node.children.len() {
+                    let m = if ones { node.ones[c] } else { node.lens[c] - node.ones[c] };
+                    if k < m {
+                        return p + node.children[c].select(k, ones);
+                    }
+                    k -= m;
+                    p += node.lens[c];
+                }
+                panic!("select out of range");
+            }
+        }
+    }
+
+    fn set(&mut self, i: usize, bit: bool) -> bool {
+        match self {
+            Node::Leaf(leaf) => leaf.set(i, bit),
+            Node::Internal(node) => {
+                let (c, j) = Node::locate(node, i, false);
+                let old = node.children[c].set(j, bit);
+                node.ones[c] = node.ones[c] + bit as usize - old as usize;
+                old
+            }
+        }
+    }
+
+    /// Insert `bit` at position `i`, returning a new sibling if the node had to be split.
+    fn insert(&mut self, i: usize, bit: bool) -> Option<Node> {
+        match self {
+            Node::Leaf(leaf) => leaf.insert(i, bit),
+            Node::Internal(node) => {
+                let (c, j) = Node::locate(node, i, true);
+                let sibling = node.children[c].insert(j, bit);
+                node.lens[c] += 1;
+                node.ones[c] += bit as usize;
+                if let Some(sibling) = sibling {
+                    node.lens[c] = node.children[c].len();
+                    node.ones[c] = node.children[c].ones();
+                    node.lens.insert(c + 1, sibling.len());
+                    node.ones.insert(c + 1, sibling.ones());
+                    node.children.insert(c + 1, sibling);
+                }
+            }
+        }
+        if self.is_full() {
+            Some(self.split())
+        } else {
+            None
+        }
+    }
+
+    /// Remove the bit at position `i`, merging children that become too small.
+    fn remove(&mut self, i: usize) -> bool {
+        match self {
+            Node::Leaf(leaf) => leaf.remove(i),
+            Node::Internal(node) => {
+                let (c, j) = Node::locate(node, i, false);
+                let bit = node.children[c].remove(j);
+                node.lens[c] -= 1;
+                node.ones[c] -= bit as usize;
+                if node.children[c].is_small() && node.children.len() > 1 {
+                    // Merge with a neighbour, then split again if the result is too big.
+                    let c = if c + 1 < node.children.len() { c } else { c - 1 };
+                    node.lens.remove(c + 1);
+                    node.ones.remove(c + 1);
+                    let next = node.children.remove(c + 1);
+                    node.children[c].merge(next);
+                    if node.children[c].is_full() {
+                        let sibling = node.children[c].split();
+                        node.lens.insert(c + 1, sibling.len());
+                        node.ones.insert(c + 1, sibling.ones());
+                        node.children.insert(c + 1, sibling);
+                    }
+                    node.lens[c] = node.children[c].len();
+                    node.ones[c] = node.children[c].ones();
+                }
+                bit
+            }
+        }
+    }
+}
+
+/// The `DynamicBitVec` data structure is a bit vector which supports insertion
+/// and removal of bits, as well as rank and select, all in O(log n) time.
+///
+/// The bits are kept in leaves of up to 512 bits, which are the leaves of a
+/// B-tree whose internal nodes record the number of bits and one bits beneath
+/// each child. As a set, the elements are the positions of the one bits, so
+/// inserting or removing a bit shifts the elements after it.
+///
+/// #Examples
+///
+/// ```
+/// use crate::ransel::dynamic::DynamicBitVec;
+/// use crate::ransel::rank::Rank;
+/// use crate::ransel::select::Select;
+///
+/// let mut v = DynamicBitVec::new();
+/// for i in 0..1000 {
+///     v.push(i % 3 == 0);
+/// }
+/// assert_eq!(v.rank(30), 10);
+/// v.insert(0, true);
+/// assert_eq!(v.rank(31), 11);
+/// assert_eq!(v.select(1), 1);
+/// assert!(v.remove(1));
+/// v.set(2, true);
+/// assert_eq!(v.select(1), 2);
+/// ```
+pub struct DynamicBitVec {
+    root: Node,
+    len: usize,
+    ones: usize,
+}
+
+impl DynamicBitVec {
+    /// Create a new empty bit vector.
+    pub fn new() -> DynamicBitVec {
+        DynamicBitVec {
+            root: Node::Leaf(Leaf::new()),
+            len: 0,
+            ones: 0,
+        }
+    }
+
+    /// Return the number of bits.
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    /// Return true if there are no bits.
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Return the bit at position `index`.
+    pub fn get(&self, index: usize) -> bool {
+        assert!(index < self.len);
+        self.root.get(index)
+    }
+
+    /// Set the bit at position `index`, returning its previous value.
+    pub fn set(&mut self, index: usize, bit: bool) -> bool {
+        assert!(index < self.len);
+        let old = self.root.set(index, bit);
+        self.ones = self.ones + bit as usize - old as usize;
+        old
+    }
+
+    /// Insert `bit` at position `index`, shifting the bits after it up by one.
+    pub fn insert(&mut self, index: usize, bit: bool) {
+        assert!(index <= self.len);
+        if let Some(sibling) = self.root.insert(index, bit) {
+            let root = std::mem::replace(&mut self.root, Node::Leaf(Leaf::new()));
+            self.root = Node::Internal(Internal {
+                lens: vec![root.len(), sibling.len()],
+                ones: vec![root.ones(), sibling.ones()],
+                children: vec![root, sibling],
+            });
+        }
+        self.len += 1;
+        self.ones += bit as usize;
+    }
+
+    /// Append `bit` to the end.
+    pub fn push(&mut self, bit: bool) {
+        self.insert(self.len, bit);
+    }
+
+    /// Remove the bit at position `index`, shifting the bits after it down by
+    /// one, and return it.
+    pub fn remove(&mut self, index: usize) -> bool {
+        assert!(index < self.len);
+        let bit = self.root.remove(index);
+        if let Node::Internal(node) = &mut self.root {
+            if node.children.len() == 1 {
+                self.root = node.children.pop().unwrap();
+            }
+        }
+        self.len -= 1;
+        self.ones -= bit as usize;
+        bit
+    }
+}
+
+impl Default for DynamicBitVec {
+    fn default() -> Self {
+        DynamicBitVec::new()
+    }
+}
+
+impl FromBitVec for DynamicBitVec {
+    fn from_bitvec(bits: BitVec) -> Self {
+        let mut v = DynamicBitVec::new();
+        for i in 0..bits.len() {
+            v.push(bits.get(i));
+        }
+        v
+    }
+}
+
+impl ImpliedSet for DynamicBitVec {
+    fn count(&self) -> usize {
+        self.ones
+    }
+
+    fn size(&self) -> u64 {
+        self.len as u64
+    }
+}
+
+impl Rank for DynamicBitVec {
+    fn rank(&self, value: u64) -> usize {
+        if value >= self.len as u64 {
+            return self.ones;
+        }
+        self.root.rank(value as usize)
+    }
+}
+
+impl Select for DynamicBitVec {
+    fn select(&self, index: usize) -> u64 {
+        assert!(index < self.ones);
+        self.root.select(index, true) as u64
+    }
+}
+
+impl Select0 for DynamicBitVec {
+    fn select_0(&self, index: usize) -> u64 {
+        assert!(index < self.len - self.ones);
+        self.root.select(index, false) as u64
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use random::Source;
+
+    /// Check the bit vector against a plain vector of bits.
+    fn check(v: &DynamicBitVec, bits: &[bool]) {
+        assert_eq!(v.len(), bits.len());
+        assert_eq!(v.root.len(), bits.len());
+        let mut ones = 0;
+        for (i, &b) in bits.iter().enumerate() {
+            assert_eq!(v.get(i), b);
+            assert_eq!(v.rank(i as u64), ones);
+            if b {
+                assert_eq!(v.select(ones), i as u64);
+                ones += 1;
+            } else {
+                assert_eq!(v.select_0(i - ones), i as u64);
+            }
+        }
+        assert_eq!(v.count(), ones);
+        assert_eq!(v.root.ones(), ones);
+    }
+
+    #[test]
+    fn test_insert_remove_1() {
+        let mut rng = random::default(19);
+        let mut v = DynamicBitVec::new();
+        let mut bits: Vec<bool> = Vec::new();
+        for round in 0..6 {
+            // Grow, then shrink, so that nodes are both split and merged.
+            let grow = round % 2 == 0;
+            for _i in 0..20000 {
+                let r = rng.read_u64();
+                let bit = (r >> 32) & 1 == 1;
+                if grow || bits.is_empty() {
+                    let i = (r % (bits.len() as u64 + 1)) as usize;
+                    v.insert(i, bit);
+                    bits.insert(i, bit);
+                } else if r % 4 == 1 {
+                    let i = (r % bits.len() as u64) as usize;
+                    assert_eq!(v.set(i, bit), bits[i]);
+                    bits[i] = bit;
+                } else {
+                    let i = (r % bits.len() as u64) as usize;
+                    assert_eq!(v.remove(i), bits.remove(i));
+                }
+            }
+            check(&v, &bits);
+        }
+        while !bits.is_empty() {
+            assert_eq!(v.remove(0), bits.remove(0));
+        }
+        check(&v, &bits);
+        assert!(matches!(v.root, Node::Leaf(_)));
+    }
+
+    #[test]
+    fn test_from_bitvec_1() {
+        let mut bits = BitVec::new();
+        for i in 0..5000 {
+            bits.push(i % 7 == 2 || i % 11 == 0);
+        }
+        let expected: Vec<bool> = (0..bits.len()).map(|i| bits.get(i)).collect();
+        let v = DynamicBitVec::from_bitvec(bits);
+        check(&v, &expected);
+    }
+}
@@ -1,3 +1,9 @@
+//! Enumerative coding of fixed width blocks with a given number of one bits.
+
+use crate::error::{check_index, check_param, Result};
+
+/// The set of `n`-bit numbers with exactly `k` one bits, with `rank` and `select`
+/// mapping between numbers and their position in increasing order.
 pub struct EnumCodeSet {
     n: u64,
     k: u64,
@@ -14,10 +20,27 @@ impl EnumCodeSet {
         }
     }
     
+    /// Create the set of `n`-bit numbers with `k` one bits.
     pub fn new(n: u64, k: u64) -> Self {
         EnumCodeSet { n, k }
     }
 
+    /// Create the set of `n`-bit numbers with `k` one bits, or return an error
+    /// if `n` is greater than 63 or `k` is greater than `n`.
+    pub fn try_new(n: u64, k: u64) -> Result<Self> {
+        check_param("n", n, 0, 63)?;
+        check_param("k", k, 0, n)?;
+        Ok(EnumCodeSet::new(n, k))
+    }
+
+    /// Returns the i-th smallest n-bit number with k one bits, or an error if
+    /// there are not more than `i` such numbers.
+    pub fn try_select(&self, i: u64) -> Result<u64> {
+        check_index(i as usize, binom(self.n, self.k) as usize)?;
+        Ok(self.select(i))
+    }
+
+    /// Returns the position of `x` among the `n`-bit numbers with `k` one bits.
     pub fn rank(&self, x: u64) -> u64 {
         assert_eq!((x & ((1 << self.n) - 1)).count_ones() as u64, self.k);
         let mut r = 0;
@@ -58,10 +81,32 @@ impl EnumCodeSet {
     }
 }
 
+const fn binom_table() -> [[u64; 64]; 64] {
+    let mut t = [[0u64; 64]; 64];
+    let mut n = 0;
+    while n < 64 {
+        t[n][0] = 1;
+        let mut k = 1;
+        while k <= n {
+            t[n][k] = t[n - 1][k - 1] + t[n - 1][k];
+            k += 1;
+        }
+        n += 1;
+    }
+    t
+}
+
+/// Binomial coefficients for n < 64, which all fit in 64 bits.
+static BINOM: [[u64; 64]; 64] = binom_table();
+
+#[allow(clippy::unnecessary_cast)]
 fn binom(n: u64, k: u64) -> u64 {
     if k > n {
         return 0;
     }
+    if n < 64 {
+        return BINOM[n as usize][k as usize];
+    }
     let mut res = 1u64;
     for i in 0..k {
         res = res * (n - i) as u64 / (i + 1) as u64;
@@ -79,6 +124,17 @@ mod tests {
         assert_eq!(binom(10, 7), 120);
         assert_eq!(binom(3, 0), 1);
         assert_eq!(binom(6, 6), 1);
+        assert_eq!(binom(63, 31), 916312070471295267);
+        assert_eq!(binom(70, 3), 54740);
+    }
+
+    #[test]
+    fn try_1() {
+        assert!(EnumCodeSet::try_new(64, 3).is_err());
+        assert!(EnumCodeSet::try_new(5, 6).is_err());
+        let s = EnumCodeSet::try_new(5, 2).unwrap();
+        assert_eq!(s.try_select(9), Ok(0b11000));
+        assert!(s.try_select(10).is_err());
     }
 
     #[test]
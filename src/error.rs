@@ -0,0 +1,149 @@
+//! Errors reported by the fallible constructors and operations.
+//!
+//! Most operations in this crate panic when given invalid input, on the basis
+//! that the input is usually produced by the program itself. Where the input
+//! comes from outside, the `try_` variants check it and return an [`Error`] instead.
+
+use std::fmt;
+
+/// The ways in which input to a set or vector may be invalid.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Error {
+    /// The element at `index` is not greater than the element before it.
+    NotIncreasing {
+        /// The position of the offending element.
+        index: usize,
+    },
+    /// The value does not fit in the available number of bits.
+    TooWide {
+        /// The offending value.
+        value: u64,
+        /// The number of bits available.
+        width: usize,
+    },
+    /// The index is beyond the end of the sequence.
+    IndexOutOfRange {
+        /// The offending index.
+        index: usize,
+        /// The length of the sequence.
+        len: usize,
+    },
+    /// The element is not less than the size of the domain.
+    OutOfDomain {
+        /// The offending element.
+        value: u64,
+        /// The size of the domain.
+        size: u64,
+    },
+    /// A parameter, such as the number of bits per element, is not supported
+    /// by the data structure.
+    InvalidParameter {
+        /// The name of the parameter.
+        name: &'static str,
+        /// The offending value.
+        value: u64,
+        /// The smallest supported value.
+        min: u64,
+        /// The largest supported value.
+        max: u64,
+    },
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::NotIncreasing { index } => {
+                write!(f, "element {} is not greater than the preceding element", index)
+            }
+            Error::TooWide { value, width } => {
+                write!(f, "value {} does not fit in {} bits", value, width)
+            }
+            Error::IndexOutOfRange { index, len } => {
+                write!(f, "index {} is out of range for length {}", index, len)
+            }
+            Error::OutOfDomain { value, size } => {
+                write!(f, "element {} is outside the domain [0, {})", value, size)
+            }
+            Error::InvalidParameter {
+                name,
+                value,
+                min,
+                max,
+            } => {
+                write!(f, "parameter {} = {} is not in the range [{}, {}]", name, value, min, max)
+            }
+        }
+    }
+}
+
+impl std::error::Error for Error {}
+
+/// A `Result` with the crate's [`Error`] type.
+pub type Result<T> = std::result::Result<T, Error>;
+
+/// Check that the parameter `name` is in the range `[min, max]`.
+pub(crate) fn check_param(name: &'static str, value: u64, min: u64, max: u64) -> Result<()> {
+    if value < min || value > max {
+        return Err(Error::InvalidParameter {
+            name,
+            value,
+            min,
+            max,
+        });
+    }
+    Ok(())
+}
+
+/// Check that `value` fits in `width` bits.
+pub(crate) fn check_value(value: u64, width: usize) -> Result<()> {
+    if width < 64 && value >> width != 0 {
+        return Err(Error::TooWide { value, width });
+    }
+    Ok(())
+}
+
+/// Check that `index` is less than `len`.
+pub(crate) fn check_index(index: usize, len: usize) -> Result<()> {
+    if index >= len {
+        return Err(Error::IndexOutOfRange { index, len });
+    }
+    Ok(())
+}
+
+/// Check that `value` is less than the domain size `size`.
+pub(crate) fn check_domain(value: u64, size: u64) -> Result<()> {
+    if value >= size {
+        return Err(Error::OutOfDomain { value, size });
+    }
+    Ok(())
+}
+
+/// Check that `elements` are strictly increasing, and each fits in `width` bits.
+pub(crate) fn check_elements(elements: &[u64], width: usize) -> Result<()> {
+    for i in 0..elements.len() {
+        check_value(elements[i], width)?;
+        if i > 0 && elements[i] <= elements[i - 1] {
+            return Err(Error::NotIncreasing { index: i });
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_checks_1() {
+        assert_eq!(check_elements(&[1, 2, 7], 3), Ok(()));
+        assert_eq!(check_elements(&[1, 2, 8], 3), Err(Error::TooWide { value: 8, width: 3 }));
+        assert_eq!(check_elements(&[1, 2, 2], 3), Err(Error::NotIncreasing { index: 2 }));
+        assert_eq!(check_value(u64::MAX, 64), Ok(()));
+        assert_eq!(check_index(3, 3), Err(Error::IndexOutOfRange { index: 3, len: 3 }));
+        assert_eq!(check_domain(1 << 40, 1 << 40), Err(Error::OutOfDomain { value: 1 << 40, size: 1 << 40 }));
+        assert_eq!(
+            check_param("b", 65, 0, 64).unwrap_err().to_string(),
+            "parameter b = 65 is not in the range [0, 64]"
+        );
+    }
+}
@@ -0,0 +1,317 @@
+//! An FM-index for counting and locating substrings of a text, based on the
+//! Ferragina & Manzini 2000 paper:
+//!
+//! > Ferragina, P. and Manzini, G., 2000, November. Opportunistic data structures
+//! > with applications. In Proceedings 41st annual symposium on foundations of
+//! > computer science (pp. 390-398). IEEE.
+
+use crate::{
+    algebra::domain_bits,
+    dense64::Dense64,
+    intvec::IntVec,
+    persist::{check_params, invalid_data, load_vec_u64, save_vec, to_usize, Persistent},
+    rank::Rank,
+    sais::suffix_array,
+    set::ImpliedSet,
+    wavelet::WaveletMatrix,
+};
+
+/// The `FmIndex` data structure is a compressed index of a text, which counts
+/// and locates the occurrences of patterns, and extracts substrings of the text.
+///
+/// The text is a sequence of symbols, such as bytes, which is stored as its
+/// Burrows-Wheeler transform in a [`WaveletMatrix`]. Each symbol is replaced by
+/// its rank among the distinct symbols of the text, plus 1, so 0 can be the
+/// terminator and the alphabet is no larger than the text. Patterns are found by backward search, with
+/// two `rank` operations per symbol. Every `rate`-th text position is sampled:
+/// a [`Dense64`] marks the rows of the suffix array holding a sampled position,
+/// and an [`IntVec`] holds those positions, so `locate` takes fewer than `rate`
+/// steps per occurrence. The rows of the sampled positions are kept too, so
+/// `extract` can start from the nearest sample after the end of the range.
+///
+/// #Examples
+///
+/// ```
+/// use crate::ransel::fmindex::FmIndex;
+///
+/// let idx = FmIndex::new("abracadabra".as_bytes(), 4);
+/// assert_eq!(idx.count("abra".as_bytes()), 2);
+/// assert_eq!(idx.count("cad".as_bytes()), 1);
+/// assert_eq!(idx.count("dab".as_bytes()), 1);
+/// assert_eq!(idx.count("bad".as_bytes()), 0);
+/// assert_eq!(idx.locate("a".as_bytes()), vec![0, 3, 5, 7, 10]);
+/// assert_eq!(idx.extract(4..8), "cada".bytes().map(|c| c as u64).collect::<Vec<u64>>());
+/// ```
+pub struct FmIndex {
+    rate: usize,
+    alphabet: Vec<u64>,
+    bwt: WaveletMatrix,
+    starts: Vec<u64>,
+    sampled: Dense64,
+    samples: IntVec,
+    rows: IntVec,
+}
+
+impl FmIndex {
+    /// Create an index of `text`, sampling every `rate`-th position.
+    pub fn new<S: Copy + Into<u64>>(text: &[S], rate: usize) -> FmIndex {
+        assert!(rate >= 1);
+        let mut alphabet: Vec<u64> = text.iter().map(|c| (*c).into()).collect();
+        alphabet.sort();
+        alphabet.dedup();
+        let mut symbols: Vec<usize> = text
+            .iter()
+            .map(|c| alphabet.partition_point(|a| *a < (*c).into()) + 1)
+            .collect();
+        symbols.push(0);
+        let sigma = alphabet.len() + 1;
+        let sa = suffix_array(&symbols, sigma);
+        let n = symbols.len();
+
+        let bwt: Vec<u64> = sa
+            .iter()
+            .map(|p| symbols[if *p == 0 { n - 1 } else { p - 1 }] as u64)
+            .collect();
+        let mut starts = vec![0u64; sigma + 1];
+        for c in symbols.iter() {
+            starts[c + 1] += 1;
+        }
+        for c in 1..=sigma {
+            starts[c] += starts[c - 1];
+        }
+
+        let width = domain_bits(n as u64);
+        let mut sampled_rows: Vec<u64> = Vec::new();
+        let mut samples = IntVec::with_capacity(width, n.div_ceil(rate));
+        let mut rows = IntVec::new(width);
+        for _p in (0..n).step_by(rate) {
+            rows.push(0);
+        }
+        for (i, p) in sa.iter().enumerate() {
+            if p % rate == 0 {
+                sampled_rows.push(i as u64);
+                samples.push(*p as u64);
+                rows.set(p / rate, i as u64);
+            }
+        }
+
+        FmIndex {
+            rate,
+            alphabet,
+            bwt: WaveletMatrix::from_slice(domain_bits(sigma as u64), &bwt),
+            starts,
+            sampled: Dense64::from_positions(n as u64, &sampled_rows),
+            samples,
+            rows,
+        }
+    }
+
+    /// Return the length of the text.
+    pub fn len(&self) -> usize {
+        self.bwt.len() - 1
+    }
+
+    /// Return true if the text is empty.
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Return the row of the suffix array for the suffix before that in row `i`.
+    fn lf(&self, c: u64, i: usize) -> usize {
+        self.starts[c as usize] as usize + self.bwt.rank(c, i)
+    }
+
+    /// Return the range of rows of the suffix array whose suffixes start with `pattern`.
+    pub fn lookup<S: Copy + Into<u64>>(&self, pattern: &[S]) -> std::ops::Range<usize> {
+        let (mut lo, mut hi) = (0, self.bwt.len());
+        for c in pattern.iter().rev() {
+            let c = match self.alphabet.binary_search(&(*c).into()) {
+                Ok(r) => r as u64 + 1,
+                Err(_) => return 0..0,
+            };
+            (lo, hi) = (self.lf(c, lo), self.lf(c, hi));
+            if lo >= hi {
+                return 0..0;
+            }
+        }
+        lo..hi
+    }
+
+    /// Return the number of occurrences of `pattern` in the text.
+    pub fn count<S: Copy + Into<u64>>(&self, pattern: &[S]) -> usize {
+        self.lookup(pattern).len()
+    }
+
+    /// Return the text position of the suffix in row `i` of the suffix array.
+    pub fn sa(&self, i: usize) -> usize {
+        let mut i = i;
+        let mut steps = 0;
+        while !self.sampled.contains(i as u64) {
+            i = self.lf(self.bwt.access(i), i);
+            steps += 1;
+        }
+        self.samples.get(self.sampled.rank(i as u64)) as usize + steps
+    }
+
+    /// Return the positions of the occurrences of `pattern` in the text, in
+    /// increasing order.
+    pub fn locate<S: Copy + Into<u64>>(&self, pattern: &[S]) -> Vec<usize> {
+        let mut positions: Vec<usize> = self.lookup(pattern).map(|i| self.sa(i)).collect();
+        positions.sort();
+        positions
+    }
+
+    /// Return the symbols of the text in `range`.
+    pub fn extract(&self, range: std::ops::Range<usize>) -> Vec<u64> {
+        assert!(range.start <= range.end && range.end <= self.len());
+        // Start from the nearest sampled position at or after the end, or the
+        // terminator, whose suffix is in row 0.
+        let mut p = range.end.div_ceil(self.rate) * self.rate;
+        let mut i = if p < self.bwt.len() {
+            self.rows.get(p / self.rate) as usize
+        } else {
+            p = self.len();
+            0
+        };
+        let mut res: Vec<u64> = Vec::with_capacity(p - range.start);
+        while p > range.start {
+            let c = self.bwt.access(i);
+            res.push(self.alphabet[c as usize - 1]);
+            i = self.lf(c, i);
+            p -= 1;
+        }
+        res.reverse();
+        res.truncate(range.end - range.start);
+        res
+    }
+}
+
+impl Persistent for FmIndex {
+    const TYPE_TAG: [u8; 4] = *b"FMIX";
+
+    fn params(&self) -> Vec<u64> {
+        vec![self.rate as u64]
+    }
+
+    fn save_body<Sink>(&self, sink: &mut Sink) -> std::io::Result<()>
+    where
+        Sink: std::io::Write,
+    {
+        save_vec(sink, &self.alphabet)?;
+        self.bwt.save(sink)?;
+        save_vec(sink, &self.starts)?;
+        self.sampled.save(sink)?;
+        self.samples.save(sink)?;
+        self.rows.save(sink)?;
+        Ok(())
+    }
+
+    fn load_body<Source>(params: &[u64], source: &mut Source) -> std::io::Result<Self>
+    where
+        Source: std::io::Read,
+    {
+        check_params(&Self::TYPE_TAG, params, 1)?;
+        let rate: usize = to_usize(params[0])?;
+        let alphabet = load_vec_u64(source)?;
+        let bwt: WaveletMatrix = *(WaveletMatrix::load(source)?);
+        let starts = load_vec_u64(source)?;
+        let sampled: Dense64 = *(Dense64::load(source)?);
+        let samples: IntVec = *(IntVec::load(source)?);
+        let rows: IntVec = *(IntVec::load(source)?);
+        let n = bwt.len();
+        if rate == 0
+            || n == 0
+            || alphabet.windows(2).any(|w| w[0] >= w[1])
+            || starts.len() != alphabet.len() + 2
+            || starts[starts.len() - 1] != n as u64
+            || sampled.size() != n as u64
+            || samples.len() != sampled.count()
+            || rows.len() != n.div_ceil(rate)
+        {
+            return Err(invalid_data(String::from("inconsistent FM-index")));
+        }
+        Ok(FmIndex {
+            rate,
+            alphabet,
+            bwt,
+            starts,
+            sampled,
+            samples,
+            rows,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use random::Source;
+
+    fn make_text(n: usize, seed: u64) -> Vec<u8> {
+        let mut rng = random::default(seed);
+        (0..n).map(|_i| b"ACGT"[(rng.read_u64() % 4) as usize]).collect()
+    }
+
+    fn naive_locate(text: &[u8], pattern: &[u8]) -> Vec<usize> {
+        (0..(text.len() + 1).saturating_sub(pattern.len()))
+            .filter(|p| text[*p..].starts_with(pattern))
+            .collect()
+    }
+
+    #[test]
+    fn test_count_locate_1() {
+        let text = make_text(5000, 19);
+        let idx = FmIndex::new(&text, 8);
+        assert_eq!(idx.len(), text.len());
+        for len in [1, 2, 5, 9] {
+            for p in (0..text.len() - len).step_by(97) {
+                let pattern = &text[p..p + len];
+                let expected = naive_locate(&text, pattern);
+                assert_eq!(idx.count(pattern), expected.len());
+                assert_eq!(idx.locate(pattern), expected);
+            }
+        }
+        assert_eq!(idx.count("ACGTN".as_bytes()), 0);
+        assert_eq!(idx.count::<u8>(&[]), text.len() + 1);
+    }
+
+    #[test]
+    fn test_extract_1() {
+        let text = make_text(1000, 23);
+        let idx = FmIndex::new(&text, 5);
+        let expected: Vec<u64> = text.iter().map(|c| *c as u64).collect();
+        for start in (0..text.len()).step_by(13) {
+            for len in [0, 1, 4, 17] {
+                let end = (start + len).min(text.len());
+                assert_eq!(idx.extract(start..end), expected[start..end]);
+            }
+        }
+        assert_eq!(idx.extract(0..text.len()), expected);
+        let sa: Vec<usize> = (0..=text.len()).map(|i| idx.sa(i)).collect();
+        assert_eq!(sa[0], text.len());
+    }
+
+    #[test]
+    fn test_load_and_save_1() {
+        let text: Vec<u64> = (0..3000u64).map(|i| (i * i) % 37).collect();
+        let idx = FmIndex::new(&text, 16);
+        let mut bytes: Vec<u8> = Vec::new();
+        idx.save(&mut bytes).expect("save failed");
+        let other = FmIndex::load(&mut bytes.as_slice()).expect("load failed");
+        assert_eq!(other.locate(&text[100..104]), idx.locate(&text[100..104]));
+        assert_eq!(other.extract(2000..2100), text[2000..2100]);
+    }
+
+    #[test]
+    fn test_large_symbols_1() {
+        // The alphabet is the distinct symbols, however large they are.
+        let text: Vec<u64> = vec![u64::MAX, 1 << 40, u64::MAX, 7, 1 << 40, u64::MAX];
+        let idx = FmIndex::new(&text, 2);
+        assert_eq!(idx.alphabet, vec![7, 1 << 40, u64::MAX]);
+        assert_eq!(idx.locate(&[u64::MAX]), vec![0, 2, 5]);
+        assert_eq!(idx.locate(&[1 << 40, u64::MAX]), vec![1, 4]);
+        assert_eq!(idx.count(&[8u64]), 0);
+        assert_eq!(idx.extract(0..6), text);
+    }
+}
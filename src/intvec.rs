@@ -1,20 +1,40 @@
 //! A module for storing unsigned integers of different widths.
 
-use crate::persist::{Persistent, save_vec, load_vec_u64, load_usize};
+use crate::{
+    error::{check_index, check_param, check_value, Result},
+    persist::{
+        check_params, invalid_data, load_vec_u64, save_vec, to_usize, ByteReader, Persistent, View,
+    },
+};
 
 /// A vector of unsigned integers.
 ///
-/// Values are stored in a vector of words.
+/// Values are stored in a vector of words. The storage for the words is a type
+/// parameter, so the vector may own its words or borrow them from a saved
+/// structure (see [`IntVecView`]).
 ///
-pub struct IntVec {
+pub struct IntVec<W = Vec<u64>> {
     b: usize,
     n: usize,
-    words: Vec<u64>,
+    words: W,
+}
+
+/// An [`IntVec`] borrowing its words from a byte buffer written by [`Persistent::save`].
+pub type IntVecView<'a> = IntVec<&'a [u64]>;
+
+/// Return a mask of the low `b` bits of a word, for `b` in `[0, 64]`.
+fn low_mask(b: usize) -> u64 {
+    if b == 0 {
+        0
+    } else {
+        u64::MAX >> (64 - b)
+    }
 }
 
 impl IntVec {
     /// Create an empty vector for integers of the requested width.
     pub fn new(b: usize) -> IntVec {
+        assert!(b <= 64);
         IntVec {
             b,
             n: 0,
@@ -22,9 +42,38 @@ impl IntVec {
         }
     }
 
-    /// Return the length of the vector.
-    pub fn len(&self) -> usize {
-        self.n
+    /// Create an empty vector for integers of the requested width, with room
+    /// for `n` integers before reallocating.
+    pub fn with_capacity(b: usize, n: usize) -> IntVec {
+        assert!(b <= 64);
+        IntVec {
+            b,
+            n: 0,
+            words: Vec::with_capacity((n * b).div_ceil(64)),
+        }
+    }
+
+    /// Create an empty vector, or return an error if `b` is greater than 64.
+    pub fn try_new(b: usize) -> Result<IntVec> {
+        check_param("b", b as u64, 0, 64)?;
+        Ok(IntVec::new(b))
+    }
+
+    /// Append a value to the vector, or return an error if it does not fit
+    /// in the width of the vector.
+    pub fn try_push(&mut self, value: u64) -> Result<()> {
+        check_value(value, self.b)?;
+        self.push(value);
+        Ok(())
+    }
+
+    /// Set an element in the vector, or return an error if `idx` is out of
+    /// range or `value` does not fit in the width of the vector.
+    pub fn try_set(&mut self, idx: usize, value: u64) -> Result<()> {
+        check_index(idx, self.n)?;
+        check_value(value, self.b)?;
+        self.set(idx, value);
+        Ok(())
     }
 
     /// Append a value to the vector.
@@ -38,9 +87,14 @@ impl IntVec {
         self.set(idx, value)
     }
 
-    /// Get an element from the vector
-    pub fn get(&self, idx: usize) -> u64 {
+    /// Set an element in the vector.
+    pub fn set(&mut self, idx: usize, value: u64) {
         assert!(idx < self.len());
+        assert!(value <= low_mask(self.b));
+        if self.b == 0 {
+            return;
+        }
+
         let begin_bit = idx * self.b;
         let end_bit = (idx + 1) * self.b;
 
@@ -51,22 +105,48 @@ impl IntVec {
 
         if end_word != begin_word && end_bit_in_word > 0 {
             // Spanning 2 words
-            let low_bits = self.words[begin_word] >> begin_bit_in_word;
-            let high_bits = self.words[end_word] & ((1u64 << end_bit_in_word) - 1);
-            low_bits | (high_bits << (self.b - end_bit_in_word))
+            let low_word_bits = self.b - end_bit_in_word;
+            let low_bits_mask = (1u64 << low_word_bits) - 1;
+            let low_bits = value & low_bits_mask;
+            let high_bits = value >> low_word_bits;
+            let low_word_mask = !(low_bits_mask << begin_bit_in_word);
+            let low_word = self.words[begin_word];
+            self.words[begin_word] = (low_word & low_word_mask) | (low_bits << begin_bit_in_word);
+            let high_word_mask = !((1u64 << end_bit_in_word) - 1);
+            let high_word = self.words[end_word];
+            self.words[end_word] = (high_word & high_word_mask) | high_bits;
         } else {
             // All the bits are in 1 word
             let w = self.words[begin_word];
-            let mask = (1u64 << self.b) - 1;
-            (w >> begin_bit_in_word) & mask
+            let mask = !(low_mask(self.b) << begin_bit_in_word);
+            self.words[begin_word] = (w & mask) | (value << begin_bit_in_word);
         }
     }
+}
 
-    /// Set an element in the vector.
-    pub fn set(&mut self, idx: usize, value: u64) {
-        assert!(idx < self.len());
-        assert!(value < (1u64 << self.b));
+impl<W: AsRef<[u64]>> IntVec<W> {
+    /// Return the length of the vector.
+    pub fn len(&self) -> usize {
+        self.n
+    }
 
+    /// Return true if the vector is empty.
+    pub fn is_empty(&self) -> bool {
+        self.n == 0
+    }
+
+    /// Return the number of bits used to store each value.
+    pub fn width(&self) -> usize {
+        self.b
+    }
+
+    /// Get an element from the vector
+    pub fn get(&self, idx: usize) -> u64 {
+        assert!(idx < self.len());
+        if self.b == 0 {
+            return 0;
+        }
+        let words = self.words.as_ref();
         let begin_bit = idx * self.b;
         let end_bit = (idx + 1) * self.b;
 
@@ -77,46 +157,100 @@ impl IntVec {
 
         if end_word != begin_word && end_bit_in_word > 0 {
             // Spanning 2 words
-            let low_word_bits = self.b - end_bit_in_word;
-            let low_bits_mask = (1u64 << low_word_bits) - 1;
-            let low_bits = value & low_bits_mask;
-            let high_bits = value >> low_word_bits;
-            let low_word_mask = !(low_bits_mask << begin_bit_in_word);
-            let low_word = self.words[begin_word];
-            self.words[begin_word] = (low_word & low_word_mask) | (low_bits << begin_bit_in_word);
-            let high_word_mask = !((1u64 << end_bit_in_word) - 1);
-            let high_word = self.words[end_word];
-            self.words[end_word] = (high_word & high_word_mask) | high_bits;
+            let low_bits = words[begin_word] >> begin_bit_in_word;
+            let high_bits = words[end_word] & ((1u64 << end_bit_in_word) - 1);
+            low_bits | (high_bits << (self.b - end_bit_in_word))
         } else {
             // All the bits are in 1 word
-            let w = self.words[begin_word];
-            let mask = !(((1u64 << self.b) - 1) << begin_bit_in_word);
-            self.words[begin_word] = (w & mask) | (value << begin_bit_in_word);
+            let w = words[begin_word];
+            (w >> begin_bit_in_word) & low_mask(self.b)
         }
     }
 }
 
 impl Persistent for IntVec {
-    fn load<Source>(source: &mut Source) -> std::io::Result<Box<Self>>
+    const TYPE_TAG: [u8; 4] = *b"IVEC";
+
+    fn params(&self) -> Vec<u64> {
+        vec![self.b as u64, self.n as u64]
+    }
+
+    fn load_body<Source>(params: &[u64], source: &mut Source) -> std::io::Result<Self>
     where
         Source: std::io::Read,
     {
-        let b: usize = load_usize(source)?;
-        let n: usize = load_usize(source)?;
+        check_params(&Self::TYPE_TAG, params, 2)?;
+        let b: usize = to_usize(params[0])?;
+        let n: usize = to_usize(params[1])?;
         let words: Vec<u64> = load_vec_u64(source)?;
-        Ok(Box::new(IntVec {b, n, words}))
+        if b > 64 || (words.len() as u128) * 64 < (n as u128) * (b as u128) {
+            return Err(invalid_data(String::from("inconsistent IntVec length")));
+        }
+        Ok(IntVec { b, n, words })
     }
 
-    fn save<Sink>(&self, sink: &mut Sink) -> std::io::Result<()>
+    fn save_body<Sink>(&self, sink: &mut Sink) -> std::io::Result<()>
     where
         Sink: std::io::Write,
     {
-        sink.write_all(&self.b.to_ne_bytes())?;
-        sink.write_all(&self.n.to_ne_bytes())?;
         save_vec(sink, &self.words)
     }
 }
 
+impl<'a> View<'a> for IntVecView<'a> {
+    const TYPE_TAG: [u8; 4] = <IntVec as Persistent>::TYPE_TAG;
+
+    fn view_body(params: &[u64], source: &mut ByteReader<'a>) -> std::io::Result<Self> {
+        check_params(&Self::TYPE_TAG, params, 2)?;
+        let b: usize = to_usize(params[0])?;
+        let n: usize = to_usize(params[1])?;
+        let words: &'a [u64] = source.slice_u64()?;
+        if b > 64 || (words.len() as u128) * 64 < (n as u128) * (b as u128) {
+            return Err(invalid_data(String::from("inconsistent IntVec length")));
+        }
+        Ok(IntVec { b, n, words })
+    }
+}
+
+#[cfg(feature = "serde")]
+impl serde::Serialize for IntVec {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> std::result::Result<S::Ok, S::Error> {
+        use serde::ser::SerializeStruct;
+        let mut state = serializer.serialize_struct("IntVec", 3)?;
+        state.serialize_field("b", &(self.b as u64))?;
+        state.serialize_field("n", &(self.n as u64))?;
+        state.serialize_field("words", &self.words)?;
+        state.end()
+    }
+}
+
+#[cfg(feature = "serde")]
+#[derive(serde::Deserialize)]
+#[serde(rename = "IntVec")]
+struct IntVecData {
+    b: u64,
+    n: u64,
+    words: Vec<u64>,
+}
+
+#[cfg(feature = "serde")]
+impl<'de> serde::Deserialize<'de> for IntVec {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> std::result::Result<Self, D::Error> {
+        use serde::de::Error;
+        let data = IntVecData::deserialize(deserializer)?;
+        check_param("b", data.b, 0, 64).map_err(D::Error::custom)?;
+        let n = usize::try_from(data.n).map_err(D::Error::custom)?;
+        if (data.words.len() as u128) * 64 < (data.n as u128) * (data.b as u128) {
+            return Err(D::Error::custom("inconsistent IntVec length"));
+        }
+        Ok(IntVec {
+            b: data.b as usize,
+            n,
+            words: data.words,
+        })
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -139,8 +273,8 @@ mod tests {
             v.push(*x);
         }
         assert!(v.words.len() * 64 >= n * b);
-        for i in 0..n {
-            assert_eq!(v.get(i), xs[i]);
+        for (i, &x) in xs.iter().enumerate() {
+            assert_eq!(v.get(i), x);
         }
     }
 
@@ -160,8 +294,8 @@ mod tests {
             v.push(*x);
         }
         assert!(v.words.len() * 64 >= n * b);
-        for i in 0..n {
-            assert_eq!(v.get(i), xs[i]);
+        for (i, &x) in xs.iter().enumerate() {
+            assert_eq!(v.get(i), x);
         }
     }
 
@@ -181,8 +315,8 @@ mod tests {
             v.push(*x);
         }
         assert!(v.words.len() * 64 >= n * b);
-        for i in 0..n {
-            assert_eq!(v.get(i), xs[i]);
+        for (i, &x) in xs.iter().enumerate() {
+            assert_eq!(v.get(i), x);
         }
     }
 
@@ -202,8 +336,55 @@ mod tests {
             v.push(*x);
         }
         assert!(v.words.len() * 64 >= n * b);
-        for i in 0..n {
-            assert_eq!(v.get(i), xs[i]);
+        for (i, &x) in xs.iter().enumerate() {
+            assert_eq!(v.get(i), x);
+        }
+    }
+
+    #[test]
+    fn test_intvec_widths_1() {
+        let mut rng = random::default(19);
+        for b in [0, 1, 64] {
+            let xs: Vec<u64> = (0..1000).map(|_i| rng.read_u64() & low_mask(b)).collect();
+            let mut v = IntVec::with_capacity(b, xs.len());
+            for x in xs.iter() {
+                v.push(*x);
+            }
+            assert_eq!(v.len(), xs.len());
+            for (i, &x) in xs.iter().enumerate() {
+                assert_eq!(v.get(i), x);
+            }
+        }
+    }
+
+    #[test]
+    fn test_intvec_try_1() {
+        use crate::error::Error;
+
+        assert!(IntVec::try_new(65).is_err());
+        let mut v = IntVec::try_new(4).unwrap();
+        assert_eq!(v.try_push(15), Ok(()));
+        assert_eq!(v.try_push(16), Err(Error::TooWide { value: 16, width: 4 }));
+        assert_eq!(v.len(), 1);
+        assert_eq!(v.try_set(0, 9), Ok(()));
+        assert_eq!(v.try_set(1, 9), Err(Error::IndexOutOfRange { index: 1, len: 1 }));
+        assert_eq!(v.get(0), 9);
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn test_intvec_serde_1() {
+        let mut v = IntVec::new(13);
+        for i in 0..100u64 {
+            v.push((i * 97) % 8192);
+        }
+        let json = serde_json::to_string(&v).expect("serialize failed");
+        let w: IntVec = serde_json::from_str(&json).expect("deserialize failed");
+        for i in 0..100 {
+            assert_eq!(w.get(i), v.get(i));
         }
+
+        assert!(serde_json::from_str::<IntVec>(r#"{"b":65,"n":0,"words":[]}"#).is_err());
+        assert!(serde_json::from_str::<IntVec>(r#"{"b":13,"n":10,"words":[0]}"#).is_err());
     }
 }
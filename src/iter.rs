@@ -0,0 +1,172 @@
+//! Traits and types for iterating over the elements of sets.
+
+use crate::set::ImpliedSet;
+
+/// The `IterableSet` trait exposes the elements of the implied set in increasing order.
+///
+/// Iterating is much faster than calling `select` for each index in turn, since
+/// implementations walk their underlying representation sequentially.
+///
+/// #Examples
+///
+/// ```
+/// use crate::ransel::iter::IterableSet;
+/// use crate::ransel::sparse::Sparse;
+///
+/// let xs: Vec<u64> = vec![3, 17, 23, 1000, 4095];
+/// let s = Sparse::new(12, &xs);
+/// assert_eq!(s.iter().collect::<Vec<u64>>(), xs);
+/// assert_eq!(s.iter_from(18).collect::<Vec<u64>>(), vec![23, 1000, 4095]);
+/// assert_eq!(s.iter().next_back(), Some(4095));
+/// ```
+pub trait IterableSet: ImpliedSet {
+    /// The type of the iterator over the elements.
+    type Iter<'a>: Iterator<Item = u64>
+    where
+        Self: 'a;
+
+    /// Return an iterator over the elements of the set in increasing order.
+    fn iter(&self) -> Self::Iter<'_> {
+        self.iter_from(0)
+    }
+
+    /// Return an iterator over the elements of the set greater than or equal to
+    /// `value`, in increasing order.
+    fn iter_from(&self, value: u64) -> Self::Iter<'_>;
+}
+
+/// A double ended iterator over the positions of the one (or zero) bits in a
+/// sequence of words.
+#[derive(Clone)]
+pub struct BitIter<'a> {
+    words: &'a [u64],
+    ones: bool,
+    remaining: usize,
+    front: usize,
+    front_word: u64,
+    back: usize,
+    back_word: u64,
+}
+
+impl<'a> BitIter<'a> {
+    /// Create an iterator over the `count` one bits (or zero bits, if `ones` is false)
+    /// at or after position `from` among the first `size` bits of `words`.
+    pub(crate) fn new(words: &'a [u64], size: u64, from: u64, count: usize, ones: bool) -> BitIter<'a> {
+        let get = |i: usize| -> u64 {
+            let w = if ones { words[i] } else { !words[i] };
+            let end = size.saturating_sub(64 * i as u64);
+            if end < 64 {
+                w & ((1u64 << end) - 1)
+            } else {
+                w
+            }
+        };
+        if count == 0 {
+            return BitIter {
+                words,
+                ones,
+                remaining: 0,
+                front: 0,
+                front_word: 0,
+                back: 0,
+                back_word: 0,
+            };
+        }
+        let front = (from / 64) as usize;
+        let front_word = get(front) & !((1u64 << (from & 63)) - 1);
+        let back = (size.div_ceil(64) - 1) as usize;
+        let back_word = get(back);
+        BitIter {
+            words,
+            ones,
+            remaining: count,
+            front,
+            front_word,
+            back,
+            back_word,
+        }
+    }
+
+    fn word(&self, i: usize) -> u64 {
+        if self.ones {
+            self.words[i]
+        } else {
+            !self.words[i]
+        }
+    }
+}
+
+impl<'a> Iterator for BitIter<'a> {
+    type Item = u64;
+
+    fn next(&mut self) -> Option<u64> {
+        if self.remaining == 0 {
+            return None;
+        }
+        while self.front_word == 0 {
+            self.front += 1;
+            self.front_word = self.word(self.front);
+        }
+        let b = self.front_word.trailing_zeros() as u64;
+        self.front_word &= self.front_word - 1;
+        self.remaining -= 1;
+        Some(64 * self.front as u64 + b)
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (self.remaining, Some(self.remaining))
+    }
+}
+
+impl<'a> DoubleEndedIterator for BitIter<'a> {
+    fn next_back(&mut self) -> Option<u64> {
+        if self.remaining == 0 {
+            return None;
+        }
+        while self.back_word == 0 {
+            self.back -= 1;
+            self.back_word = self.word(self.back);
+        }
+        let b = 63 - self.back_word.leading_zeros() as u64;
+        self.back_word ^= 1u64 << b;
+        self.remaining -= 1;
+        Some(64 * self.back as u64 + b)
+    }
+}
+
+impl<'a> ExactSizeIterator for BitIter<'a> {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_bit_iter_1() {
+        let words: Vec<u64> = vec![0b1010_0001, 0, 1 << 63, 0b11];
+        let ones: Vec<u64> = vec![0, 5, 7, 191, 192, 193];
+        assert_eq!(BitIter::new(&words, 194, 0, 6, true).collect::<Vec<u64>>(), ones);
+        assert_eq!(BitIter::new(&words, 194, 6, 4, true).collect::<Vec<u64>>(), ones[2..]);
+        assert_eq!(
+            BitIter::new(&words, 194, 0, 6, true).rev().collect::<Vec<u64>>(),
+            ones.iter().rev().copied().collect::<Vec<u64>>()
+        );
+        let mut it = BitIter::new(&words, 194, 0, 6, true);
+        assert_eq!(it.next(), Some(0));
+        assert_eq!(it.next_back(), Some(193));
+        assert_eq!(it.next_back(), Some(192));
+        assert_eq!(it.next(), Some(5));
+        assert_eq!(it.len(), 2);
+        assert_eq!(it.collect::<Vec<u64>>(), vec![7, 191]);
+    }
+
+    #[test]
+    fn test_bit_iter_zeros_1() {
+        let words: Vec<u64> = vec![!0b1010_0001u64, !0u64, 0b11];
+        let zeros: Vec<u64> = vec![0, 5, 7, 130, 131, 132];
+        assert_eq!(BitIter::new(&words, 133, 0, 6, false).collect::<Vec<u64>>(), zeros);
+        assert_eq!(
+            BitIter::new(&words, 133, 0, 6, false).rev().collect::<Vec<u64>>(),
+            zeros.iter().rev().copied().collect::<Vec<u64>>()
+        );
+    }
+}
@@ -40,18 +40,69 @@
 //! 
 //! The [`Select`](crate::select::Select) trait exposes `select` and its associated operations.
 //! 
+//! The [`IterableSet`](crate::iter::IterableSet) trait exposes iteration over the elements,
+//! and the [`algebra`] module combines sets of any representation.
+//! 
+//! Implementations include [`Sparse`](crate::sparse::Sparse) and
+//! [`PartitionedEf`](crate::pef::PartitionedEf) for sparse sets,
+//! [`Dense64`](crate::dense64::Dense64) and [`Poppy`](crate::poppy::Poppy) for indexed
+//! dense sets, [`Rrr`](crate::rrr::Rrr)
+//! for compressed dense sets, [`Rle`](crate::rle::Rle) for sets made up of long runs, and the simpler
+//! [`NaiveSparse`](crate::naive_sparse::NaiveSparse), [`NaiveDense`](crate::naive_dense::NaiveDense)
+//! and [`Sorted`](crate::sorted::Sorted) representations. The [`Roaring`](crate::roaring::Roaring)
+//! set also supports inserting and removing elements, and the
+//! [`DynamicBitVec`](crate::dynamic::DynamicBitVec) supports inserting and removing bits.
+//! 
+//! The [`WaveletMatrix`](crate::wavelet::WaveletMatrix) builds on these to support
+//! `access`, `rank` and `select` over sequences of symbols from larger alphabets,
+//! and the [`FmIndex`](crate::fmindex::FmIndex) uses one to index the substrings of a text.
+//! The [`Csa`](crate::csa::Csa) is an alternative text index built on a [`Sparse`](crate::sparse::Sparse) set.
+//! 
+//! The [`RmmParens`](crate::parens::RmmParens) and [`Louds`](crate::louds::Louds)
+//! structures use rank and select to represent ordinal trees succinctly.
+//! 
+//! All of these may be saved and loaded in a portable format using the
+//! [`Persistent`](crate::persist::Persistent) trait. With the optional `serde` feature,
+//! the basic sets and vectors ([`Sparse`](crate::sparse::Sparse), `NaiveSparse`, `Sorted`,
+//! `NaiveDense`, [`IntVec`](crate::intvec::IntVec), [`BitVec`](crate::bitvec::BitVec) and
+//! [`Dense64`](crate::dense64::Dense64)) also implement serde's `Serialize` and `Deserialize`.
+//! Only their contents are serialized: indexes are rebuilt and invariants checked when
+//! they are deserialized.
+//!
+//! Constructors and operations panic on invalid input, but the `try_` variants,
+//! such as [`Sparse::try_new`](crate::sparse::Sparse::try_new) and
+//! [`Select::try_select`](crate::select::Select::try_select), report an
+//! [`Error`](crate::error::Error) instead.
+//! 
 
 pub mod set;
 pub mod rank;
 pub mod select;
+pub mod iter;
+pub mod algebra;
 pub mod sparse;
+pub mod pef;
+pub mod roaring;
 pub mod naive_dense;
 pub mod naive_sparse;
 pub mod sorted;
 pub mod intvec;
+pub mod dac;
+pub mod monotone;
+pub mod codes;
 pub mod bitvec;
+pub mod dynamic;
 pub mod parens;
-mod persist;
+pub mod louds;
+pub mod dense64;
+pub mod poppy;
+pub mod rrr;
+pub mod rle;
+pub mod wavelet;
+pub mod fmindex;
+pub mod csa;
+pub mod persist;
+pub mod error;
+pub mod ecode;
+mod sais;
 mod words;
-mod dense64;
-mod ecode;
@@ -0,0 +1,305 @@
+//! A succinct ordinal tree representation using the level-order unary degree
+//! sequence (LOUDS) due to Guy Jacobson:
+//!
+//! > Jacobson, G., 1989, October. Space-efficient static trees and graphs.
+//! > In 30th annual symposium on foundations of computer science (pp. 549-554).
+//! > IEEE Computer Society.
+
+use std::collections::VecDeque;
+
+use crate::{
+    bitvec::BitVec,
+    dense64::Dense64,
+    persist::{check_params, invalid_data, to_usize, Persistent},
+    rank::Rank,
+    select::{Select, Select0},
+    set::ImpliedSet,
+};
+
+/// A tree of `n` nodes stored in `2n + 1` bits.
+///
+/// The nodes are visited in breadth first order, and each node with `d` children
+/// is written as `d` one bits followed by a zero bit, after an initial `10` for
+/// a virtual super-root whose only child is the root. Nodes are numbered from 0
+/// in breadth first order, so the root is node 0, and the children of a node
+/// have consecutive numbers. The one bit for node `x` is at position
+/// `select(x)`, and the degree sequence of node `x` follows its zero bit at
+/// `select_0(x)`, so all the navigation operations take a constant number of
+/// `rank` and `select` operations over a [`Dense64`].
+///
+/// #Examples
+///
+/// ```
+/// use crate::ransel::louds::Louds;
+///
+/// //        0
+/// //      / | \
+/// //     1  2  3
+/// //    / \     \
+/// //   4   5     6
+/// let t = Louds::from_degrees(&[3, 2, 0, 1, 0, 0, 0]);
+/// assert_eq!(t.degree(0), 3);
+/// assert_eq!(t.child(0, 2), Some(3));
+/// assert_eq!(t.first_child(1), Some(4));
+/// assert_eq!(t.last_child(3), Some(6));
+/// assert_eq!(t.parent(5), Some(1));
+/// assert_eq!(t.next_sibling(2), Some(3));
+/// assert_eq!(t.next_sibling(3), None);
+/// ```
+pub struct Louds {
+    n: usize,
+    bits: Dense64,
+}
+
+impl Louds {
+    /// Create a tree from the degrees of its nodes, listed in breadth first order.
+    ///
+    /// The degrees must describe a single tree: the sum of the degrees must be one
+    /// less than the number of nodes, and every node but the root must be a
+    /// child of an earlier node.
+    pub fn from_degrees(degrees: &[usize]) -> Louds {
+        let n = degrees.len();
+        assert!(n == 0 || degrees.iter().sum::<usize>() + 1 == n);
+        // The root and the children of the first `k` nodes must include node `k`.
+        let mut reached = 1;
+        for (k, d) in degrees.iter().enumerate() {
+            assert!(reached > k, "node {} is not a child of an earlier node", k);
+            reached += d;
+        }
+        let mut bits = BitVec::new();
+        if n > 0 {
+            bits.push(true);
+            bits.push(false);
+        }
+        for d in degrees.iter() {
+            for _j in 0..*d {
+                bits.push(true);
+            }
+            bits.push(false);
+        }
+        Louds {
+            n,
+            bits: Dense64::from_bitvec(bits),
+        }
+    }
+
+    /// Create a tree from an adjacency list, where `children[v]` lists the
+    /// children of node `v` in order, and `root` is the root.
+    ///
+    /// The nodes are renumbered in breadth first order, so along with the tree,
+    /// this returns the original node for each node in the tree.
+    ///
+    /// Every node must be reachable from the root by exactly one path, and
+    /// every child must be less than `children.len()`.
+    pub fn from_adjacency(children: &[Vec<usize>], root: usize) -> (Louds, Vec<usize>) {
+        assert!(root < children.len(), "root {} is not a node", root);
+        let mut seen: Vec<bool> = vec![false; children.len()];
+        let mut order: Vec<usize> = Vec::with_capacity(children.len());
+        let mut degrees: Vec<usize> = Vec::with_capacity(children.len());
+        let mut queue: VecDeque<usize> = VecDeque::new();
+        seen[root] = true;
+        queue.push_back(root);
+        while let Some(v) = queue.pop_front() {
+            order.push(v);
+            degrees.push(children[v].len());
+            for c in children[v].iter() {
+                assert!(*c < children.len(), "child {} of node {} is not a node", c, v);
+                assert!(!seen[*c], "adjacency list is not a tree");
+                seen[*c] = true;
+                queue.push_back(*c);
+            }
+        }
+        assert_eq!(order.len(), children.len(), "nodes are unreachable from the root");
+        (Louds::from_degrees(&degrees), order)
+    }
+
+    /// Return the number of nodes in the tree.
+    pub fn len(&self) -> usize {
+        self.n
+    }
+
+    /// Return true if the tree has no nodes.
+    pub fn is_empty(&self) -> bool {
+        self.n == 0
+    }
+
+    /// Return the position of the one bit representing node `x`.
+    pub fn node_position(&self, x: usize) -> u64 {
+        assert!(x < self.n);
+        self.bits.select(x)
+    }
+
+    /// Return the node represented by the one bit at position `p`.
+    pub fn position_node(&self, p: u64) -> usize {
+        assert!(self.bits.contains(p));
+        self.bits.rank(p)
+    }
+
+    /// Return the number of children of node `x`.
+    pub fn degree(&self, x: usize) -> usize {
+        assert!(x < self.n);
+        (self.bits.select_0(x + 1) - self.bits.select_0(x) - 1) as usize
+    }
+
+    /// Return the `i`-th child (counting from 0) of node `x`, if there is one.
+    pub fn child(&self, x: usize, i: usize) -> Option<usize> {
+        assert!(x < self.n);
+        let p = self.bits.select_0(x) + 1 + i as u64;
+        if self.bits.contains(p) {
+            Some(self.bits.rank(p))
+        } else {
+            None
+        }
+    }
+
+    /// Return the first child of node `x`, if it has any children.
+    pub fn first_child(&self, x: usize) -> Option<usize> {
+        self.child(x, 0)
+    }
+
+    /// Return the last child of node `x`, if it has any children.
+    pub fn last_child(&self, x: usize) -> Option<usize> {
+        assert!(x < self.n);
+        let p = self.bits.select_0(x + 1) - 1;
+        if self.bits.contains(p) {
+            Some(self.bits.rank(p))
+        } else {
+            None
+        }
+    }
+
+    /// Return the parent of node `x`, or `None` for the root.
+    pub fn parent(&self, x: usize) -> Option<usize> {
+        let p = self.node_position(x);
+        let z = self.bits.rank_0(p);
+        if z == 0 {
+            None
+        } else {
+            Some(z - 1)
+        }
+    }
+
+    /// Return the next sibling of node `x`, if there is one.
+    pub fn next_sibling(&self, x: usize) -> Option<usize> {
+        let p = self.node_position(x);
+        if self.bits.contains(p + 1) {
+            Some(x + 1)
+        } else {
+            None
+        }
+    }
+}
+
+impl Persistent for Louds {
+    const TYPE_TAG: [u8; 4] = *b"LODS";
+
+    fn params(&self) -> Vec<u64> {
+        vec![self.n as u64]
+    }
+
+    fn save_body<Sink>(&self, sink: &mut Sink) -> std::io::Result<()>
+    where
+        Sink: std::io::Write,
+    {
+        self.bits.save(sink)
+    }
+
+    fn load_body<Source>(params: &[u64], source: &mut Source) -> std::io::Result<Self>
+    where
+        Source: std::io::Read,
+    {
+        check_params(&Self::TYPE_TAG, params, 1)?;
+        let n: usize = to_usize(params[0])?;
+        let bits: Dense64 = *(Dense64::load(source)?);
+        let expected = if n == 0 { 0 } else { 2 * n as u64 + 1 };
+        if bits.size() != expected || bits.count() != n {
+            return Err(invalid_data(String::from("inconsistent LOUDS bit vector")));
+        }
+        Ok(Louds { n, bits })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use random::Source;
+
+    /// Make a random tree as an adjacency list, with node labels in random order.
+    fn make_tree(n: usize, seed: u64) -> (Vec<Vec<usize>>, usize) {
+        let mut rng = random::default(seed);
+        let mut children: Vec<Vec<usize>> = vec![Vec::new(); n];
+        // Node labels are a scrambled permutation of 0..n.
+        let label = |i: usize| (i * 7919) % n;
+        for i in 1..n {
+            let p = (rng.read_u64() % i as u64) as usize;
+            children[label(p)].push(label(i));
+        }
+        (children, label(0))
+    }
+
+    #[test]
+    fn test_navigation_1() {
+        let n = 5000;
+        let (children, root) = make_tree(n, 19);
+        let (t, order) = Louds::from_adjacency(&children, root);
+        assert_eq!(t.len(), n);
+        let mut id: Vec<usize> = vec![0; n];
+        for (x, v) in order.iter().enumerate() {
+            id[*v] = x;
+        }
+        assert_eq!(t.parent(0), None);
+        for (x, &v) in order.iter().enumerate() {
+            let cs = &children[v];
+            assert_eq!(t.degree(x), cs.len());
+            assert_eq!(t.position_node(t.node_position(x)), x);
+            for (i, c) in cs.iter().enumerate() {
+                assert_eq!(t.child(x, i), Some(id[*c]));
+                assert_eq!(t.parent(id[*c]), Some(x));
+                let sib = cs.get(i + 1).map(|s| id[*s]);
+                assert_eq!(t.next_sibling(id[*c]), sib);
+            }
+            assert_eq!(t.child(x, cs.len()), None);
+            assert_eq!(t.first_child(x), cs.first().map(|c| id[*c]));
+            assert_eq!(t.last_child(x), cs.last().map(|c| id[*c]));
+        }
+    }
+
+    #[test]
+    fn test_load_and_save_1() {
+        let (children, root) = make_tree(1000, 23);
+        let (t, _order) = Louds::from_adjacency(&children, root);
+        let mut bytes: Vec<u8> = Vec::new();
+        t.save(&mut bytes).expect("save failed");
+        let u = Louds::load(&mut bytes.as_slice()).expect("load failed");
+        assert_eq!(u.len(), t.len());
+        for x in 0..t.len() {
+            assert_eq!(u.degree(x), t.degree(x));
+            assert_eq!(u.parent(x), t.parent(x));
+        }
+    }
+
+    #[test]
+    #[should_panic(expected = "not a child of an earlier node")]
+    fn test_bad_degrees_1() {
+        Louds::from_degrees(&[0, 1]);
+    }
+
+    #[test]
+    #[should_panic(expected = "unreachable")]
+    fn test_unreachable_1() {
+        Louds::from_adjacency(&[vec![1], vec![], vec![]], 0);
+    }
+
+    #[test]
+    #[should_panic(expected = "not a node")]
+    fn test_bad_child_1() {
+        Louds::from_adjacency(&[vec![1, 3], vec![], vec![]], 0);
+    }
+
+    #[test]
+    #[should_panic(expected = "not a tree")]
+    fn test_shared_child_1() {
+        Louds::from_adjacency(&[vec![1, 2], vec![2], vec![]], 0);
+    }
+}
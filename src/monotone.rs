@@ -0,0 +1,226 @@
+//! Non-decreasing sequences of integers, such as offset tables, using the
+//! Elias–Fano coding of [`Sparse`] sets.
+
+use crate::{
+    algebra::domain_bits,
+    error::{check_value, Error, Result},
+    iter::IterableSet,
+    persist::{check_params, Persistent},
+    rank::Rank,
+    select::Select,
+    set::ImpliedSet,
+    sparse::{Sparse, SparseBuilder, SparseIter},
+};
+
+/// The `Monotone` data structure is a non-decreasing sequence of integers.
+///
+/// It is stored just as a [`Sparse`] set is, except that equal consecutive
+/// values are allowed, so `get` is a `select` and `find` is a `rank`. Built with
+/// [`prefix_sums`](Monotone::prefix_sums), it holds the offsets of a sequence of
+/// records from their lengths, in little more than 2 bits per record plus the
+/// bits needed for the average length.
+///
+/// #Examples
+///
+/// ```
+/// use crate::ransel::monotone::Monotone;
+///
+/// let offsets = Monotone::prefix_sums(&[3, 0, 4, 10, 2]);
+/// assert_eq!(offsets.len(), 6);
+/// assert_eq!(offsets.sum(3), 7);
+/// assert_eq!(offsets.length(1), 0);
+/// assert_eq!(offsets.length(3), 10);
+/// // The record containing position 8 in the buffer.
+/// assert_eq!(offsets.find(8), Some(3));
+///
+/// let m = Monotone::new(&[2, 2, 5, 5, 5, 9]);
+/// assert_eq!(m.get(4), 5);
+/// assert_eq!(m.find(5), Some(4));
+/// assert_eq!(m.find(1), None);
+/// ```
+pub struct Monotone {
+    seq: Sparse,
+}
+
+impl Monotone {
+    /// Create a new sequence from non-decreasing values less than `2**63`.
+    pub fn new(values: &[u64]) -> Monotone {
+        match Monotone::try_new(values) {
+            Ok(m) => m,
+            Err(e) => panic!("{}", e),
+        }
+    }
+
+    /// Create a new sequence, or return an error if `values` are not
+    /// non-decreasing, or not less than `2**63`.
+    pub fn try_new(values: &[u64]) -> Result<Monotone> {
+        let max = values.iter().copied().max().unwrap_or(0);
+        check_value(max, 63)?;
+        let mut builder = SparseBuilder::try_new_monotone(domain_bits(max + 1).max(1), values.len())?;
+        for x in values.iter() {
+            builder.try_push(*x)?;
+        }
+        Ok(Monotone {
+            seq: builder.finish(),
+        })
+    }
+
+    /// Create the sequence of prefix sums of `lengths`, starting with 0, so
+    /// that element `i` is the sum of the first `i` lengths.
+    ///
+    /// The sum of the lengths must be less than `2**63`.
+    pub fn prefix_sums(lengths: &[u64]) -> Monotone {
+        match Monotone::try_prefix_sums(lengths) {
+            Ok(m) => m,
+            Err(e) => panic!("{}", e),
+        }
+    }
+
+    /// Create the sequence of prefix sums of `lengths`, or return an error if
+    /// their sum is not less than `2**63`.
+    pub fn try_prefix_sums(lengths: &[u64]) -> Result<Monotone> {
+        let mut sums: Vec<u64> = Vec::with_capacity(lengths.len() + 1);
+        let mut sum: u64 = 0;
+        sums.push(sum);
+        for len in lengths.iter() {
+            sum = sum.checked_add(*len).ok_or(Error::TooWide { value: u64::MAX, width: 63 })?;
+            sums.push(sum);
+        }
+        Monotone::try_new(&sums)
+    }
+
+    /// Return the number of values.
+    pub fn len(&self) -> usize {
+        self.seq.count()
+    }
+
+    /// Return true if there are no values.
+    pub fn is_empty(&self) -> bool {
+        self.seq.count() == 0
+    }
+
+    /// Return the value at position `i`.
+    pub fn get(&self, i: usize) -> u64 {
+        assert!(i < self.len());
+        self.seq.select(i)
+    }
+
+    /// Return the largest position `i` with `self.get(i) <= value`, or `None`
+    /// if every value is greater than `value`.
+    pub fn find(&self, value: u64) -> Option<usize> {
+        self.seq.rank(value.saturating_add(1)).checked_sub(1)
+    }
+
+    /// For a sequence of prefix sums, return the sum of the first `i` lengths.
+    pub fn sum(&self, i: usize) -> u64 {
+        self.get(i)
+    }
+
+    /// For a sequence of prefix sums, return the `i`-th length.
+    pub fn length(&self, i: usize) -> u64 {
+        self.get(i + 1) - self.get(i)
+    }
+
+    /// Return an iterator over the values.
+    pub fn iter(&self) -> MonotoneIter<'_> {
+        MonotoneIter(self.seq.iter())
+    }
+}
+
+/// An iterator over the values of a [`Monotone`] sequence.
+pub struct MonotoneIter<'a>(SparseIter<'a, Vec<u64>>);
+
+impl<'a> Iterator for MonotoneIter<'a> {
+    type Item = u64;
+
+    fn next(&mut self) -> Option<u64> {
+        self.0.next()
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.0.size_hint()
+    }
+}
+
+impl<'a> DoubleEndedIterator for MonotoneIter<'a> {
+    fn next_back(&mut self) -> Option<u64> {
+        self.0.next_back()
+    }
+}
+
+impl<'a> ExactSizeIterator for MonotoneIter<'a> {}
+
+impl Persistent for Monotone {
+    const TYPE_TAG: [u8; 4] = *b"MONO";
+
+    fn params(&self) -> Vec<u64> {
+        Vec::new()
+    }
+
+    fn save_body<Sink>(&self, sink: &mut Sink) -> std::io::Result<()>
+    where
+        Sink: std::io::Write,
+    {
+        self.seq.save(sink)
+    }
+
+    fn load_body<Source>(params: &[u64], source: &mut Source) -> std::io::Result<Self>
+    where
+        Source: std::io::Read,
+    {
+        check_params(&Self::TYPE_TAG, params, 0)?;
+        let seq = Sparse::load_monotone(source)?;
+        Ok(Monotone { seq })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::error::Error;
+    use random::Source;
+
+    #[test]
+    fn test_get_find_1() {
+        let mut rng = random::default(19);
+        let mut xs: Vec<u64> = (0..10000).map(|_i| rng.read_u64() % 3000).collect();
+        xs.sort();
+        let m = Monotone::new(&xs);
+        assert_eq!(m.len(), xs.len());
+        for (i, &x) in xs.iter().enumerate() {
+            assert_eq!(m.get(i), x);
+        }
+        assert_eq!(m.iter().collect::<Vec<u64>>(), xs);
+        for v in 0..3100 {
+            let expected = xs.partition_point(|x| *x <= v).checked_sub(1);
+            assert_eq!(m.find(v), expected);
+        }
+        assert_eq!(Monotone::try_new(&[1, 1, 0]).err(), Some(Error::NotIncreasing { index: 2 }));
+        assert_eq!(Monotone::try_new(&[5, 3]).err(), Some(Error::NotIncreasing { index: 1 }));
+        assert_eq!(Monotone::try_new(&[0, 1 << 63]).err(), Some(Error::TooWide { value: 1 << 63, width: 63 }));
+        assert_eq!(m.iter().next_back(), xs.last().copied());
+        assert!(Monotone::new(&[]).is_empty());
+    }
+
+    #[test]
+    fn test_prefix_sums_1() {
+        let mut rng = random::default(23);
+        let lengths: Vec<u64> = (0..5000).map(|_i| rng.read_u64() % 200).collect();
+        let m = Monotone::prefix_sums(&lengths);
+        let mut sum = 0;
+        for (i, &len) in lengths.iter().enumerate() {
+            assert_eq!(m.sum(i), sum);
+            assert_eq!(m.length(i), len);
+            sum += len;
+        }
+        assert_eq!(m.sum(lengths.len()), sum);
+        assert!(Monotone::try_prefix_sums(&[1 << 62, 1 << 62]).is_err());
+        assert!(Monotone::try_prefix_sums(&[u64::MAX, 1]).is_err());
+
+        let mut bytes: Vec<u8> = Vec::new();
+        m.save(&mut bytes).expect("save failed");
+        let other = Monotone::load(&mut bytes.as_slice()).expect("load failed");
+        assert_eq!(other.iter().collect::<Vec<u64>>(), m.iter().collect::<Vec<u64>>());
+    }
+}
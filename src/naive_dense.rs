@@ -1,6 +1,15 @@
 //! A simple dense set based on an un-indexed bit vector.
 
-use crate::{bitvec::BitVec, rank::Rank, set::ImpliedSet, select::Select, words::select64};
+use crate::{
+    algebra::FromSorted,
+    bitvec::{AsWords, BitVec, FromBitVec},
+    iter::{BitIter, IterableSet},
+    persist::{check_params, Persistent},
+    rank::Rank,
+    select::{Select, Successor},
+    set::ImpliedSet,
+    words::select64,
+};
 
 /// A sparse set based on an un-indexed bit vector.
 pub struct NaiveDense {
@@ -69,6 +78,7 @@ impl Rank for NaiveDense {
 }
 
 impl Select for NaiveDense {
+    #[allow(clippy::needless_range_loop)]
     fn select(&self, index: usize) -> u64 {
         assert!(index < self.count());
         let words = self.bits.as_words();
@@ -86,6 +96,95 @@ impl Select for NaiveDense {
     }
 }
 
+impl Successor for NaiveDense {}
+
+impl FromBitVec for NaiveDense {
+    fn from_bitvec(bits: BitVec) -> Self {
+        NaiveDense::new(bits)
+    }
+}
+
+impl AsWords for NaiveDense {
+    fn as_words(&self) -> &[u64] {
+        self.bits.as_words()
+    }
+}
+
+impl FromSorted for NaiveDense {
+    fn from_sorted(size: u64, elements: &[u64]) -> Self {
+        let mut bits = BitVec::new();
+        let mut j = 0;
+        for x in 0..size {
+            let present = j < elements.len() && elements[j] == x;
+            if present {
+                j += 1;
+            }
+            bits.push(present);
+        }
+        NaiveDense::new(bits)
+    }
+}
+
+impl IterableSet for NaiveDense {
+    type Iter<'a> = BitIter<'a>;
+
+    fn iter_from(&self, value: u64) -> Self::Iter<'_> {
+        let count = self.count() - self.rank(value);
+        BitIter::new(self.bits.as_words(), self.size(), value, count, true)
+    }
+}
+
+impl Persistent for NaiveDense {
+    const TYPE_TAG: [u8; 4] = *b"NDNS";
+
+    fn params(&self) -> Vec<u64> {
+        Vec::new()
+    }
+
+    fn save_body<Sink>(&self, sink: &mut Sink) -> std::io::Result<()>
+    where
+        Sink: std::io::Write,
+    {
+        self.bits.save(sink)
+    }
+
+    fn load_body<Source>(params: &[u64], source: &mut Source) -> std::io::Result<Self>
+    where
+        Source: std::io::Read,
+    {
+        check_params(&Self::TYPE_TAG, params, 0)?;
+        let bits: BitVec = *(BitVec::load(source)?);
+        Ok(NaiveDense::new(bits))
+    }
+}
+
+#[cfg(feature = "serde")]
+impl serde::Serialize for NaiveDense {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        use serde::ser::SerializeStruct;
+        let mut state = serializer.serialize_struct("NaiveDense", 1)?;
+        state.serialize_field("bits", &self.bits)?;
+        state.end()
+    }
+}
+
+#[cfg(feature = "serde")]
+#[derive(serde::Deserialize)]
+#[serde(rename = "NaiveDense")]
+struct NaiveDenseData {
+    bits: BitVec,
+}
+
+/// The count of elements is recomputed from the bits, which are checked as
+/// a [`BitVec`] is.
+#[cfg(feature = "serde")]
+impl<'de> serde::Deserialize<'de> for NaiveDense {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let data = NaiveDenseData::deserialize(deserializer)?;
+        Ok(NaiveDense::new(data.bits))
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -120,8 +219,26 @@ mod tests {
             }
         }
         assert_eq!(ones.len(), 248);
-        for i in 0..248 {
-            assert_eq!(nd.select(i), ones[i]);
+        for (i, &x) in ones.iter().enumerate() {
+            assert_eq!(nd.select(i), x);
         }
+        assert_eq!(nd.iter().collect::<Vec<u64>>(), ones);
+        assert_eq!(nd.iter().next_back(), Some(ones[247]));
+        assert_eq!(nd.iter_from(ones[100]).collect::<Vec<u64>>(), ones[100..]);
     }
-}
\ No newline at end of file
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn test_serde_1() {
+        let nd = NaiveDense::new(hex_bits(&[0x634b9340deec8469, 0x84eb72e372e6a42f]));
+        let json = serde_json::to_string(&nd).expect("serialize failed");
+        let other: NaiveDense = serde_json::from_str(&json).expect("deserialize failed");
+        assert_eq!(other.count(), nd.count());
+        assert_eq!(other.select(10), nd.select(10));
+
+        let bad = r#"{"bits":{"size":10,"words":[1024]}}"#;
+        assert!(serde_json::from_str::<NaiveDense>(bad).is_err());
+        let bad = r#"{"bits":{"size":100,"words":[0]}}"#;
+        assert!(serde_json::from_str::<NaiveDense>(bad).is_err());
+    }
+}
@@ -1,9 +1,12 @@
 //! A simple sparse set based on an indexed sorted vector.
 
 use crate::{
-    persist::{load_usize, load_vec_u64, save_vec, Persistent, load_vec_usize},
+    error::{check_elements, check_param, Result},
+    algebra::{domain_bits, FromSorted},
+    iter::IterableSet,
+    persist::{check_params, invalid_data, load_vec_u64, save_vec, to_usize, Persistent},
     rank::Rank,
-    select::Select,
+    select::{Select, Successor},
     set::ImpliedSet,
 };
 
@@ -25,6 +28,7 @@ static B: usize = 10;
 
 impl NaiveSparse {
     /// Create a naive sparse set for values with `b` bits.
+    #[allow(clippy::implicit_saturating_sub, clippy::needless_range_loop, clippy::slow_vector_initialization)]
     pub fn new(b: usize, elements: &[u64]) -> NaiveSparse {
         assert!(b > B);
         let m = 1 << B;
@@ -48,6 +52,14 @@ impl NaiveSparse {
             toc,
         }
     }
+
+    /// Create a naive sparse set, or return an error if `b` is not in `[11, 63]`,
+    /// or `elements` are not strictly increasing values less than `2**b`.
+    pub fn try_new(b: usize, elements: &[u64]) -> Result<NaiveSparse> {
+        check_param("b", b as u64, B as u64 + 1, 63)?;
+        check_elements(elements, b)?;
+        Ok(NaiveSparse::new(b, elements))
+    }
 }
 
 impl ImpliedSet for NaiveSparse {
@@ -90,25 +102,85 @@ impl Select for NaiveSparse {
     }
 }
 
+impl Successor for NaiveSparse {}
+
+impl FromSorted for NaiveSparse {
+    fn from_sorted(size: u64, elements: &[u64]) -> Self {
+        NaiveSparse::new(domain_bits(size).max(B + 1), elements)
+    }
+}
+
+impl IterableSet for NaiveSparse {
+    type Iter<'a> = std::iter::Copied<std::slice::Iter<'a, u64>>;
+
+    fn iter_from(&self, value: u64) -> Self::Iter<'_> {
+        self.elements[self.rank(value)..].iter().copied()
+    }
+}
+
 impl Persistent for NaiveSparse {
-    fn save<Sink>(&self, sink: &mut Sink) -> std::io::Result<()>
+    const TYPE_TAG: [u8; 4] = *b"NSPR";
+
+    fn params(&self) -> Vec<u64> {
+        vec![self.b as u64]
+    }
+
+    fn save_body<Sink>(&self, sink: &mut Sink) -> std::io::Result<()>
     where
         Sink: std::io::Write,
     {
-        sink.write_all(&self.b.to_ne_bytes())?;
+        let toc: Vec<u64> = self.toc.iter().map(|t| *t as u64).collect();
         save_vec(sink, &self.elements)?;
-        save_vec(sink, &self.toc)?;
+        save_vec(sink, &toc)?;
         Ok(())
     }
 
-    fn load<Source>(source: &mut Source) -> std::io::Result<Box<Self>>
+    fn load_body<Source>(params: &[u64], source: &mut Source) -> std::io::Result<Self>
     where
         Source: std::io::Read,
     {
-        let b: usize = load_usize(source)?;
+        check_params(&Self::TYPE_TAG, params, 1)?;
+        let b: usize = to_usize(params[0])?;
         let elements = load_vec_u64(source)?;
-        let toc = load_vec_usize(source)?;
-        Ok(Box::new(NaiveSparse { b, elements, toc }))
+        let toc = load_vec_u64(source)?
+            .into_iter()
+            .map(to_usize)
+            .collect::<std::io::Result<Vec<usize>>>()?;
+        let s = NaiveSparse::try_new(b, &elements).map_err(|e| invalid_data(e.to_string()))?;
+        if s.toc != toc {
+            return Err(invalid_data(String::from("inconsistent NaiveSparse table of contents")));
+        }
+        Ok(s)
+    }
+}
+
+/// Only the elements are serialized. The table of contents is rebuilt from
+/// them when the set is deserialized.
+#[cfg(feature = "serde")]
+impl serde::Serialize for NaiveSparse {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> std::result::Result<S::Ok, S::Error> {
+        use serde::ser::SerializeStruct;
+        let mut state = serializer.serialize_struct("NaiveSparse", 2)?;
+        state.serialize_field("b", &(self.b as u64))?;
+        state.serialize_field("elements", &self.elements)?;
+        state.end()
+    }
+}
+
+#[cfg(feature = "serde")]
+#[derive(serde::Deserialize)]
+#[serde(rename = "NaiveSparse")]
+struct NaiveSparseData {
+    b: u64,
+    elements: Vec<u64>,
+}
+
+#[cfg(feature = "serde")]
+impl<'de> serde::Deserialize<'de> for NaiveSparse {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> std::result::Result<Self, D::Error> {
+        use serde::de::Error;
+        let data = NaiveSparseData::deserialize(deserializer)?;
+        NaiveSparse::try_new(data.b as usize, &data.elements).map_err(D::Error::custom)
     }
 }
 
@@ -153,8 +225,7 @@ mod tests {
         println!("xs={:?}", xs);
 
         let r = NaiveSparse::new(b, &xs);
-        for i in 0..xs.len() {
-            let x = xs[i];
+        for (i, &x) in xs.iter().enumerate() {
             println!("xs[i]={}", x);
             let j = r.rank(x);
             assert_eq!(j, i);
@@ -162,4 +233,61 @@ mod tests {
             assert_eq!(r.access_and_rank(x), (i, true));
         }
     }
+
+    #[test]
+    fn test_load_and_save_1() {
+        let xs: Vec<u64> = vec![1, 5, 1000, 1 << 15, (1 << 20) - 1];
+        let r = NaiveSparse::new(20, &xs);
+        let mut bytes: Vec<u8> = Vec::new();
+        r.save(&mut bytes).expect("save failed");
+        let s = NaiveSparse::load(&mut bytes.as_slice()).expect("load failed");
+        assert_eq!(s.size(), 1 << 20);
+        for (i, &x) in xs.iter().enumerate() {
+            assert_eq!(s.rank(x), i);
+            assert_eq!(s.select(i), x);
+        }
+    }
+
+    #[test]
+    fn test_load_corrupt_1() {
+        use crate::persist::save_header;
+
+        let r = NaiveSparse::new(20, &[1, 5, 1000]);
+        let toc: Vec<u64> = r.toc.iter().map(|t| *t as u64).collect();
+        let save = |elements: &[u64], toc: &[u64]| {
+            let mut bytes: Vec<u8> = Vec::new();
+            save_header(&mut bytes, b"NSPR", &[20]).unwrap();
+            save_vec(&mut bytes, elements).unwrap();
+            save_vec(&mut bytes, toc).unwrap();
+            bytes
+        };
+        let bytes = save(&[1, 5, 1000], &toc);
+        assert!(NaiveSparse::load(&mut bytes.as_slice()).is_ok());
+        // Elements out of order, or too wide.
+        let bytes = save(&[1, 1000, 5], &toc);
+        assert!(NaiveSparse::load(&mut bytes.as_slice()).is_err());
+        let bytes = save(&[1, 5, 1 << 20], &toc);
+        assert!(NaiveSparse::load(&mut bytes.as_slice()).is_err());
+        // A table of contents that does not match the elements.
+        let mut bad = toc.clone();
+        bad[1] = 7;
+        let bytes = save(&[1, 5, 1000], &bad);
+        assert!(NaiveSparse::load(&mut bytes.as_slice()).is_err());
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn test_serde_1() {
+        let xs: Vec<u64> = vec![1, 5, 1000, 1 << 15, (1 << 20) - 1];
+        let r = NaiveSparse::new(20, &xs);
+        let json = serde_json::to_string(&r).expect("serialize failed");
+        let s: NaiveSparse = serde_json::from_str(&json).expect("deserialize failed");
+        assert_eq!(s.toc, r.toc);
+        assert_eq!(s.rank(1000), 2);
+
+        let bad = r#"{"b":20,"elements":[1,5,5]}"#;
+        assert!(serde_json::from_str::<NaiveSparse>(bad).is_err());
+        let bad = r#"{"b":20,"elements":[1,5,1048576]}"#;
+        assert!(serde_json::from_str::<NaiveSparse>(bad).is_err());
+    }
 }
@@ -1,9 +1,46 @@
-use crate::rank::Rank;
+//! Navigation operations over balanced parentheses sequences.
+//!
+//! A balanced parentheses sequence represents an ordinal tree with `n` nodes in
+//! `2n` bits: a depth first traversal writes an open parenthesis on entering a
+//! node and a close parenthesis on leaving it. Nodes are identified by the
+//! position of their open parenthesis.
+//!
+//! The [`RmmParens`] structure uses a range min-max tree, due to
+//!
+//! > Sadakane, K. and Navarro, G., 2010, January. Fully-functional succinct trees.
+//! > In Proceedings of the twenty-first annual ACM-SIAM symposium on Discrete
+//! > Algorithms (pp. 134-149).
+//!
+//! to answer the navigation operations in logarithmic time.
 
+use crate::{
+    bitvec::BitVec,
+    dense64::Dense64,
+    iter::{BitIter, IterableSet},
+    persist::{check_params, invalid_data, load_vec_u64, save_vec, Persistent},
+    rank::Rank,
+    select::{Select, Successor},
+    set::ImpliedSet,
+};
 
-pub trait BalencedPArens: Rank {
+/// Operations on a balanced parentheses sequence where elements of the
+/// implied set are open parentheses and the remaining positions are
+/// close parentheses.
+///
+/// The excess at position `i` is the number of open parentheses less the
+/// number of close parentheses in positions `[0, i]`. The default
+/// implementations of the searches scan the sequence one position at a
+/// time; [`RmmParens`] provides logarithmic time implementations.
+///
+/// This trait replaces [`BalencedPArens`], and its contract differs:
+/// `excess(i)` is now an `i64` counting positions `[0, i]`, where it was a
+/// `u64` counting positions `[0, i)`, which underflowed; `open` and `enclose`
+/// return the position found by `bwd_search` without adding 1; and
+/// `first_child` returns `None` for a leaf.
+pub trait BalancedParentheses: Rank {
 
     /// Scan the bits to check they are properly balanced.
+    #[allow(unused_variables)]
     fn valid(&self) -> bool {
         if 2 * self.count() as u64 != self.size() {
             return false;
@@ -17,36 +54,46 @@ pub trait BalencedPArens: Rank {
         true
     }
 
+    /// Return the position of the open parenthesis enclosing the one at `value`.
     fn parent(&self, value: u64) -> Option<u64> {
         self.enclose(value)
     }
 
+    /// Return the position of the first child of the node at `value`.
     fn first_child(&self, value: u64) -> Option<u64> {
-        Some(value + 1)
+        if value + 1 < self.size() && self.contains(value + 1) {
+            Some(value + 1)
+        } else {
+            None
+        }
     }
 
+    /// Return the position of the close parenthesis matching the open parenthesis at `i`.
     fn close(&self, i: u64) -> Option<u64> {
         self.fwd_search(i, -1)
     }
 
+    /// Return the position of the open parenthesis matching the close parenthesis at `i`.
     fn open(&self, i: u64) -> Option<u64> {
-        self.bwd_search(i, 0).map(|j| j + 1)
+        self.bwd_search(i, 0)
     }
 
+    /// Return the position of the open parenthesis of the nearest pair enclosing `i`.
     fn enclose(&self, i: u64) -> Option<u64> {
-        self.bwd_search(i, -2).map(|j| j + 1)
+        self.bwd_search(i, -2)
     }
 
-    fn excess(&self, value: u64) -> u64 {
-        2 * self.rank(value) as u64 - value
+    /// Return the number of open less close parentheses in positions `[0, i]`.
+    fn excess(&self, i: u64) -> i64 {
+        2 * self.rank(i + 1) as i64 - (i + 1) as i64
     }
 
+    /// Return the first position `j > i` such that `excess(j) == excess(i) + d`.
     fn fwd_search(&self, i: u64, d: i64) -> Option<u64> {
-        let excess_i = self.excess(i);
+        let target = self.excess(i) + d;
         let mut j = i + 1;
         while j < self.size() {
-            let excess_j = self.excess(j);
-            if  excess_j == (excess_i as i64 + d) as u64 {
+            if self.excess(j) == target {
                 return Some(j)
             }
             j += 1;
@@ -54,16 +101,671 @@ pub trait BalencedPArens: Rank {
         None
     }
 
+    /// Return the last position `j <= i` such that the excess of the positions
+    /// `[0, j)` before `j` is `excess(i) + d`.
     fn bwd_search(&self, i: u64, d: i64) -> Option<u64> {
-        let excess_i = self.excess(i);
+        let target = self.excess(i) + d;
         let mut j = i;
         while j > 0 {
-            j -= 1;
-            let excess_j = self.excess(j);
-            if  excess_j == (excess_i as i64 + d) as u64 {
+            if self.excess(j - 1) == target {
                 return Some(j)
             }
+            j -= 1;
+        }
+        if target == 0 {
+            Some(0)
+        } else {
+            None
+        }
+    }
+}
+
+/// The misspelled former name of [`BalancedParentheses`].
+///
+/// It is implemented for every [`BalancedParentheses`], so existing bounds
+/// keep working, but types must now implement [`BalancedParentheses`], whose
+/// `excess`, `open`, `enclose` and `first_child` have changed.
+#[deprecated(note = "use `BalancedParentheses`, whose `excess` now returns an `i64` over `[0, i]`")]
+pub trait BalencedPArens: BalancedParentheses {}
+
+#[allow(deprecated)]
+impl<T: BalancedParentheses + ?Sized> BalencedPArens for T {}
+
+/// The number of bits in each leaf of the range min-max tree.
+static BLOCK_BITS: usize = 1024;
+
+const fn byte_table() -> [[i8; 3]; 256] {
+    let mut t = [[0i8; 3]; 256];
+    let mut x = 0;
+    while x < 256 {
+        let mut e: i8 = 0;
+        let mut lo: i8 = 8;
+        let mut hi: i8 = -8;
+        let mut j = 0;
+        while j < 8 {
+            e += if (x >> j) & 1 == 1 { 1 } else { -1 };
+            if e < lo {
+                lo = e;
+            }
+            if e > hi {
+                hi = e;
+            }
+            j += 1;
+        }
+        t[x] = [e, lo, hi];
+        x += 1;
+    }
+    t
+}
+
+/// For each byte, the total excess, and the minimum and maximum excess after each bit.
+static BYTE_EXCESS: [[i8; 3]; 256] = byte_table();
+
+/// A balanced parentheses sequence with a range min-max tree, supporting tree
+/// navigation in logarithmic time.
+///
+/// Open parentheses are stored as one bits in a [`Dense64`], so the excess at
+/// any position is available in constant time from `rank`. The range min-max
+/// tree records the minimum and maximum excess over blocks of 1024 positions,
+/// and over the nodes of a complete binary tree above the blocks, so that
+/// searches for a given excess only scan the bits of at most two blocks.
+///
+/// #Examples
+///
+/// ```
+/// use crate::ransel::parens::{RmmParens, BalancedParentheses};
+///
+/// //                                     0123456789
+/// let p: RmmParens = "(()(()))()".chars().map(|c| c == '(').collect();
+/// assert_eq!(p.close(0), Some(7));
+/// assert_eq!(p.open(6), Some(3));
+/// assert_eq!(p.enclose(4), Some(3));
+/// assert_eq!(p.next_sibling(1), Some(3));
+/// assert_eq!(p.subtree_size(3), 2);
+/// assert_eq!(p.depth(4), 2);
+/// assert_eq!(p.lca(1, 4), 0);
+/// assert_eq!(p.level_ancestor(4, 2), Some(0));
+/// ```
+pub struct RmmParens {
+    bits: Dense64,
+    leaves: usize,
+    mins: Vec<i64>,
+    maxs: Vec<i64>,
+}
+
+impl RmmParens {
+    /// Create a balanced parentheses sequence from a bit vector, with `true`
+    /// for open parentheses.
+    pub fn new(bits: BitVec) -> RmmParens {
+        RmmParens::from_dense(Dense64::from_bitvec(bits))
+    }
+
+    fn from_dense(bits: Dense64) -> RmmParens {
+        let n = bits.size() as usize;
+        let blocks = n.div_ceil(BLOCK_BITS).max(1);
+        let leaves = blocks.next_power_of_two();
+        let mut mins: Vec<i64> = vec![i64::MAX; 2 * leaves];
+        let mut maxs: Vec<i64> = vec![i64::MIN; 2 * leaves];
+        let words = bits.as_words();
+        let mut p: i64 = 0;
+        for k in 0..n {
+            p += if (words[k >> 6] >> (k & 63)) & 1 == 1 { 1 } else { -1 };
+            let leaf = leaves + k / BLOCK_BITS;
+            mins[leaf] = mins[leaf].min(p);
+            maxs[leaf] = maxs[leaf].max(p);
+        }
+        for v in (1..leaves).rev() {
+            mins[v] = mins[2 * v].min(mins[2 * v + 1]);
+            maxs[v] = maxs[2 * v].max(maxs[2 * v + 1]);
+        }
+        RmmParens {
+            bits,
+            leaves,
+            mins,
+            maxs,
+        }
+    }
+
+    fn len(&self) -> usize {
+        self.bits.size() as usize
+    }
+
+    fn bit(&self, k: usize) -> bool {
+        (self.bits.as_words()[k >> 6] >> (k & 63)) & 1 == 1
+    }
+
+    fn byte(&self, k: usize) -> usize {
+        ((self.bits.as_words()[k >> 6] >> (k & 63)) & 0xff) as usize
+    }
+
+    /// The excess of the positions `[0, k)`.
+    fn prefix(&self, k: usize) -> i64 {
+        2 * self.bits.rank(k as u64) as i64 - k as i64
+    }
+
+    fn contains_target(&self, v: usize, target: i64) -> bool {
+        self.mins[v] <= target && target <= self.maxs[v]
+    }
+
+    /// Return the smallest `k` in `(lo, hi]` with `prefix(k) == target`, given `p == prefix(lo)`.
+    fn scan_fwd(&self, lo: usize, hi: usize, p: i64, target: i64) -> Option<usize> {
+        let mut k = lo;
+        let mut p = p;
+        while k < hi {
+            if k & 7 == 0 && k + 8 <= hi {
+                let [e, lo, hi] = BYTE_EXCESS[self.byte(k)];
+                if target < p + lo as i64 || target > p + hi as i64 {
+                    p += e as i64;
+                    k += 8;
+                    continue;
+                }
+            }
+            p += if self.bit(k) { 1 } else { -1 };
+            k += 1;
+            if p == target {
+                return Some(k);
+            }
+        }
+        None
+    }
+
+    /// Return the largest `k` in `[lo, hi)` with `prefix(k) == target`, given `p == prefix(hi)`.
+    fn scan_bwd(&self, lo: usize, hi: usize, p: i64, target: i64) -> Option<usize> {
+        let mut k = hi;
+        let mut p = p;
+        while k > lo {
+            if k & 7 == 0 && k >= lo + 8 {
+                let [e, lo, hi] = BYTE_EXCESS[self.byte(k - 8)];
+                let start = p - e as i64;
+                if start != target && (target < start + lo as i64 || target > start + hi as i64) {
+                    p = start;
+                    k -= 8;
+                    continue;
+                }
+            }
+            p -= if self.bit(k - 1) { 1 } else { -1 };
+            k -= 1;
+            if p == target {
+                return Some(k);
+            }
         }
         None
     }
-}
\ No newline at end of file
+
+    /// The range of `k` covered by leaf `b` is `(b * BLOCK_BITS, (b + 1) * BLOCK_BITS]`.
+    fn leaf_range(&self, b: usize) -> (usize, usize) {
+        let lo = b * BLOCK_BITS;
+        (lo, (lo + BLOCK_BITS).min(self.len()))
+    }
+
+    /// Return the smallest `k >= k0` with `prefix(k) == target`.
+    fn fwd(&self, k0: usize, target: i64) -> Option<usize> {
+        let n = self.len();
+        if k0 > n {
+            return None;
+        }
+        let p = self.prefix(k0);
+        if p == target {
+            return Some(k0);
+        }
+        if k0 == n {
+            return None;
+        }
+        let b0 = k0 / BLOCK_BITS;
+        let (_, end) = self.leaf_range(b0);
+        if let Some(k) = self.scan_fwd(k0, end, p, target) {
+            return Some(k);
+        }
+        let mut v = self.leaves + b0;
+        while v > 1 {
+            if v & 1 == 0 && self.contains_target(v + 1, target) {
+                v += 1;
+                while v < self.leaves {
+                    v = if self.contains_target(2 * v, target) { 2 * v } else { 2 * v + 1 };
+                }
+                let (lo, hi) = self.leaf_range(v - self.leaves);
+                return self.scan_fwd(lo, hi, self.prefix(lo), target);
+            }
+            v >>= 1;
+        }
+        None
+    }
+
+    /// Return the largest `k <= k0` with `prefix(k) == target`.
+    fn bwd(&self, k0: usize, target: i64) -> Option<usize> {
+        let p = self.prefix(k0);
+        if p == target {
+            return Some(k0);
+        }
+        if k0 == 0 {
+            return None;
+        }
+        let b0 = (k0 - 1) / BLOCK_BITS;
+        let (start, _) = self.leaf_range(b0);
+        if let Some(k) = self.scan_bwd(start, k0, p, target) {
+            return Some(k);
+        }
+        let mut v = self.leaves + b0;
+        while v > 1 {
+            if v & 1 == 1 && self.contains_target(v - 1, target) {
+                v -= 1;
+                while v < self.leaves {
+                    v = if self.contains_target(2 * v + 1, target) { 2 * v + 1 } else { 2 * v };
+                }
+                let (lo, hi) = self.leaf_range(v - self.leaves);
+                let p = self.prefix(hi);
+                if p == target {
+                    return Some(hi);
+                }
+                return self.scan_bwd(lo, hi, p, target);
+            }
+            v >>= 1;
+        }
+        if target == 0 {
+            Some(0)
+        } else {
+            None
+        }
+    }
+
+    /// Return the leftmost `k` in `(lo, hi]` minimising `prefix(k)`, scanning bits.
+    fn scan_min(&self, lo: usize, hi: usize, best: &mut (i64, usize)) {
+        let mut k = lo;
+        let mut p = self.prefix(lo);
+        while k < hi {
+            if k & 7 == 0 && k + 8 <= hi {
+                let [e, lo, _] = BYTE_EXCESS[self.byte(k)];
+                if p + lo as i64 >= best.0 {
+                    p += e as i64;
+                    k += 8;
+                    continue;
+                }
+            }
+            p += if self.bit(k) { 1 } else { -1 };
+            k += 1;
+            if p < best.0 {
+                *best = (p, k);
+            }
+        }
+    }
+
+    /// Return the position of the leftmost minimum of `excess` over positions `[i, j]`.
+    pub fn rmq(&self, i: u64, j: u64) -> u64 {
+        assert!(i <= j && j < self.len() as u64);
+        // In terms of prefix excess, find the leftmost minimum over k in [i + 1, j + 1].
+        let (lo, hi) = (i as usize, j as usize + 1);
+        let mut best: (i64, usize) = (i64::MAX, 0);
+        let b_lo = lo / BLOCK_BITS;
+        let b_hi = (hi - 1) / BLOCK_BITS;
+        if b_lo == b_hi {
+            self.scan_min(lo, hi, &mut best);
+            return best.1 as u64 - 1;
+        }
+        let (_, end) = self.leaf_range(b_lo);
+        self.scan_min(lo, end, &mut best);
+
+        // The full blocks strictly between b_lo and b_hi, in left to right order.
+        let mut left: Vec<usize> = Vec::new();
+        let mut right: Vec<usize> = Vec::new();
+        let mut l = self.leaves + b_lo + 1;
+        let mut r = self.leaves + b_hi;
+        while l < r {
+            if l & 1 == 1 {
+                left.push(l);
+                l += 1;
+            }
+            if r & 1 == 1 {
+                r -= 1;
+                right.push(r);
+            }
+            l >>= 1;
+            r >>= 1;
+        }
+        left.extend(right.iter().rev());
+        let mut best_node: Option<usize> = None;
+        for v in left {
+            if self.mins[v] < best.0 {
+                best = (self.mins[v], 0);
+                best_node = Some(v);
+            }
+        }
+        if let Some(mut v) = best_node {
+            while v < self.leaves {
+                v = if self.mins[2 * v] == best.0 { 2 * v } else { 2 * v + 1 };
+            }
+            let (lo, hi) = self.leaf_range(v - self.leaves);
+            best.0 += 1;
+            self.scan_min(lo, hi, &mut best);
+        }
+
+        let (start, _) = self.leaf_range(b_hi);
+        self.scan_min(start, hi, &mut best);
+        best.1 as u64 - 1
+    }
+
+    /// Return the depth of the node at `i`, with the root at depth 0.
+    pub fn depth(&self, i: u64) -> u64 {
+        (self.excess(i) - 1) as u64
+    }
+
+    /// Return the number of nodes in the subtree rooted at `i`, including `i`.
+    pub fn subtree_size(&self, i: u64) -> u64 {
+        let j = self.close(i).expect("unbalanced parentheses");
+        (j - i).div_ceil(2)
+    }
+
+    /// Return the next sibling of the node at `i`, if any.
+    pub fn next_sibling(&self, i: u64) -> Option<u64> {
+        let j = self.close(i)? + 1;
+        if j < self.size() && self.bit(j as usize) {
+            Some(j)
+        } else {
+            None
+        }
+    }
+
+    /// Return the lowest common ancestor of the nodes at `i` and `j`.
+    pub fn lca(&self, i: u64, j: u64) -> u64 {
+        let (i, j) = if i <= j { (i, j) } else { (j, i) };
+        if i == j || self.close(i).is_some_and(|c| j < c) {
+            return i;
+        }
+        let k = self.rmq(i, j);
+        self.enclose(k + 1).expect("unbalanced parentheses")
+    }
+
+    /// Return the ancestor `d` levels above the node at `i`, so that
+    /// `level_ancestor(i, 0) == Some(i)`.
+    pub fn level_ancestor(&self, i: u64, d: u64) -> Option<u64> {
+        self.bwd_search(i, -(d as i64) - 1)
+    }
+}
+
+impl FromIterator<bool> for RmmParens {
+    fn from_iter<I: IntoIterator<Item = bool>>(iter: I) -> Self {
+        RmmParens::from_dense(Dense64::from_iter(iter))
+    }
+}
+
+impl ImpliedSet for RmmParens {
+    fn count(&self) -> usize {
+        self.bits.count()
+    }
+
+    fn size(&self) -> u64 {
+        self.bits.size()
+    }
+}
+
+impl Rank for RmmParens {
+    fn rank(&self, value: u64) -> usize {
+        self.bits.rank(value)
+    }
+}
+
+impl Select for RmmParens {
+    fn select(&self, index: usize) -> u64 {
+        self.bits.select(index)
+    }
+}
+
+impl Successor for RmmParens {}
+
+impl IterableSet for RmmParens {
+    type Iter<'a> = BitIter<'a>;
+
+    fn iter_from(&self, value: u64) -> Self::Iter<'_> {
+        self.bits.iter_from(value)
+    }
+}
+
+impl BalancedParentheses for RmmParens {
+    fn fwd_search(&self, i: u64, d: i64) -> Option<u64> {
+        let target = self.excess(i) + d;
+        self.fwd(i as usize + 2, target).map(|k| k as u64 - 1)
+    }
+
+    fn bwd_search(&self, i: u64, d: i64) -> Option<u64> {
+        let target = self.excess(i) + d;
+        self.bwd(i as usize, target).map(|k| k as u64)
+    }
+}
+
+impl Persistent for RmmParens {
+    const TYPE_TAG: [u8; 4] = *b"BPRN";
+
+    fn params(&self) -> Vec<u64> {
+        vec![self.leaves as u64]
+    }
+
+    fn save_body<Sink>(&self, sink: &mut Sink) -> std::io::Result<()>
+    where
+        Sink: std::io::Write,
+    {
+        let mins: Vec<u64> = self.mins.iter().map(|x| *x as u64).collect();
+        let maxs: Vec<u64> = self.maxs.iter().map(|x| *x as u64).collect();
+        self.bits.save(sink)?;
+        save_vec(sink, &mins)?;
+        save_vec(sink, &maxs)?;
+        Ok(())
+    }
+
+    fn load_body<Source>(params: &[u64], source: &mut Source) -> std::io::Result<Self>
+    where
+        Source: std::io::Read,
+    {
+        check_params(&Self::TYPE_TAG, params, 1)?;
+        let bits: Dense64 = *(Dense64::load(source)?);
+        let mins: Vec<i64> = load_vec_u64(source)?.into_iter().map(|x| x as i64).collect();
+        let maxs: Vec<i64> = load_vec_u64(source)?.into_iter().map(|x| x as i64).collect();
+        let leaves = bits.size().div_ceil(BLOCK_BITS as u64).max(1).next_power_of_two() as usize;
+        if params[0] != leaves as u64 || mins.len() != 2 * leaves || maxs.len() != 2 * leaves {
+            return Err(invalid_data(String::from("inconsistent range min-max tree")));
+        }
+        Ok(RmmParens {
+            bits,
+            leaves,
+            mins,
+            maxs,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use random::Source;
+
+    /// A naive implementation of the trait, for checking against.
+    struct Naive {
+        ranks: Vec<usize>,
+    }
+
+    impl Naive {
+        fn new(bits: &[bool]) -> Naive {
+            let mut ranks = vec![0];
+            for b in bits.iter() {
+                ranks.push(ranks.last().unwrap() + *b as usize);
+            }
+            Naive { ranks }
+        }
+    }
+
+    impl ImpliedSet for Naive {
+        fn count(&self) -> usize {
+            *self.ranks.last().unwrap()
+        }
+
+        fn size(&self) -> u64 {
+            self.ranks.len() as u64 - 1
+        }
+    }
+
+    impl Rank for Naive {
+        fn rank(&self, value: u64) -> usize {
+            self.ranks[value as usize]
+        }
+    }
+
+    impl BalancedParentheses for Naive {}
+
+    /// Generate a random tree with `n` nodes as balanced parentheses.
+    fn make_tree(n: usize, seed: u64) -> Vec<bool> {
+        let mut rng = random::default(seed);
+        let mut res = Vec::new();
+        let mut open = 0;
+        let mut remaining = n;
+        while remaining > 0 || open > 0 {
+            let go_down = remaining > 0 && (open == 0 || rng.read_u64() % 100 < 52);
+            if go_down {
+                res.push(true);
+                open += 1;
+                remaining -= 1;
+            } else {
+                res.push(false);
+                open -= 1;
+            }
+        }
+        // Wrap everything in a single root.
+        let mut wrapped = vec![true];
+        wrapped.append(&mut res);
+        wrapped.push(false);
+        wrapped
+    }
+
+    #[test]
+    fn test_small_1() {
+        let s = "((()())(()))";
+        let bits: Vec<bool> = s.chars().map(|c| c == '(').collect();
+        let p: RmmParens = bits.iter().copied().collect();
+        let q = Naive::new(&bits);
+        assert!(p.valid());
+        assert!(q.valid());
+        assert_eq!(p.close(0), Some(11));
+        assert_eq!(q.close(0), Some(11));
+        assert_eq!(p.close(1), Some(6));
+        assert_eq!(p.open(6), Some(1));
+        assert_eq!(q.open(6), Some(1));
+        assert_eq!(p.open(11), Some(0));
+        assert_eq!(q.open(11), Some(0));
+        assert_eq!(p.enclose(4), Some(1));
+        assert_eq!(q.enclose(4), Some(1));
+        assert_eq!(p.enclose(0), None);
+        assert_eq!(q.enclose(0), None);
+        assert_eq!(p.first_child(1), Some(2));
+        assert_eq!(p.first_child(2), None);
+        assert_eq!(p.excess(11), 0);
+        assert_eq!(p.depth(8), 2);
+        assert_eq!(p.rmq(2, 9), 6);
+        assert_eq!(p.lca(2, 8), 0);
+        assert_eq!(p.lca(2, 4), 1);
+        assert_eq!(p.lca(1, 4), 1);
+        assert_eq!(p.iter().collect::<Vec<u64>>(), vec![0, 1, 2, 4, 7, 8]);
+    }
+
+    #[test]
+    #[allow(deprecated)]
+    fn test_deprecated_name_1() {
+        fn closes<P: BalencedPArens>(p: &P) -> Vec<u64> {
+            (0..p.size()).filter(|i| p.contains(*i)).filter_map(|i| p.close(i)).collect()
+        }
+        let bits: Vec<bool> = "(()())".chars().map(|c| c == '(').collect();
+        let p: RmmParens = bits.iter().copied().collect();
+        assert_eq!(closes(&p), vec![5, 2, 4]);
+        assert_eq!(closes(&Naive::new(&bits)), vec![5, 2, 4]);
+    }
+
+    #[test]
+    fn test_against_naive_1() {
+        for (n, seed) in [(100, 19), (1500, 23)] {
+            let bits = make_tree(n, seed);
+            let p: RmmParens = bits.iter().copied().collect();
+            let q = Naive::new(&bits);
+            assert!(p.valid());
+            for i in 0..bits.len() as u64 {
+                assert_eq!(p.excess(i), q.excess(i));
+                if bits[i as usize] {
+                    assert_eq!(p.close(i), q.close(i));
+                    assert_eq!(p.enclose(i), q.enclose(i));
+                    assert_eq!(p.first_child(i), q.first_child(i));
+                } else {
+                    assert_eq!(p.open(i), q.open(i));
+                }
+                for d in [-3, -1, 1, 2] {
+                    assert_eq!(p.fwd_search(i, d), q.fwd_search(i, d));
+                    assert_eq!(p.bwd_search(i, d), q.bwd_search(i, d));
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_tree_ops_1() {
+        let bits = make_tree(5000, 29);
+        let p: RmmParens = bits.iter().copied().collect();
+        let n = bits.len();
+
+        // Compute parents and depths by walking the sequence with a stack.
+        let mut parent: Vec<Option<usize>> = vec![None; n];
+        let mut depth: Vec<usize> = vec![0; n];
+        let mut stack: Vec<usize> = Vec::new();
+        for i in 0..n {
+            if bits[i] {
+                parent[i] = stack.last().copied();
+                depth[i] = stack.len();
+                stack.push(i);
+            } else {
+                stack.pop();
+            }
+        }
+
+        let mut rng = random::default(31);
+        let opens: Vec<usize> = (0..n).filter(|i| bits[*i]).collect();
+        for _t in 0..2000 {
+            let i = opens[(rng.read_u64() % opens.len() as u64) as usize];
+            let j = opens[(rng.read_u64() % opens.len() as u64) as usize];
+            assert_eq!(p.depth(i as u64), depth[i] as u64);
+            assert_eq!(p.parent(i as u64), parent[i].map(|x| x as u64));
+
+            let mut ancestors_i: Vec<usize> = vec![i];
+            while let Some(a) = parent[*ancestors_i.last().unwrap()] {
+                ancestors_i.push(a);
+            }
+            for (d, &a) in ancestors_i.iter().enumerate() {
+                assert_eq!(p.level_ancestor(i as u64, d as u64), Some(a as u64));
+            }
+            assert_eq!(p.level_ancestor(i as u64, ancestors_i.len() as u64), None);
+
+            let mut a = j;
+            while !ancestors_i.contains(&a) {
+                a = parent[a].unwrap();
+            }
+            assert_eq!(p.lca(i as u64, j as u64), a as u64);
+
+            let c = p.close(i as u64).unwrap() as usize;
+            let size = (i..=c).filter(|k| bits[*k]).count();
+            assert_eq!(p.subtree_size(i as u64), size as u64);
+            let sib = if c + 1 < n && bits[c + 1] { Some(c as u64 + 1) } else { None };
+            assert_eq!(p.next_sibling(i as u64), sib);
+
+            let (lo, hi) = if i <= j { (i, j) } else { (j, i) };
+            let expected = (lo..=hi).min_by_key(|k| p.excess(*k as u64)).unwrap();
+            assert_eq!(p.rmq(lo as u64, hi as u64), expected as u64);
+        }
+    }
+
+    #[test]
+    fn test_load_and_save_1() {
+        let bits = make_tree(2000, 37);
+        let p: RmmParens = bits.iter().copied().collect();
+        let mut bytes: Vec<u8> = Vec::new();
+        p.save(&mut bytes).expect("save failed");
+        let q = RmmParens::load(&mut bytes.as_slice()).expect("load failed");
+        for i in 0..bits.len() as u64 {
+            if bits[i as usize] {
+                assert_eq!(q.close(i), p.close(i));
+            }
+        }
+    }
+}
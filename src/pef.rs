@@ -0,0 +1,521 @@
+//! A partitioned Elias–Fano set based on the Ottaviano & Venturini 2014 paper:
+//!
+//! > Ottaviano, G. and Venturini, R., 2014, July. Partitioned Elias-Fano indexes.
+//! > In Proceedings of the 37th international ACM SIGIR conference on Research &
+//! > development in information retrieval (pp. 273-282).
+
+use crate::{
+    algebra::{domain_bits, FromSorted},
+    bitvec::BitVec,
+    error::{check_domain, check_elements, check_param, Result},
+    intvec::IntVec,
+    iter::IterableSet,
+    persist::{check_params, invalid_data, to_usize, Persistent},
+    rank::Rank,
+    select::{Select, Successor},
+    set::ImpliedSet,
+    sparse::Sparse,
+    words::{get_bits, select64},
+};
+
+/// The number of elements in each chunk.
+const CHUNK: usize = 128;
+
+/// A chunk containing every element between its endpoints, which needs no storage.
+const FULL: u64 = 0;
+
+/// A chunk stored as a bitmap over the range between its endpoints.
+const DENSE: u64 = 1;
+
+/// A chunk stored with Elias–Fano coding relative to its lower endpoint.
+const ELIAS_FANO: u64 = 2;
+
+/// The `PartitionedEf` data structure is a sparse set which adapts to clustered elements.
+///
+/// The elements are split into chunks of 128, and the last element of each chunk
+/// is stored in a [`Sparse`] set of endpoints. Each chunk is encoded relative to
+/// the preceding endpoint, using whichever is smallest of: nothing, if the chunk
+/// holds every value in its range; a bitmap of the range; or Elias–Fano coding
+/// with a low bit width chosen for the chunk. So dense clusters cost about one bit
+/// per value in their range, and the gaps between clusters cost very little,
+/// whereas a [`Sparse`] set chooses one low bit width for the whole set.
+///
+/// #Examples
+///
+/// ```
+/// use crate::ransel::pef::PartitionedEf;
+/// use crate::ransel::rank::Rank;
+/// use crate::ransel::select::Select;
+///
+/// let mut xs: Vec<u64> = Vec::new();
+/// for c in 0..20u64 {
+///     // Clusters of 300 nearby elements, a million apart.
+///     xs.extend((0..300).map(|i| c * 1_000_000 + 3 * i));
+/// }
+/// let s = PartitionedEf::new(1 << 25, &xs);
+/// for i in 0..xs.len() {
+///     assert_eq!(s.select(i), xs[i]);
+///     assert_eq!(s.rank(xs[i]), i);
+/// }
+/// ```
+pub struct PartitionedEf {
+    size_: u64,
+    n: usize,
+    endpoints: Sparse,
+    kinds: IntVec,
+    offsets: IntVec,
+    bits: BitVec,
+}
+
+/// Return the number of low bits for an Elias–Fano chunk of `n` elements in `[0, u)`.
+fn low_bits(u: u64, n: usize) -> usize {
+    if u <= n as u64 {
+        0
+    } else {
+        (u / n as u64).ilog2() as usize
+    }
+}
+
+/// Return the position (relative to `start`) of the `k`-th one bit (or zero bit,
+/// if `ones` is false) in the bits starting at `start`.
+fn select_bits(words: &[u64], start: usize, k: usize, ones: bool) -> usize {
+    let mut k = k;
+    let mut p = start;
+    loop {
+        let w = get_bits(words, p, 64);
+        let w = if ones { w } else { !w };
+        let c = w.count_ones() as usize;
+        if k < c {
+            return p - start + select64(w, k) as usize;
+        }
+        k -= c;
+        p += 64;
+    }
+}
+
+/// Return the number of one bits among the `len` bits starting at `start`.
+fn count_bits(words: &[u64], start: usize, len: usize) -> usize {
+    let mut c = 0;
+    let mut p = 0;
+    while p < len {
+        let width = (len - p).min(64);
+        c += get_bits(words, start + p, width).count_ones() as usize;
+        p += width;
+    }
+    c
+}
+
+impl PartitionedEf {
+    /// Create a new set over the domain `[0, size_)`, where `size_` is at most `2**63`.
+    ///
+    /// The elements must be strictly increasing and less than `size_`.
+    pub fn new(size_: u64, elements: &[u64]) -> PartitionedEf {
+        assert!(size_ <= 1 << 63);
+        let n = elements.len();
+        let chunk_count = n.div_ceil(CHUNK);
+        let mut ends: Vec<u64> = Vec::with_capacity(chunk_count);
+        let mut kinds = IntVec::with_capacity(2, chunk_count);
+        let mut starts: Vec<u64> = Vec::with_capacity(chunk_count);
+        let mut bits = BitVec::new();
+        let mut base: u64 = 0;
+        for chunk in elements.chunks(CHUNK) {
+            for i in 0..chunk.len() {
+                assert!(chunk[i] >= base && (i == 0 || chunk[i - 1] < chunk[i]));
+            }
+            let end = chunk[chunk.len() - 1];
+            assert!(end < size_);
+            let u = end - base + 1;
+            let m = chunk.len();
+            let l = low_bits(u, m);
+            let ef_cost = (m * l + m) as u64 + ((u - 1) >> l);
+            starts.push(bits.len() as u64);
+            if u == m as u64 {
+                kinds.push(FULL);
+            } else if u <= ef_cost {
+                kinds.push(DENSE);
+                let mut j = 0;
+                for v in 0..u {
+                    let present = chunk[j] - base == v;
+                    if present {
+                        j += 1;
+                    }
+                    bits.push(present);
+                }
+            } else {
+                kinds.push(ELIAS_FANO);
+                for x in chunk.iter() {
+                    bits.push_bits(x - base, l);
+                }
+                let mut h0 = 0;
+                for x in chunk.iter() {
+                    let h = (x - base) >> l;
+                    for _j in h0..h {
+                        bits.push(false);
+                    }
+                    bits.push(true);
+                    h0 = h;
+                }
+            }
+            ends.push(end);
+            base = end + 1;
+        }
+        let mut offsets = IntVec::with_capacity(domain_bits(bits.len() as u64 + 1), chunk_count);
+        for s in starts.iter() {
+            offsets.push(*s);
+        }
+        PartitionedEf {
+            size_,
+            n,
+            endpoints: Sparse::new(domain_bits(size_).max(1), &ends),
+            kinds,
+            offsets,
+            bits,
+        }
+    }
+
+    /// Create a new set, or return an error if `size_` is greater than `2**63`,
+    /// or `elements` are not strictly increasing values less than `size_`.
+    pub fn try_new(size_: u64, elements: &[u64]) -> Result<PartitionedEf> {
+        check_param("size", size_, 0, 1 << 63)?;
+        check_elements(elements, 64)?;
+        if let Some(x) = elements.last() {
+            check_domain(*x, size_)?;
+        }
+        Ok(PartitionedEf::new(size_, elements))
+    }
+
+    /// Return the number of elements in chunk `c`.
+    fn chunk_len(&self, c: usize) -> usize {
+        (self.n - c * CHUNK).min(CHUNK)
+    }
+
+    /// Return the lower endpoint of chunk `c`.
+    fn chunk_base(&self, c: usize) -> u64 {
+        if c == 0 {
+            0
+        } else {
+            self.endpoints.select(c - 1) + 1
+        }
+    }
+
+    /// Return the `j`-th element of chunk `c`, relative to the chunk's base.
+    fn chunk_select(&self, c: usize, base: u64, j: usize) -> u64 {
+        let words = self.bits.as_words();
+        let start = self.offsets.get(c) as usize;
+        match self.kinds.get(c) {
+            FULL => j as u64,
+            DENSE => select_bits(words, start, j, true) as u64,
+            _ => {
+                let m = self.chunk_len(c);
+                let u = self.endpoints.select(c) - base + 1;
+                let l = low_bits(u, m);
+                let lo = get_bits(words, start + j * l, l);
+                let p = select_bits(words, start + m * l, j, true);
+                (((p - j) as u64) << l) | lo
+            }
+        }
+    }
+
+    /// Return the number of elements of chunk `c` less than `v`, relative to the
+    /// chunk's base.
+    fn chunk_rank(&self, c: usize, base: u64, v: u64) -> usize {
+        let words = self.bits.as_words();
+        let start = self.offsets.get(c) as usize;
+        let m = self.chunk_len(c);
+        match self.kinds.get(c) {
+            FULL => (v as usize).min(m),
+            DENSE => count_bits(words, start, v as usize),
+            _ => {
+                let u = self.endpoints.select(c) - base + 1;
+                let l = low_bits(u, m);
+                let hi_start = start + m * l;
+                let h = (v >> l) as usize;
+                let lo = v & ((1u64 << l) - 1);
+                // The bucket for `h` starts after the h-th zero.
+                let mut p = if h == 0 {
+                    0
+                } else {
+                    select_bits(words, hi_start, h - 1, false) + 1
+                };
+                let mut r = p - h;
+                while r < m && get_bits(words, hi_start + p, 1) == 1 {
+                    if get_bits(words, start + r * l, l) >= lo {
+                        break;
+                    }
+                    r += 1;
+                    p += 1;
+                }
+                r
+            }
+        }
+    }
+
+    /// Decode the elements of chunk `c` into `buffer`.
+    fn decode_chunk(&self, c: usize, buffer: &mut Vec<u64>) {
+        buffer.clear();
+        let base = self.chunk_base(c);
+        let m = self.chunk_len(c);
+        let words = self.bits.as_words();
+        let start = self.offsets.get(c) as usize;
+        match self.kinds.get(c) {
+            FULL => buffer.extend((0..m as u64).map(|j| base + j)),
+            DENSE => {
+                let mut p = start;
+                while buffer.len() < m {
+                    let mut w = get_bits(words, p, 64);
+                    while w != 0 && buffer.len() < m {
+                        buffer.push(base + (p - start) as u64 + w.trailing_zeros() as u64);
+                        w &= w - 1;
+                    }
+                    p += 64;
+                }
+            }
+            _ => {
+                let u = self.endpoints.select(c) - base + 1;
+                let l = low_bits(u, m);
+                let mut p = start + m * l;
+                let mut j = 0;
+                while j < m {
+                    let mut w = get_bits(words, p, 64);
+                    while w != 0 && j < m {
+                        let h = (p - start - m * l) as u64 + w.trailing_zeros() as u64 - j as u64;
+                        buffer.push(base + ((h << l) | get_bits(words, start + j * l, l)));
+                        w &= w - 1;
+                        j += 1;
+                    }
+                    p += 64;
+                }
+            }
+        }
+    }
+
+    /// Return the number of bits used by the chunk encodings.
+    pub fn chunk_bits(&self) -> usize {
+        self.bits.len()
+    }
+}
+
+impl ImpliedSet for PartitionedEf {
+    fn count(&self) -> usize {
+        self.n
+    }
+
+    fn size(&self) -> u64 {
+        self.size_
+    }
+}
+
+impl Rank for PartitionedEf {
+    fn rank(&self, value: u64) -> usize {
+        if value >= self.size_ {
+            return self.n;
+        }
+        let c = self.endpoints.rank(value);
+        if c * CHUNK >= self.n {
+            return self.n;
+        }
+        let base = self.chunk_base(c);
+        c * CHUNK + self.chunk_rank(c, base, value - base)
+    }
+}
+
+impl Select for PartitionedEf {
+    fn select(&self, index: usize) -> u64 {
+        assert!(index < self.n);
+        let c = index / CHUNK;
+        let base = self.chunk_base(c);
+        base + self.chunk_select(c, base, index % CHUNK)
+    }
+}
+
+impl Successor for PartitionedEf {}
+
+/// An iterator over the elements of a [`PartitionedEf`] set, which decodes a
+/// chunk at a time.
+pub struct PartitionedEfIter<'a> {
+    pef: &'a PartitionedEf,
+    index: usize,
+    buffer: Vec<u64>,
+}
+
+impl<'a> Iterator for PartitionedEfIter<'a> {
+    type Item = u64;
+
+    fn next(&mut self) -> Option<u64> {
+        if self.index >= self.pef.n {
+            return None;
+        }
+        let j = self.index % CHUNK;
+        if j == 0 || self.buffer.is_empty() {
+            self.pef.decode_chunk(self.index / CHUNK, &mut self.buffer);
+        }
+        self.index += 1;
+        Some(self.buffer[j])
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        let remaining = self.pef.n - self.index.min(self.pef.n);
+        (remaining, Some(remaining))
+    }
+}
+
+impl<'a> ExactSizeIterator for PartitionedEfIter<'a> {}
+
+impl IterableSet for PartitionedEf {
+    type Iter<'a> = PartitionedEfIter<'a>;
+
+    fn iter_from(&self, value: u64) -> Self::Iter<'_> {
+        PartitionedEfIter {
+            pef: self,
+            index: self.rank(value),
+            buffer: Vec::with_capacity(CHUNK),
+        }
+    }
+}
+
+impl FromSorted for PartitionedEf {
+    fn from_sorted(size: u64, elements: &[u64]) -> Self {
+        PartitionedEf::new(size, elements)
+    }
+}
+
+impl Persistent for PartitionedEf {
+    const TYPE_TAG: [u8; 4] = *b"PEFS";
+
+    fn params(&self) -> Vec<u64> {
+        vec![self.size_, self.n as u64]
+    }
+
+    fn save_body<Sink>(&self, sink: &mut Sink) -> std::io::Result<()>
+    where
+        Sink: std::io::Write,
+    {
+        self.endpoints.save(sink)?;
+        self.kinds.save(sink)?;
+        self.offsets.save(sink)?;
+        self.bits.save(sink)?;
+        Ok(())
+    }
+
+    fn load_body<Source>(params: &[u64], source: &mut Source) -> std::io::Result<Self>
+    where
+        Source: std::io::Read,
+    {
+        check_params(&Self::TYPE_TAG, params, 2)?;
+        let size_: u64 = params[0];
+        let n: usize = to_usize(params[1])?;
+        let endpoints: Sparse = *(Sparse::load(source)?);
+        let kinds: IntVec = *(IntVec::load(source)?);
+        let offsets: IntVec = *(IntVec::load(source)?);
+        let bits: BitVec = *(BitVec::load(source)?);
+        let chunk_count = n.div_ceil(CHUNK);
+        if endpoints.count() != chunk_count
+            || kinds.len() != chunk_count
+            || offsets.len() != chunk_count
+            || kinds.width() != 2
+        {
+            return Err(invalid_data(String::from("inconsistent partitioned Elias-Fano index")));
+        }
+        Ok(PartitionedEf {
+            size_,
+            n,
+            endpoints,
+            kinds,
+            offsets,
+            bits,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use random::Source;
+
+    /// Make a clustered set: runs of dense, full and sparse elements.
+    fn make_set(seed: u64) -> Vec<u64> {
+        let mut rng = random::default(seed);
+        let mut xs: Vec<u64> = Vec::new();
+        let mut x: u64 = 0;
+        for i in 0..200 {
+            let len = 10 + rng.read_u64() % 500;
+            for _j in 0..len {
+                x += match i % 3 {
+                    0 => 1,
+                    1 => 1 + rng.read_u64() % 3,
+                    _ => 1 + rng.read_u64() % 50,
+                };
+                xs.push(x);
+            }
+            x += rng.read_u64() % 1_000_000;
+        }
+        xs
+    }
+
+    #[test]
+    fn test_rank_select_1() {
+        let xs = make_set(19);
+        let size = xs[xs.len() - 1] + 1000;
+        let s = PartitionedEf::new(size, &xs);
+        assert_eq!(s.count(), xs.len());
+        for (i, &x) in xs.iter().enumerate() {
+            assert_eq!(s.select(i), x);
+            assert_eq!(s.rank(x), i);
+            assert_eq!(s.rank(x + 1), i + 1);
+        }
+        assert_eq!(s.rank(size), xs.len());
+        for v in (0..size).step_by(997) {
+            assert_eq!(s.rank(v), xs.partition_point(|x| *x < v));
+        }
+    }
+
+    #[test]
+    fn test_iter_1() {
+        let xs = make_set(23);
+        let s = PartitionedEf::new(1 << 40, &xs);
+        assert_eq!(s.iter().collect::<Vec<u64>>(), xs);
+        for i in (0..xs.len()).step_by(101) {
+            assert_eq!(s.iter_from(xs[i]).collect::<Vec<u64>>(), xs[i..]);
+        }
+        let empty = PartitionedEf::new(100, &[]);
+        assert_eq!(empty.iter().next(), None);
+        assert_eq!(empty.rank(50), 0);
+    }
+
+    #[test]
+    fn test_try_new_1() {
+        use crate::error::Error;
+
+        let s = PartitionedEf::try_new(1 << 63, &[3, (1 << 63) - 1]).expect("try_new failed");
+        assert_eq!(s.select(1), (1 << 63) - 1);
+        assert!(matches!(
+            PartitionedEf::try_new((1 << 63) + 1, &[3]),
+            Err(Error::InvalidParameter { name: "size", .. })
+        ));
+        assert!(PartitionedEf::try_new(u64::MAX, &[]).is_err());
+        assert_eq!(PartitionedEf::try_new(100, &[3, 3]).err(), Some(Error::NotIncreasing { index: 1 }));
+        assert_eq!(PartitionedEf::try_new(100, &[3, 100]).err(), Some(Error::OutOfDomain { value: 100, size: 100 }));
+    }
+
+    #[test]
+    fn test_compression_1() {
+        let xs = make_set(29);
+        let size = xs[xs.len() - 1] + 1;
+        let s = PartitionedEf::new(size, &xs);
+        let t = Sparse::new(domain_bits(size), &xs);
+        let sparse_bits = t.count() * (2 + t.size().div_ceil(t.count() as u64).ilog2() as usize);
+        assert!(3 * s.chunk_bits() < 2 * sparse_bits);
+    }
+
+    #[test]
+    fn test_load_and_save_1() {
+        let xs = make_set(31);
+        let s = PartitionedEf::new(1 << 40, &xs);
+        let mut bytes: Vec<u8> = Vec::new();
+        s.save(&mut bytes).expect("save failed");
+        let t = PartitionedEf::load(&mut bytes.as_slice()).expect("load failed");
+        assert_eq!(t.count(), xs.len());
+        assert_eq!(t.iter().collect::<Vec<u64>>(), xs);
+    }
+}
@@ -1,172 +1,473 @@
-use std::io::{Read, Write};
+//! Saving and loading set representations.
+//!
+//! Structures implementing [`Persistent`] are written in a portable binary format
+//! so that a structure saved on one machine may be loaded on another. Every
+//! structure starts with a header:
+//!
+//! | field         | size      | value                                          |
+//! |---------------|-----------|------------------------------------------------|
+//! | magic         | 6 bytes   | `RANSEL`                                       |
+//! | version       | 2 bytes   | [`FORMAT_VERSION`]                             |
+//! | byte order    | 4 bytes   | `0x01020304`                                   |
+//! | type tag      | 4 bytes   | [`Persistent::TYPE_TAG`] of the structure      |
+//! | param count   | 8 bytes   | the number of parameters that follow           |
+//! | params        | 8 bytes each | the [`Persistent::params`] of the structure |
+//!
+//! followed by the body of the structure. All integers are written little endian,
+//! and lengths are always written as 64 bit integers, regardless of the width
+//! of `usize` on the machine writing the file. Vectors are written as a 64 bit
+//! length followed by the elements, padded with zeros to a multiple of 8 bytes,
+//! so every section of a file starts on an 8 byte boundary.
+//!
+//! Loading a structure checks the header, and a file with the wrong magic bytes,
+//! format version, byte order or type tag is rejected with an error of kind
+//! [`InvalidData`](std::io::ErrorKind::InvalidData).
+//!
+//! Because every section is aligned, a saved structure may also be used in place,
+//! without copying, from a byte buffer such as a memory mapped file. Types
+//! implementing [`View`] borrow their words directly from the buffer, which must
+//! start on an 8 byte boundary (as a memory mapped file always does). Zero-copy
+//! views are only supported on little endian machines.
+//!
+//! #Examples
+//!
+//! ```
+//! use crate::ransel::persist::Persistent;
+//! use crate::ransel::sparse::Sparse;
+//! use crate::ransel::sorted::Sorted;
+//! use crate::ransel::select::Select;
+//!
+//! let xs: Vec<u64> = vec![3, 17, 23, 1000, 4095];
+//! let s = Sparse::new(12, &xs);
+//! let mut bytes: Vec<u8> = Vec::new();
+//! s.save(&mut bytes).expect("save failed");
+//!
+//! let t = Sparse::load(&mut bytes.as_slice()).expect("load failed");
+//! assert_eq!(t.select(3), 1000);
+//!
+//! assert!(Sorted::load(&mut bytes.as_slice()).is_err());
+//! ```
+
+use std::io::{Error, ErrorKind, Read, Write};
 
 use num_traits::{FromBytes, ToBytes};
 
-pub trait Persistent {
-    fn save<Sink>(&self, sink: &mut Sink) -> std::io::Result<()>
+/// The magic bytes at the start of every saved structure.
+pub const MAGIC: [u8; 6] = *b"RANSEL";
+
+/// The version of the on-disk format written by this version of the library.
+pub const FORMAT_VERSION: u16 = 1;
+
+const BYTE_ORDER_MARKER: u32 = 0x01020304;
+
+/// Structures that may be saved to and loaded from a byte stream.
+///
+/// Implementations provide the parameters for the header, and the body of the
+/// structure. The provided `save` and `load` methods write and check the header.
+pub trait Persistent: Sized {
+    /// A four byte tag identifying the type of the structure in the header.
+    const TYPE_TAG: [u8; 4];
+
+    /// Return the scalar parameters of the structure, to be written in the header.
+    fn params(&self) -> Vec<u64>;
+
+    /// Write the body of the structure.
+    fn save_body<Sink>(&self, sink: &mut Sink) -> std::io::Result<()>
     where
         Sink: Write;
 
-    fn load<Source>(source: &mut Source) -> std::io::Result<Box<Self>>
+    /// Read the body of the structure, given the parameters from the header.
+    fn load_body<Source>(params: &[u64], source: &mut Source) -> std::io::Result<Self>
     where
         Source: Read;
+
+    /// Write the structure, including its header.
+    fn save<Sink>(&self, sink: &mut Sink) -> std::io::Result<()>
+    where
+        Sink: Write,
+    {
+        save_header(sink, &Self::TYPE_TAG, &self.params())?;
+        self.save_body(sink)
+    }
+
+    /// Read a structure, checking that the header matches this type.
+    fn load<Source>(source: &mut Source) -> std::io::Result<Box<Self>>
+    where
+        Source: Read,
+    {
+        let params = load_header(source, &Self::TYPE_TAG)?;
+        Ok(Box::new(Self::load_body(&params, source)?))
+    }
+}
+
+/// Structures that may be used in place from a byte buffer holding a saved structure.
+///
+/// The view shares the type tag and layout of the corresponding [`Persistent`] type.
+pub trait View<'a>: Sized {
+    /// A four byte tag identifying the type of the structure in the header.
+    const TYPE_TAG: [u8; 4];
+
+    /// Construct the view of the body of a structure, given the parameters from the header.
+    fn view_body(params: &[u64], source: &mut ByteReader<'a>) -> std::io::Result<Self>;
+
+    /// Construct a view of a structure from a reader, checking that the header matches this type.
+    fn view_from(source: &mut ByteReader<'a>) -> std::io::Result<Self> {
+        let params = load_header(source, &Self::TYPE_TAG)?;
+        Self::view_body(&params, source)
+    }
+
+    /// Construct a view of a structure saved at the start of `bytes`.
+    ///
+    /// `bytes` must start on an 8 byte boundary.
+    fn view(bytes: &'a [u8]) -> std::io::Result<Self> {
+        let mut source = ByteReader::new(bytes)?;
+        Self::view_from(&mut source)
+    }
+}
+
+/// A reader over a borrowed byte buffer, which hands out slices of the buffer
+/// rather than copying them.
+pub struct ByteReader<'a> {
+    bytes: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> ByteReader<'a> {
+    /// Create a reader over a buffer, which must start on an 8 byte boundary.
+    pub fn new(bytes: &'a [u8]) -> std::io::Result<ByteReader<'a>> {
+        if cfg!(target_endian = "big") {
+            return Err(Error::new(
+                ErrorKind::Unsupported,
+                "zero-copy views require a little endian machine",
+            ));
+        }
+        if !bytes.as_ptr().cast::<u64>().is_aligned() {
+            return Err(invalid_data(String::from("buffer is not aligned to 8 bytes")));
+        }
+        Ok(ByteReader { bytes, pos: 0 })
+    }
+
+    /// Return the number of bytes consumed so far.
+    pub fn position(&self) -> usize {
+        self.pos
+    }
+
+    fn take(&mut self, n: usize) -> std::io::Result<&'a [u8]> {
+        if self.bytes.len() - self.pos < n {
+            return Err(Error::new(ErrorKind::UnexpectedEof, "truncated buffer"));
+        }
+        let res = &self.bytes[self.pos..self.pos + n];
+        self.pos += n;
+        Ok(res)
+    }
+
+    fn slice<T>(&mut self) -> std::io::Result<&'a [T]> {
+        let n = load_len(self)?;
+        let bytes = n
+            .checked_mul(std::mem::size_of::<T>())
+            .ok_or_else(|| invalid_data(format!("vector length {} too large", n)))?;
+        let xs = self.take(bytes)?;
+        self.take(padding(bytes))?;
+        if !xs.as_ptr().cast::<T>().is_aligned() {
+            return Err(invalid_data(String::from("misaligned vector")));
+        }
+        // Safety: the slice is in bounds and suitably aligned, every bit pattern
+        // is a valid unsigned integer, and the host is little endian so the
+        // in-memory representation matches the file.
+        Ok(unsafe { std::slice::from_raw_parts(xs.as_ptr() as *const T, n) })
+    }
+
+    /// Borrow a saved vector of `u64` values.
+    pub fn slice_u64(&mut self) -> std::io::Result<&'a [u64]> {
+        self.slice::<u64>()
+    }
+
+    /// Borrow a saved vector of `u32` values.
+    pub fn slice_u32(&mut self) -> std::io::Result<&'a [u32]> {
+        self.slice::<u32>()
+    }
+}
+
+impl<'a> Read for ByteReader<'a> {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        let n = buf.len().min(self.bytes.len() - self.pos);
+        buf[..n].copy_from_slice(&self.bytes[self.pos..self.pos + n]);
+        self.pos += n;
+        Ok(n)
+    }
+}
+
+pub(crate) fn invalid_data(msg: String) -> Error {
+    Error::new(ErrorKind::InvalidData, msg)
+}
+
+fn tag_name(tag: &[u8; 4]) -> String {
+    String::from_utf8_lossy(tag).into_owned()
+}
+
+pub(crate) fn save_header<Sink>(sink: &mut Sink, tag: &[u8; 4], params: &[u64]) -> std::io::Result<()>
+where
+    Sink: Write,
+{
+    sink.write_all(&MAGIC)?;
+    sink.write_all(&FORMAT_VERSION.to_le_bytes())?;
+    sink.write_all(&BYTE_ORDER_MARKER.to_le_bytes())?;
+    sink.write_all(tag)?;
+    sink.write_all(&(params.len() as u64).to_le_bytes())?;
+    for p in params.iter() {
+        sink.write_all(&p.to_le_bytes())?;
+    }
+    Ok(())
+}
+
+pub(crate) fn load_header<Source>(source: &mut Source, tag: &[u8; 4]) -> std::io::Result<Vec<u64>>
+where
+    Source: Read,
+{
+    let mut magic: [u8; 6] = [0; 6];
+    source.read_exact(&mut magic)?;
+    if magic != MAGIC {
+        return Err(invalid_data(String::from("not a ransel structure (bad magic bytes)")));
+    }
+
+    let mut version_buf: [u8; 2] = [0; 2];
+    source.read_exact(&mut version_buf)?;
+    let version = u16::from_le_bytes(version_buf);
+    if version != FORMAT_VERSION {
+        return Err(invalid_data(format!(
+            "unsupported format version {} (expected {})",
+            version, FORMAT_VERSION
+        )));
+    }
+
+    let mut marker_buf: [u8; 4] = [0; 4];
+    source.read_exact(&mut marker_buf)?;
+    let marker = u32::from_le_bytes(marker_buf);
+    if marker != BYTE_ORDER_MARKER {
+        return Err(invalid_data(format!("unrecognised byte order marker {:#010x}", marker)));
+    }
+
+    let mut found: [u8; 4] = [0; 4];
+    source.read_exact(&mut found)?;
+    if &found != tag {
+        return Err(invalid_data(format!(
+            "type mismatch: expected {}, found {}",
+            tag_name(tag),
+            tag_name(&found)
+        )));
+    }
+
+    let n = load_len(source)?;
+    let mut params: Vec<u64> = Vec::with_capacity(n.min(64));
+    for _i in 0..n {
+        params.push(load_u64(source)?);
+    }
+    Ok(params)
 }
 
-pub fn save_vec<Sink, T>(sink: &mut Sink, xs: &[T]) -> std::io::Result<()>
+/// Check that the header contained the expected number of parameters.
+pub(crate) fn check_params(tag: &[u8; 4], params: &[u64], expected: usize) -> std::io::Result<()> {
+    if params.len() != expected {
+        return Err(invalid_data(format!(
+            "{} expects {} parameters, found {}",
+            tag_name(tag),
+            expected,
+            params.len()
+        )));
+    }
+    Ok(())
+}
+
+/// Convert a length or count read from a file to a `usize`.
+pub(crate) fn to_usize(x: u64) -> std::io::Result<usize> {
+    usize::try_from(x).map_err(|_| invalid_data(format!("value {} does not fit in usize", x)))
+}
+
+fn padding(bytes: usize) -> usize {
+    (8 - (bytes & 7)) & 7
+}
+
+pub(crate) fn save_vec<Sink, T>(sink: &mut Sink, xs: &[T]) -> std::io::Result<()>
 where
     Sink: Write,
     T: ToBytes,
 {
-    sink.write_all(&xs.len().to_ne_bytes())?;
-    for i in 0..xs.len() {
-        let bytes = ToBytes::to_ne_bytes(&xs[i]);
+    sink.write_all(&(xs.len() as u64).to_le_bytes())?;
+    for x in xs.iter() {
+        let bytes = ToBytes::to_le_bytes(x);
         sink.write_all(bytes.as_ref())?;
     }
+    let pad = padding(std::mem::size_of_val(xs));
+    sink.write_all(&[0u8; 8][..pad])?;
     Ok(())
 }
 
-pub fn load_u64<Source>(source: &mut Source) -> std::io::Result<u64>
+pub(crate) fn load_u64<Source>(source: &mut Source) -> std::io::Result<u64>
 where
     Source: Read,
 {
     const X_SIZE: usize = std::mem::size_of::<u64>();
     let mut x_buf: [u8; X_SIZE] = [0; X_SIZE];
     source.read_exact(&mut x_buf)?;
-    let x: u64 = FromBytes::from_ne_bytes(&x_buf);
+    let x: u64 = FromBytes::from_le_bytes(&x_buf);
     Ok(x)
 }
 
-pub fn load_usize<Source>(source: &mut Source) -> std::io::Result<usize>
+fn load_len<Source>(source: &mut Source) -> std::io::Result<usize>
 where
     Source: Read,
 {
-    const X_SIZE: usize = std::mem::size_of::<usize>();
-    let mut x_buf: [u8; X_SIZE] = [0; X_SIZE];
-    source.read_exact(&mut x_buf)?;
-    let x: usize = FromBytes::from_ne_bytes(&x_buf);
-    Ok(x)
+    to_usize(load_u64(source)?)
 }
 
-pub fn load_vec_u8<Source>(source: &mut Source) -> std::io::Result<Vec<u8>>
+fn skip_padding<Source>(source: &mut Source, bytes: usize) -> std::io::Result<()>
 where
     Source: Read,
 {
-    const N_SIZE: usize = std::mem::size_of::<usize>();
-    let mut n_buf: [u8; N_SIZE] = [0; N_SIZE];
-    source.read_exact(&mut n_buf)?;
-    let n: usize = FromBytes::from_ne_bytes(&n_buf);
+    let mut pad_buf: [u8; 8] = [0; 8];
+    source.read_exact(&mut pad_buf[..padding(bytes)])
+}
 
+#[allow(dead_code)]
+pub(crate) fn load_vec_u8<Source>(source: &mut Source) -> std::io::Result<Vec<u8>>
+where
+    Source: Read,
+{
+    let n = load_len(source)?;
     let mut res: Vec<u8> = Vec::new();
-    res.reserve(n);
-
-    const X_SIZE: usize = std::mem::size_of::<u8>();
-    let mut x_buf: [u8; X_SIZE] = [0; X_SIZE];
-
-    for _i in 0..n {
-        source.read_exact(&mut x_buf)?;
-        let x: u8 = FromBytes::from_ne_bytes(&x_buf);
-        res.push(x);
+    (&mut *source).take(n as u64).read_to_end(&mut res)?;
+    if res.len() != n {
+        return Err(Error::new(ErrorKind::UnexpectedEof, "truncated vector"));
     }
+    skip_padding(source, n)?;
     Ok(res)
 }
 
-pub fn load_vec_u16<Source>(source: &mut Source) -> std::io::Result<Vec<u16>>
+#[allow(dead_code)]
+pub(crate) fn load_vec_u16<Source>(source: &mut Source) -> std::io::Result<Vec<u16>>
 where
     Source: Read,
 {
-    const N_SIZE: usize = std::mem::size_of::<usize>();
-    let mut n_buf: [u8; N_SIZE] = [0; N_SIZE];
-    source.read_exact(&mut n_buf)?;
-    let n: usize = FromBytes::from_ne_bytes(&n_buf);
-
-    let mut res: Vec<u16> = Vec::new();
-    res.reserve(n);
+    let n = load_len(source)?;
+    let mut res: Vec<u16> = Vec::with_capacity(n.min(1 << 20));
 
     const X_SIZE: usize = std::mem::size_of::<u16>();
     let mut x_buf: [u8; X_SIZE] = [0; X_SIZE];
 
     for _i in 0..n {
         source.read_exact(&mut x_buf)?;
-        let x: u16 = FromBytes::from_ne_bytes(&x_buf);
+        let x: u16 = FromBytes::from_le_bytes(&x_buf);
         res.push(x);
     }
+    skip_padding(source, n * X_SIZE)?;
     Ok(res)
 }
 
-pub fn load_vec_u32<Source>(source: &mut Source) -> std::io::Result<Vec<u32>>
+pub(crate) fn load_vec_u32<Source>(source: &mut Source) -> std::io::Result<Vec<u32>>
 where
     Source: Read,
 {
-    const N_SIZE: usize = std::mem::size_of::<usize>();
-    let mut n_buf: [u8; N_SIZE] = [0; N_SIZE];
-    source.read_exact(&mut n_buf)?;
-    let n: usize = FromBytes::from_ne_bytes(&n_buf);
-
-    let mut res: Vec<u32> = Vec::new();
-    res.reserve(n);
+    let n = load_len(source)?;
+    let mut res: Vec<u32> = Vec::with_capacity(n.min(1 << 20));
 
     const X_SIZE: usize = std::mem::size_of::<u32>();
     let mut x_buf: [u8; X_SIZE] = [0; X_SIZE];
 
     for _i in 0..n {
         source.read_exact(&mut x_buf)?;
-        let x: u32 = FromBytes::from_ne_bytes(&x_buf);
+        let x: u32 = FromBytes::from_le_bytes(&x_buf);
         res.push(x);
     }
+    skip_padding(source, n * X_SIZE)?;
     Ok(res)
 }
 
-pub fn load_vec_u64<Source>(source: &mut Source) -> std::io::Result<Vec<u64>>
+pub(crate) fn load_vec_u64<Source>(source: &mut Source) -> std::io::Result<Vec<u64>>
 where
     Source: Read,
 {
-    const N_SIZE: usize = std::mem::size_of::<usize>();
-    let mut n_buf: [u8; N_SIZE] = [0; N_SIZE];
-    source.read_exact(&mut n_buf)?;
-    let n: usize = FromBytes::from_ne_bytes(&n_buf);
-
-    let mut res: Vec<u64> = Vec::new();
-    res.reserve(n);
+    let n = load_len(source)?;
+    let mut res: Vec<u64> = Vec::with_capacity(n.min(1 << 20));
 
     const X_SIZE: usize = std::mem::size_of::<u64>();
     let mut x_buf: [u8; X_SIZE] = [0; X_SIZE];
 
     for _i in 0..n {
         source.read_exact(&mut x_buf)?;
-        let x: u64 = FromBytes::from_ne_bytes(&x_buf);
+        let x: u64 = FromBytes::from_le_bytes(&x_buf);
         res.push(x);
     }
     Ok(res)
 }
 
-pub fn load_vec_usize<Source>(source: &mut Source) -> std::io::Result<Vec<usize>>
-where
-    Source: Read,
-{
-    const N_SIZE: usize = std::mem::size_of::<usize>();
-    let mut n_buf: [u8; N_SIZE] = [0; N_SIZE];
-    source.read_exact(&mut n_buf)?;
-    let n: usize = FromBytes::from_ne_bytes(&n_buf);
+#[cfg(test)]
+mod tests {
+    use std::io::{BufWriter, Cursor};
 
-    let mut res: Vec<usize> = Vec::new();
-    res.reserve(n);
+    use super::*;
 
-    const X_SIZE: usize = std::mem::size_of::<usize>();
-    let mut x_buf: [u8; X_SIZE] = [0; X_SIZE];
+    struct Pair {
+        a: u64,
+        xs: Vec<u32>,
+    }
 
-    for _i in 0..n {
-        source.read_exact(&mut x_buf)?;
-        let x: usize = FromBytes::from_ne_bytes(&x_buf);
-        res.push(x);
+    impl Persistent for Pair {
+        const TYPE_TAG: [u8; 4] = *b"PAIR";
+
+        fn params(&self) -> Vec<u64> {
+            vec![self.a]
+        }
+
+        fn save_body<Sink>(&self, sink: &mut Sink) -> std::io::Result<()>
+        where
+            Sink: Write,
+        {
+            save_vec(sink, &self.xs)
+        }
+
+        fn load_body<Source>(params: &[u64], source: &mut Source) -> std::io::Result<Self>
+        where
+            Source: Read,
+        {
+            check_params(&Self::TYPE_TAG, params, 1)?;
+            let xs = load_vec_u32(source)?;
+            Ok(Pair { a: params[0], xs })
+        }
     }
-    Ok(res)
-}
 
-#[cfg(test)]
-mod tests {
-    use std::io::{BufWriter, Cursor};
+    struct Other {}
 
-    use super::*;
+    impl Persistent for Other {
+        const TYPE_TAG: [u8; 4] = *b"OTHR";
+
+        fn params(&self) -> Vec<u64> {
+            Vec::new()
+        }
+
+        fn save_body<Sink>(&self, _sink: &mut Sink) -> std::io::Result<()>
+        where
+            Sink: Write,
+        {
+            Ok(())
+        }
+
+        fn load_body<Source>(_params: &[u64], _source: &mut Source) -> std::io::Result<Self>
+        where
+            Source: Read,
+        {
+            Ok(Other {})
+        }
+    }
+
+    fn pair_bytes() -> Vec<u8> {
+        let p = Pair {
+            a: 0x0102030405060708,
+            xs: vec![1, 2, 3],
+        };
+        let mut bytes = Vec::new();
+        p.save(&mut bytes).expect("save failed");
+        bytes
+    }
 
     #[test]
     fn test_vec_u8() {
@@ -177,6 +478,7 @@ mod tests {
         save_vec(&mut buf, &xs).expect("save_vec failed");
 
         let bytes = buf.into_inner().expect("failed to get bytes");
+        assert_eq!(bytes.len(), 16);
 
         let mut cursor = Cursor::new(bytes);
 
@@ -203,4 +505,48 @@ mod tests {
 
         assert_eq!(xs, ys);
     }
+
+    #[test]
+    fn test_header_layout() {
+        let bytes = pair_bytes();
+        assert_eq!(&bytes[0..6], b"RANSEL");
+        assert_eq!(&bytes[6..8], &[1, 0]);
+        assert_eq!(&bytes[8..12], &[4, 3, 2, 1]);
+        assert_eq!(&bytes[12..16], b"PAIR");
+        assert_eq!(&bytes[16..24], &[1, 0, 0, 0, 0, 0, 0, 0]);
+        assert_eq!(&bytes[24..32], &[8, 7, 6, 5, 4, 3, 2, 1]);
+        assert_eq!(&bytes[32..40], &[3, 0, 0, 0, 0, 0, 0, 0]);
+        assert_eq!(bytes.len(), 56);
+
+        let p = Pair::load(&mut bytes.as_slice()).expect("load failed");
+        assert_eq!(p.a, 0x0102030405060708);
+        assert_eq!(p.xs, vec![1, 2, 3]);
+    }
+
+    #[test]
+    fn test_header_rejected() {
+        let bytes = pair_bytes();
+
+        let err = Other::load(&mut bytes.as_slice()).err().expect("type mismatch accepted");
+        assert_eq!(err.kind(), ErrorKind::InvalidData);
+        assert!(err.to_string().contains("expected OTHR, found PAIR"));
+
+        let mut bad_magic = bytes.clone();
+        bad_magic[0] = b'X';
+        let err = Pair::load(&mut bad_magic.as_slice()).err().expect("bad magic accepted");
+        assert_eq!(err.kind(), ErrorKind::InvalidData);
+
+        let mut bad_version = bytes.clone();
+        bad_version[6] = 99;
+        let err = Pair::load(&mut bad_version.as_slice()).err().expect("bad version accepted");
+        assert!(err.to_string().contains("version 99"));
+
+        let mut bad_order = bytes.clone();
+        bad_order[8..12].copy_from_slice(&[1, 2, 3, 4]);
+        let err = Pair::load(&mut bad_order.as_slice()).err().expect("bad byte order accepted");
+        assert_eq!(err.kind(), ErrorKind::InvalidData);
+
+        let truncated = &bytes[..bytes.len() - 12];
+        assert!(Pair::load(&mut &truncated[..]).is_err());
+    }
 }
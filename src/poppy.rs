@@ -0,0 +1,432 @@
+//! A dense set with a compact rank/select directory based on the cs-poppy
+//! layout of Zhou, Andersen & Kaminsky:
+//!
+//! > Zhou, D., Andersen, D.G. and Kaminsky, M., 2013. Space-efficient, high-performance
+//! > rank and select structures on uncompressed bit sequences. In International
+//! > Symposium on Experimental Algorithms (pp. 151-163). Springer.
+
+use crate::{
+    algebra::FromSorted,
+    bitvec::{AsWords, BitVec, FromBitVec},
+    iter::{BitIter, IterableSet},
+    persist::{check_params, invalid_data, load_vec_u64, save_vec, Persistent},
+    rank::Rank,
+    select::{Select, Select0, Successor, Successor0},
+    set::ImpliedSet,
+    words::{clear_from, rank64, select64},
+};
+
+/// The number of words in a basic block.
+const BASIC_WORDS: usize = 8;
+
+/// The number of words in a lower block, each of which has one directory entry.
+const LOWER_WORDS: usize = 4 * BASIC_WORDS;
+
+/// The number of lower blocks in an upper block, which spans `2**32` bits.
+const UPPER_SHIFT: usize = 21;
+
+/// The number of ones (or zeros) between select samples.
+const SAMPLE_RATE: usize = 8192;
+
+/// The `Poppy` data structure is a dense set with a rank/select directory that
+/// costs about 3% of the size of the bit vector, and supports any number of elements.
+///
+/// The bit vector is divided into lower blocks of 2048 bits, each of which has a
+/// single 64 bit directory entry packing the number of elements before the block
+/// (relative to its upper block of `2**32` bits) with the number of elements in
+/// the first three of its four basic blocks of 512 bits. The upper blocks record
+/// absolute 64 bit counts. So a `rank` reads one directory entry, one upper block
+/// count, and at most one basic block of the bit vector. To accelerate `select`
+/// and `select_0`, the lower block containing every 8192nd one and zero is sampled.
+///
+/// Unlike [`Dense64`](crate::dense64::Dense64), whose 32 bit count per word costs
+/// 50% of the size of the bit vector and limits it to `2**32` elements, the
+/// directory is small and its counts do not overflow.
+///
+/// #Examples
+///
+/// ```
+/// use crate::ransel::poppy::Poppy;
+/// use crate::ransel::rank::Rank;
+/// use crate::ransel::select::{Select, Select0};
+///
+/// let words: Vec<u64> = vec![0x8000_0000_0000_0001, 0, 0b101];
+/// let s = Poppy::new(192, &words);
+/// assert_eq!(s.rank(64), 2);
+/// assert_eq!(s.select(3), 130);
+/// assert_eq!(s.select_0(0), 1);
+/// ```
+pub struct Poppy {
+    size_: u64,
+    count: usize,
+    words: Vec<u64>,
+    upper: Vec<u64>,
+    lower: Vec<u64>,
+    ones: Vec<u64>,
+    zeros: Vec<u64>,
+}
+
+impl Poppy {
+    /// Create a new dense set over the domain `[0, size_)` from the words of a bit vector.
+    ///
+    /// Bit `j` of `words[i]` represents the element `64 * i + j`. Bits at or
+    /// beyond `size_` must be zero.
+    pub fn new(size_: u64, words: &[u64]) -> Poppy {
+        Poppy::from_words(size_, Vec::from(words))
+    }
+
+    /// Create a new dense set from a bit vector, taking ownership of its words.
+    pub fn from_bitvec(bits: BitVec) -> Poppy {
+        let size_ = bits.len() as u64;
+        Poppy::from_words(size_, bits.into_words())
+    }
+
+    fn from_words(size_: u64, mut words: Vec<u64>) -> Poppy {
+        let word_count = size_.div_ceil(64) as usize;
+        std::debug_assert!(word_count <= words.len());
+        words.truncate(word_count);
+
+        let lower_count = word_count.div_ceil(LOWER_WORDS);
+        let mut upper: Vec<u64> = Vec::new();
+        let mut lower: Vec<u64> = Vec::with_capacity(lower_count);
+        let mut ones: Vec<u64> = Vec::new();
+        let mut zeros: Vec<u64> = Vec::new();
+        let mut count: u64 = 0;
+        for l in 0..lower_count {
+            if l % (1 << UPPER_SHIFT) == 0 {
+                upper.push(count);
+            }
+            let mut entry = count - upper[upper.len() - 1];
+            for j in 0..4 {
+                let begin = (l * LOWER_WORDS + j * BASIC_WORDS).min(word_count);
+                let end = (begin + BASIC_WORDS).min(word_count);
+                let c: u64 = words[begin..end].iter().map(|w| w.count_ones() as u64).sum();
+                if j < 3 {
+                    entry |= c << (32 + 10 * j);
+                }
+                count += c;
+            }
+            lower.push(entry);
+
+            let bits = (64 * (l + 1) * LOWER_WORDS) as u64;
+            while (ones.len() * SAMPLE_RATE) < count as usize {
+                ones.push(l as u64);
+            }
+            while (zeros.len() * SAMPLE_RATE) < (bits.min(size_) - count) as usize {
+                zeros.push(l as u64);
+            }
+        }
+
+        Poppy {
+            size_,
+            count: count as usize,
+            words,
+            upper,
+            lower,
+            ones,
+            zeros,
+        }
+    }
+
+    /// Return the underlying words of the bit vector.
+    pub fn as_words(&self) -> &[u64] {
+        &self.words
+    }
+
+    /// Return the number of elements before lower block `l`.
+    fn lower_rank(&self, l: usize) -> usize {
+        if l == self.lower.len() {
+            return self.count;
+        }
+        (self.upper[l >> UPPER_SHIFT] + (self.lower[l] & 0xffff_ffff)) as usize
+    }
+
+    /// Return the number of non-elements before lower block `l`.
+    fn lower_rank_0(&self, l: usize) -> usize {
+        let bits = (64 * l * LOWER_WORDS).min(self.size_ as usize);
+        bits - self.lower_rank(l)
+    }
+
+    /// Find the last lower block for which `f` is at most `index`, using the
+    /// samples to narrow the search.
+    fn find_lower<F>(&self, samples: &[u64], index: usize, f: F) -> usize
+    where
+        F: Fn(usize) -> usize,
+    {
+        let s = index / SAMPLE_RATE;
+        let mut first = samples[s] as usize;
+        let last = match samples.get(s + 1) {
+            Some(l) => *l as usize + 1,
+            None => self.lower.len(),
+        };
+        let mut count = last - first;
+        while count > 0 {
+            let step = count / 2;
+            let l = first + step;
+            if f(l) <= index {
+                first = l + 1;
+                count -= step + 1;
+            } else {
+                count = step;
+            }
+        }
+        first - 1
+    }
+}
+
+impl ImpliedSet for Poppy {
+    fn count(&self) -> usize {
+        self.count
+    }
+
+    fn size(&self) -> u64 {
+        self.size_
+    }
+}
+
+impl Rank for Poppy {
+    fn rank(&self, value: u64) -> usize {
+        if value >= self.size_ {
+            return self.count;
+        }
+        let w = (value / 64) as usize;
+        let l = w / LOWER_WORDS;
+        let entry = self.lower[l];
+        let mut r = self.lower_rank(l);
+        for j in 0..(w % LOWER_WORDS) / BASIC_WORDS {
+            r += ((entry >> (32 + 10 * j)) & 0x3ff) as usize;
+        }
+        for i in (w - w % BASIC_WORDS)..w {
+            r += self.words[i].count_ones() as usize;
+        }
+        r + rank64(self.words[w], value & 63) as usize
+    }
+}
+
+impl Select for Poppy {
+    fn select(&self, index: usize) -> u64 {
+        assert!(index < self.count);
+        let l = self.find_lower(&self.ones, index, |l| self.lower_rank(l));
+        let entry = self.lower[l];
+        let mut r = index - self.lower_rank(l);
+        let mut w = l * LOWER_WORDS;
+        for j in 0..3 {
+            let c = ((entry >> (32 + 10 * j)) & 0x3ff) as usize;
+            if r < c {
+                break;
+            }
+            r -= c;
+            w += BASIC_WORDS;
+        }
+        loop {
+            let c = self.words[w].count_ones() as usize;
+            if r < c {
+                return 64 * w as u64 + select64(self.words[w], r);
+            }
+            r -= c;
+            w += 1;
+        }
+    }
+}
+
+impl Select0 for Poppy {
+    fn select_0(&self, index: usize) -> u64 {
+        assert!(index < self.size_ as usize - self.count);
+        let l = self.find_lower(&self.zeros, index, |l| self.lower_rank_0(l));
+        let entry = self.lower[l];
+        let mut r = index - self.lower_rank_0(l);
+        let mut w = l * LOWER_WORDS;
+        for j in 0..3 {
+            let c = 64 * BASIC_WORDS - ((entry >> (32 + 10 * j)) & 0x3ff) as usize;
+            if r < c {
+                break;
+            }
+            r -= c;
+            w += BASIC_WORDS;
+        }
+        loop {
+            let c = self.words[w].count_zeros() as usize;
+            if r < c {
+                return 64 * w as u64 + select64(!self.words[w], r);
+            }
+            r -= c;
+            w += 1;
+        }
+    }
+}
+
+impl Successor for Poppy {}
+
+impl Successor0 for Poppy {}
+
+impl IterableSet for Poppy {
+    type Iter<'a> = BitIter<'a>;
+
+    fn iter_from(&self, value: u64) -> Self::Iter<'_> {
+        let count = self.count - self.rank(value);
+        BitIter::new(&self.words, self.size_, value, count, true)
+    }
+}
+
+impl FromBitVec for Poppy {
+    fn from_bitvec(bits: BitVec) -> Self {
+        Poppy::from_bitvec(bits)
+    }
+}
+
+impl AsWords for Poppy {
+    fn as_words(&self) -> &[u64] {
+        &self.words
+    }
+}
+
+impl FromSorted for Poppy {
+    fn from_sorted(size: u64, elements: &[u64]) -> Self {
+        let mut words: Vec<u64> = vec![0; size.div_ceil(64) as usize];
+        for x in elements.iter() {
+            words[(x / 64) as usize] |= 1u64 << (x & 63);
+        }
+        Poppy::from_words(size, words)
+    }
+}
+
+impl Persistent for Poppy {
+    const TYPE_TAG: [u8; 4] = *b"POPY";
+
+    fn params(&self) -> Vec<u64> {
+        vec![self.size_, self.count as u64]
+    }
+
+    fn save_body<Sink>(&self, sink: &mut Sink) -> std::io::Result<()>
+    where
+        Sink: std::io::Write,
+    {
+        save_vec(sink, &self.words)?;
+        save_vec(sink, &self.upper)?;
+        save_vec(sink, &self.lower)?;
+        save_vec(sink, &self.ones)?;
+        save_vec(sink, &self.zeros)?;
+        Ok(())
+    }
+
+    fn load_body<Source>(params: &[u64], source: &mut Source) -> std::io::Result<Self>
+    where
+        Source: std::io::Read,
+    {
+        check_params(&Self::TYPE_TAG, params, 2)?;
+        let size_: u64 = params[0];
+        let count: u64 = params[1];
+        let words: Vec<u64> = load_vec_u64(source)?;
+        let upper: Vec<u64> = load_vec_u64(source)?;
+        let lower: Vec<u64> = load_vec_u64(source)?;
+        let ones: Vec<u64> = load_vec_u64(source)?;
+        let zeros: Vec<u64> = load_vec_u64(source)?;
+        if words.len() as u64 != size_.div_ceil(64) || !clear_from(&words, size_) {
+            return Err(invalid_data(String::from("inconsistent Poppy words")));
+        }
+        // The directory is cheap to rebuild from the words, and must match.
+        let s = Poppy::from_words(size_, words);
+        if s.count as u64 != count || s.upper != upper || s.lower != lower || s.ones != ones || s.zeros != zeros {
+            return Err(invalid_data(String::from("inconsistent Poppy index")));
+        }
+        Ok(s)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use random::Source;
+
+    fn make_bits(m: usize, density: u64, seed: u64) -> BitVec {
+        let mut rng = random::default(seed);
+        let mut bits = BitVec::new();
+        for _i in 0..m {
+            bits.push(rng.read_u64() % 1000 < density);
+        }
+        bits
+    }
+
+    #[test]
+    fn test_rank_select_1() {
+        let cases = [(0, 500), (1, 1000), (3000, 500), (100_000, 5), (100_000, 995), (70_001, 300)];
+        for (m, density) in cases {
+            let bits = make_bits(m, density, 19);
+            let s = Poppy::from_bitvec(bits.clone());
+            let xs: Vec<u64> = (0..m as u64).filter(|x| bits.get(*x as usize)).collect();
+            let zs: Vec<u64> = (0..m as u64).filter(|x| !bits.get(*x as usize)).collect();
+            assert_eq!(s.size(), m as u64);
+            assert_eq!(s.count(), xs.len());
+            for (i, &x) in xs.iter().enumerate() {
+                assert_eq!(s.rank(x), i);
+                assert_eq!(s.select(i), x);
+            }
+            for (i, &z) in zs.iter().enumerate() {
+                assert_eq!(s.rank_0(z), i);
+                assert_eq!(s.select_0(i), z);
+            }
+            assert_eq!(s.rank(m as u64), xs.len());
+            assert_eq!(s.iter().collect::<Vec<u64>>(), xs);
+        }
+    }
+
+    #[test]
+    fn test_overhead_1() {
+        let m = 1 << 20;
+        let s = Poppy::from_bitvec(make_bits(m, 500, 23));
+        let index_bits = 64 * (s.upper.len() + s.lower.len() + s.ones.len() + s.zeros.len());
+        assert!(16 * index_bits <= m);
+    }
+
+    #[test]
+    fn test_load_and_save_1() {
+        let m = 50_000;
+        let bits = make_bits(m, 100, 29);
+        let s = Poppy::from_bitvec(bits);
+        let mut bytes: Vec<u8> = Vec::new();
+        s.save(&mut bytes).expect("save failed");
+        let t = Poppy::load(&mut bytes.as_slice()).expect("load failed");
+        assert_eq!(t.size(), s.size());
+        assert_eq!(t.count(), s.count());
+        for i in 0..s.count() {
+            assert_eq!(t.select(i), s.select(i));
+        }
+        bytes.truncate(bytes.len() - 8);
+        assert!(Poppy::load(&mut bytes.as_slice()).is_err());
+    }
+
+    #[test]
+    fn test_load_corrupt_1() {
+        use crate::persist::save_header;
+
+        let s = Poppy::from_bitvec(make_bits(40_010, 500, 31));
+        let save = |words: &[u64], lower: &[u64], ones: &[u64]| {
+            let mut bytes: Vec<u8> = Vec::new();
+            save_header(&mut bytes, b"POPY", &[40_010, s.count as u64]).unwrap();
+            save_vec(&mut bytes, words).unwrap();
+            save_vec(&mut bytes, &s.upper).unwrap();
+            save_vec(&mut bytes, lower).unwrap();
+            save_vec(&mut bytes, ones).unwrap();
+            save_vec(&mut bytes, &s.zeros).unwrap();
+            bytes
+        };
+        let bytes = save(&s.words, &s.lower, &s.ones);
+        assert!(Poppy::load(&mut bytes.as_slice()).is_ok());
+        // A lower block count that does not match the words.
+        let mut lower = s.lower.clone();
+        lower[3] += 1;
+        let bytes = save(&s.words, &lower, &s.ones);
+        assert!(Poppy::load(&mut bytes.as_slice()).is_err());
+        // A select sample past the last lower block.
+        let mut ones = s.ones.clone();
+        ones[1] = s.lower.len() as u64;
+        let bytes = save(&s.words, &s.lower, &ones);
+        assert!(Poppy::load(&mut bytes.as_slice()).is_err());
+        // A bit set beyond the size.
+        let mut words = s.words.clone();
+        *words.last_mut().unwrap() |= 1 << 63;
+        let bytes = save(&words, &s.lower, &s.ones);
+        assert!(Poppy::load(&mut bytes.as_slice()).is_err());
+    }
+}
@@ -0,0 +1,441 @@
+//! A run-length encoded set, for bit vectors made up of long runs of ones and zeros.
+
+use crate::{
+    algebra::{domain_bits, FromSorted},
+    bitvec::{BitVec, FromBitVec},
+    iter::IterableSet,
+    persist::{check_params, invalid_data, Persistent},
+    rank::Rank,
+    select::{Select, Select0, Successor},
+    set::ImpliedSet,
+    sparse::{Sparse, SparseBuilder},
+};
+
+/// The `Rle` data structure is a set stored as runs of consecutive elements.
+///
+/// The first element of each run is stored in one [`Sparse`] set, and the
+/// cumulative lengths of the runs in another, so the space depends on the
+/// number of runs rather than the number of elements or the size of the domain.
+/// `rank` and `select` each find the run with one `rank` on one sequence, and
+/// `select_0` with a binary search over the runs.
+///
+/// #Examples
+///
+/// ```
+/// use crate::ransel::rank::Rank;
+/// use crate::ransel::rle::Rle;
+/// use crate::ransel::set::ImpliedSet;
+/// use crate::ransel::select::{Select, Select0};
+///
+/// let s = Rle::from_runs(1 << 30, &[(10, 1000), (5000, 3), (1 << 20, 1 << 20)]);
+/// assert_eq!(s.count(), 1003 + (1 << 20));
+/// assert_eq!(s.rank(500), 490);
+/// assert!(s.contains(5002));
+/// assert!(!s.contains(5003));
+/// assert_eq!(s.select(1001), 5001);
+/// assert_eq!(s.select_0(10), 1010);
+/// assert_eq!(s.runs().collect::<Vec<(u64, u64)>>(), vec![(10, 1000), (5000, 3), (1 << 20, 1 << 20)]);
+/// ```
+pub struct Rle {
+    size_: u64,
+    starts: Sparse,
+    ends: Sparse,
+}
+
+impl Rle {
+    /// Create a new set over the domain `[0, size_)` from runs of consecutive
+    /// elements, given as the first element and the length of each run.
+    ///
+    /// The runs must be in increasing order, and must not overlap. Empty runs
+    /// are ignored, and adjacent runs are combined.
+    pub fn from_runs(size_: u64, runs: &[(u64, u64)]) -> Rle {
+        let mut merged: Vec<(u64, u64)> = Vec::with_capacity(runs.len());
+        for (start, len) in runs.iter().copied().filter(|r| r.1 > 0) {
+            assert!(start.checked_add(len).is_some_and(|end| end <= size_));
+            match merged.last_mut() {
+                Some(r) if r.0 + r.1 == start => r.1 += len,
+                Some(r) => {
+                    assert!(r.0 + r.1 < start);
+                    merged.push((start, len));
+                }
+                None => merged.push((start, len)),
+            }
+        }
+        let count: u64 = merged.iter().map(|r| r.1).sum();
+        let mut starts = SparseBuilder::new(domain_bits(size_).max(1), merged.len());
+        let mut ends = SparseBuilder::new(domain_bits(count + 1).max(1), merged.len());
+        let mut end = 0;
+        for (start, len) in merged.iter() {
+            starts.push(*start);
+            end += len;
+            ends.push(end);
+        }
+        Rle {
+            size_,
+            starts: starts.finish(),
+            ends: ends.finish(),
+        }
+    }
+
+    /// Return the number of runs.
+    pub fn run_count(&self) -> usize {
+        self.starts.count()
+    }
+
+    /// Return the number of elements in the runs before run `k`.
+    fn before(&self, k: usize) -> u64 {
+        if k == 0 {
+            0
+        } else {
+            self.ends.select(k - 1)
+        }
+    }
+
+    /// Return the first element and length of run `k`.
+    fn run(&self, k: usize) -> (u64, u64) {
+        (self.starts.select(k), self.ends.select(k) - self.before(k))
+    }
+
+    /// Return an iterator over the runs, as pairs of the first element and the
+    /// length of each run.
+    pub fn runs(&self) -> RunIter<'_> {
+        RunIter { rle: self, index: 0 }
+    }
+}
+
+impl ImpliedSet for Rle {
+    fn count(&self) -> usize {
+        match self.run_count() {
+            0 => 0,
+            k => self.ends.select(k - 1) as usize,
+        }
+    }
+
+    fn size(&self) -> u64 {
+        self.size_
+    }
+}
+
+impl Rank for Rle {
+    fn rank(&self, value: u64) -> usize {
+        if value >= self.size_ {
+            return self.count();
+        }
+        // The number of runs starting at or before `value`.
+        let k = self.starts.rank(value + 1);
+        if k == 0 {
+            return 0;
+        }
+        let (start, len) = self.run(k - 1);
+        (self.before(k - 1) + (value - start).min(len)) as usize
+    }
+
+    fn contains(&self, value: u64) -> bool {
+        if value >= self.size_ {
+            return false;
+        }
+        let k = self.starts.rank(value + 1);
+        if k == 0 {
+            return false;
+        }
+        let (start, len) = self.run(k - 1);
+        value < start + len
+    }
+}
+
+impl Select for Rle {
+    fn select(&self, index: usize) -> u64 {
+        assert!(index < self.count());
+        let k = self.ends.rank(index as u64 + 1);
+        self.starts.select(k) + (index as u64 - self.before(k))
+    }
+}
+
+impl Select0 for Rle {
+    fn select_0(&self, index: usize) -> u64 {
+        assert!((index as u64) < self.size_ - self.count() as u64);
+        // Find the number of runs with at most `index` zeros before them.
+        let mut first = 0;
+        let mut count = self.run_count();
+        while count > 0 {
+            let step = count / 2;
+            let k = first + step;
+            if self.starts.select(k) - self.before(k) <= index as u64 {
+                first = k + 1;
+                count -= step + 1;
+            } else {
+                count = step;
+            }
+        }
+        index as u64 + self.before(first)
+    }
+}
+
+impl Successor for Rle {}
+
+/// An iterator over the runs of an [`Rle`] set.
+pub struct RunIter<'a> {
+    rle: &'a Rle,
+    index: usize,
+}
+
+impl<'a> Iterator for RunIter<'a> {
+    type Item = (u64, u64);
+
+    fn next(&mut self) -> Option<(u64, u64)> {
+        if self.index >= self.rle.run_count() {
+            return None;
+        }
+        self.index += 1;
+        Some(self.rle.run(self.index - 1))
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        let remaining = self.rle.run_count() - self.index.min(self.rle.run_count());
+        (remaining, Some(remaining))
+    }
+}
+
+impl<'a> ExactSizeIterator for RunIter<'a> {}
+
+/// An iterator over the elements of an [`Rle`] set.
+pub struct RleIter<'a> {
+    runs: RunIter<'a>,
+    next: u64,
+    end: u64,
+}
+
+impl<'a> Iterator for RleIter<'a> {
+    type Item = u64;
+
+    fn next(&mut self) -> Option<u64> {
+        if self.next == self.end {
+            let (start, len) = self.runs.next()?;
+            self.next = start;
+            self.end = start + len;
+        }
+        self.next += 1;
+        Some(self.next - 1)
+    }
+}
+
+impl IterableSet for Rle {
+    type Iter<'a> = RleIter<'a>;
+
+    fn iter_from(&self, value: u64) -> Self::Iter<'_> {
+        let k = self.starts.rank(value.min(self.size_) + 1);
+        let mut iter = RleIter {
+            runs: RunIter { rle: self, index: k },
+            next: 0,
+            end: 0,
+        };
+        if k > 0 {
+            let (start, len) = self.run(k - 1);
+            if value < start + len {
+                iter.next = value;
+                iter.end = start + len;
+            }
+        }
+        iter
+    }
+}
+
+impl FromSorted for Rle {
+    fn from_sorted(size: u64, elements: &[u64]) -> Self {
+        let mut runs: Vec<(u64, u64)> = Vec::new();
+        for x in elements.iter() {
+            match runs.last_mut() {
+                Some(r) if r.0 + r.1 == *x => r.1 += 1,
+                _ => runs.push((*x, 1)),
+            }
+        }
+        Rle::from_runs(size, &runs)
+    }
+}
+
+impl FromBitVec for Rle {
+    fn from_bitvec(bits: BitVec) -> Self {
+        let mut runs: Vec<(u64, u64)> = Vec::new();
+        let mut i = 0;
+        while i < bits.len() {
+            // Skip whole words of zeros, or ones, at a time where possible.
+            let w = bits.get_bits(i, (bits.len() - i).min(64));
+            let in_run = matches!(runs.last(), Some(r) if r.0 + r.1 == i as u64);
+            if i & 63 == 0 && bits.len() - i >= 64 && ((w == 0 && !in_run) || (w == u64::MAX && in_run)) {
+                if in_run {
+                    runs.last_mut().unwrap().1 += 64;
+                }
+                i += 64;
+                continue;
+            }
+            if bits.get(i) {
+                if in_run {
+                    runs.last_mut().unwrap().1 += 1;
+                } else {
+                    runs.push((i as u64, 1));
+                }
+            }
+            i += 1;
+        }
+        Rle::from_runs(bits.len() as u64, &runs)
+    }
+}
+
+impl Persistent for Rle {
+    const TYPE_TAG: [u8; 4] = *b"RLEB";
+
+    fn params(&self) -> Vec<u64> {
+        vec![self.size_]
+    }
+
+    fn save_body<Sink>(&self, sink: &mut Sink) -> std::io::Result<()>
+    where
+        Sink: std::io::Write,
+    {
+        self.starts.save(sink)?;
+        self.ends.save(sink)?;
+        Ok(())
+    }
+
+    fn load_body<Source>(params: &[u64], source: &mut Source) -> std::io::Result<Self>
+    where
+        Source: std::io::Read,
+    {
+        check_params(&Self::TYPE_TAG, params, 1)?;
+        let size_: u64 = params[0];
+        let starts: Sparse = *(Sparse::load(source)?);
+        let ends: Sparse = *(Sparse::load(source)?);
+        let b = domain_bits(size_).max(1);
+        if starts.count() != ends.count() || b > 63 || starts.size() != 1 << b {
+            return Err(invalid_data(String::from("inconsistent run-length encoding")));
+        }
+        // The runs must be non-empty, within the domain, and separated by at
+        // least one non-element, as `from_runs` leaves them.
+        let mut prev_end: Option<u64> = None;
+        let mut prev_count = 0;
+        for (start, count) in starts.iter().zip(ends.iter()) {
+            let len = count - prev_count;
+            if len == 0 || start + len > size_ || prev_end.is_some_and(|e| start <= e) {
+                return Err(invalid_data(format!("run ({}, {}) empty, overlapping or beyond {}", start, len, size_)));
+            }
+            prev_end = Some(start + len);
+            prev_count = count;
+        }
+        Ok(Rle { size_, starts, ends })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use random::Source;
+
+    /// Make a bit vector of alternating runs of random lengths.
+    fn make_bits(seed: u64) -> BitVec {
+        let mut rng = random::default(seed);
+        let mut bits = BitVec::new();
+        let mut bit = rng.read_u64() & 1 == 1;
+        for _i in 0..500 {
+            let len = 1 + rng.read_u64() % 300;
+            for _j in 0..len {
+                bits.push(bit);
+            }
+            bit = !bit;
+        }
+        bits
+    }
+
+    #[test]
+    fn test_rank_select_1() {
+        let bits = make_bits(19);
+        let n = bits.len();
+        let expected: Vec<bool> = (0..n).map(|i| bits.get(i)).collect();
+        let s = Rle::from_bitvec(bits);
+        assert_eq!(s.size(), n as u64);
+        assert!(s.run_count() >= 249);
+        let (mut ones, mut zeros) = (0, 0);
+        for (i, &e) in expected.iter().enumerate() {
+            assert_eq!(s.rank(i as u64), ones);
+            assert_eq!(s.contains(i as u64), e);
+            if e {
+                assert_eq!(s.select(ones), i as u64);
+                ones += 1;
+            } else {
+                assert_eq!(s.select_0(zeros), i as u64);
+                zeros += 1;
+            }
+        }
+        assert_eq!(s.count(), ones);
+        assert_eq!(s.rank(n as u64), ones);
+    }
+
+    #[test]
+    fn test_iter_1() {
+        let xs: Vec<u64> = vec![0, 1, 2, 3, 10, 11, 12, 50, 63, 64, 65];
+        let s = Rle::from_sorted(100, &xs);
+        assert_eq!(
+            s.runs().collect::<Vec<(u64, u64)>>(),
+            vec![(0, 4), (10, 3), (50, 1), (63, 3)]
+        );
+        assert_eq!(s.iter().collect::<Vec<u64>>(), xs);
+        for v in 0..100 {
+            let expected: Vec<u64> = xs.iter().copied().filter(|x| *x >= v).collect();
+            assert_eq!(s.iter_from(v).collect::<Vec<u64>>(), expected);
+        }
+        let t = Rle::from_runs(100, &[(5, 5), (10, 0), (10, 3), (20, 0)]);
+        assert_eq!(t.runs().collect::<Vec<(u64, u64)>>(), vec![(5, 8)]);
+        let empty = Rle::from_sorted(100, &[]);
+        assert_eq!(empty.count(), 0);
+        assert_eq!(empty.rank(50), 0);
+        assert_eq!(empty.select_0(50), 50);
+        assert_eq!(empty.iter().next(), None);
+        assert!(!s.contains(u64::MAX));
+    }
+
+    #[test]
+    #[should_panic]
+    fn test_from_runs_overflow_1() {
+        Rle::from_runs(1 << 20, &[(5, 3), (10, u64::MAX - 5)]);
+    }
+
+    #[test]
+    fn test_load_and_save_1() {
+        let bits = make_bits(23);
+        let s = Rle::from_bitvec(bits);
+        let mut bytes: Vec<u8> = Vec::new();
+        s.save(&mut bytes).expect("save failed");
+        let t = Rle::load(&mut bytes.as_slice()).expect("load failed");
+        assert_eq!(t.size(), s.size());
+        assert_eq!(t.runs().collect::<Vec<(u64, u64)>>(), s.runs().collect::<Vec<(u64, u64)>>());
+    }
+
+    #[test]
+    fn test_load_corrupt_1() {
+        use crate::persist::save_header;
+
+        let save = |size: u64, b: usize, starts: &[u64], ends: &[u64]| {
+            let mut bytes: Vec<u8> = Vec::new();
+            save_header(&mut bytes, b"RLEB", &[size]).unwrap();
+            Sparse::new(b, starts).save(&mut bytes).unwrap();
+            Sparse::new(4, ends).save(&mut bytes).unwrap();
+            bytes
+        };
+        let bytes = save(100, 7, &[10, 50], &[5, 8]);
+        let s = Rle::load(&mut bytes.as_slice()).expect("load failed");
+        assert_eq!(s.runs().collect::<Vec<(u64, u64)>>(), vec![(10, 5), (50, 3)]);
+        // Starts over a different domain than the size.
+        let bytes = save(100, 12, &[10, 50], &[5, 8]);
+        assert!(Rle::load(&mut bytes.as_slice()).is_err());
+        // Runs that overlap, touch, or pass the end of the domain.
+        let bytes = save(100, 7, &[10, 12], &[5, 8]);
+        assert!(Rle::load(&mut bytes.as_slice()).is_err());
+        let bytes = save(100, 7, &[10, 15], &[5, 8]);
+        assert!(Rle::load(&mut bytes.as_slice()).is_err());
+        let bytes = save(100, 7, &[10, 98], &[5, 8]);
+        assert!(Rle::load(&mut bytes.as_slice()).is_err());
+        // An empty run.
+        let bytes = save(100, 7, &[10, 50], &[0, 8]);
+        assert!(Rle::load(&mut bytes.as_slice()).is_err());
+    }
+}
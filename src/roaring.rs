@@ -0,0 +1,660 @@
+//! A mutable set of integers in the style of Roaring bitmaps:
+//!
+//! > Chambi, S., Lemire, D., Kaser, O. and Godin, R., 2016. Better bitmap
+//! > performance with Roaring bitmaps. Software: practice and experience,
+//! > 46(5), pp. 709-719.
+
+use crate::{
+    algebra::FromSorted,
+    iter::IterableSet,
+    persist::{check_params, invalid_data, load_vec_u16, load_vec_u64, load_vec_u8, save_vec, to_usize, Persistent},
+    rank::Rank,
+    select::{Select, Successor},
+    set::ImpliedSet,
+    words::select64,
+};
+
+/// The number of low bits of an element stored in its container.
+const LOW_BITS: usize = 16;
+
+/// The number of words in a bitmap container.
+const BITMAP_WORDS: usize = 1 << (LOW_BITS - 6);
+
+/// The largest number of elements stored in an array container. Beyond this,
+/// a bitmap container is smaller.
+const ARRAY_MAX: usize = 4096;
+
+const ARRAY: u8 = 0;
+const BITMAP: u8 = 1;
+const RUNS: u8 = 2;
+
+/// The elements of a set sharing the same high bits, as their low 16 bits.
+enum Container {
+    /// The elements in increasing order.
+    Array(Vec<u16>),
+    /// A bitmap of 2^16 bits, and the number of bits set.
+    Bitmap(usize, Vec<u64>),
+    /// Runs of consecutive elements, as pairs of the first element and the
+    /// length of the run less 1.
+    Runs(Vec<(u16, u16)>),
+}
+
+impl Container {
+    /// Create a container from elements in increasing order, as an array or
+    /// bitmap, whichever is smaller.
+    fn new(elements: &[u16]) -> Container {
+        if elements.len() <= ARRAY_MAX {
+            Container::Array(Vec::from(elements))
+        } else {
+            let mut words = vec![0u64; BITMAP_WORDS];
+            for x in elements.iter() {
+                words[(x >> 6) as usize] |= 1u64 << (x & 63);
+            }
+            Container::Bitmap(elements.len(), words)
+        }
+    }
+
+    fn len(&self) -> usize {
+        match self {
+            Container::Array(xs) => xs.len(),
+            Container::Bitmap(count, _) => *count,
+            Container::Runs(runs) => runs.iter().map(|r| r.1 as usize + 1).sum(),
+        }
+    }
+
+    /// Return the number of elements less than `low`.
+    fn rank(&self, low: u16) -> usize {
+        match self {
+            Container::Array(xs) => xs.partition_point(|x| *x < low),
+            Container::Bitmap(_, words) => {
+                let w = (low >> 6) as usize;
+                let r: usize = words[..w].iter().map(|x| x.count_ones() as usize).sum();
+                r + (words[w] & ((1u64 << (low & 63)) - 1)).count_ones() as usize
+            }
+            Container::Runs(runs) => runs
+                .iter()
+                .take_while(|r| r.0 < low)
+                .map(|r| (r.1 as usize + 1).min((low - r.0) as usize))
+                .sum(),
+        }
+    }
+
+    /// Return the `index`-th smallest element.
+    fn select(&self, index: usize) -> u16 {
+        match self {
+            Container::Array(xs) => xs[index],
+            Container::Bitmap(_, words) => {
+                let mut index = index;
+                for (i, w) in words.iter().enumerate() {
+                    let c = w.count_ones() as usize;
+                    if index < c {
+                        return (64 * i) as u16 + select64(*w, index) as u16;
+                    }
+                    index -= c;
+                }
+                panic!("index out of range");
+            }
+            Container::Runs(runs) => {
+                let mut index = index;
+                for r in runs.iter() {
+                    if index <= r.1 as usize {
+                        return r.0 + index as u16;
+                    }
+                    index -= r.1 as usize + 1;
+                }
+                panic!("index out of range");
+            }
+        }
+    }
+
+    /// Append the elements, with `high` in the upper bits, to `buffer`.
+    fn decode(&self, high: u64, buffer: &mut Vec<u64>) {
+        let base = high << LOW_BITS;
+        match self {
+            Container::Array(xs) => buffer.extend(xs.iter().map(|x| base + *x as u64)),
+            Container::Bitmap(_, words) => {
+                for (i, w) in words.iter().enumerate() {
+                    let mut w = *w;
+                    while w != 0 {
+                        buffer.push(base + (64 * i) as u64 + w.trailing_zeros() as u64);
+                        w &= w - 1;
+                    }
+                }
+            }
+            Container::Runs(runs) => {
+                for r in runs.iter() {
+                    let first = base + r.0 as u64;
+                    buffer.extend(first..=first + r.1 as u64);
+                }
+            }
+        }
+    }
+
+    /// Convert a run container to an array or bitmap, so it may be updated.
+    fn unrun(&mut self) {
+        if let Container::Runs(_) = self {
+            let mut buffer: Vec<u64> = Vec::with_capacity(self.len());
+            self.decode(0, &mut buffer);
+            let elements: Vec<u16> = buffer.iter().map(|x| *x as u16).collect();
+            *self = Container::new(&elements);
+        }
+    }
+
+    /// Add `low`, returning true if it was not already present.
+    fn insert(&mut self, low: u16) -> bool {
+        self.unrun();
+        match self {
+            Container::Array(xs) => match xs.binary_search(&low) {
+                Ok(_) => false,
+                Err(i) => {
+                    xs.insert(i, low);
+                    if xs.len() > ARRAY_MAX {
+                        *self = Container::new(&std::mem::take(xs));
+                    }
+                    true
+                }
+            },
+            Container::Bitmap(count, words) => {
+                let (w, m) = ((low >> 6) as usize, 1u64 << (low & 63));
+                if words[w] & m != 0 {
+                    return false;
+                }
+                words[w] |= m;
+                *count += 1;
+                true
+            }
+            Container::Runs(_) => unreachable!(),
+        }
+    }
+
+    /// Remove `low`, returning true if it was present.
+    fn remove(&mut self, low: u16) -> bool {
+        self.unrun();
+        match self {
+            Container::Array(xs) => match xs.binary_search(&low) {
+                Ok(i) => {
+                    xs.remove(i);
+                    true
+                }
+                Err(_) => false,
+            },
+            Container::Bitmap(count, words) => {
+                let (w, m) = ((low >> 6) as usize, 1u64 << (low & 63));
+                if words[w] & m == 0 {
+                    return false;
+                }
+                words[w] ^= m;
+                *count -= 1;
+                if *count <= ARRAY_MAX {
+                    let mut buffer: Vec<u64> = Vec::with_capacity(*count);
+                    self.decode(0, &mut buffer);
+                    *self = Container::Array(buffer.iter().map(|x| *x as u16).collect());
+                }
+                true
+            }
+            Container::Runs(_) => unreachable!(),
+        }
+    }
+
+    /// Convert the container to runs, if that is the smallest representation,
+    /// or from runs, if it is not.
+    fn optimize(&mut self) {
+        let mut buffer: Vec<u64> = Vec::with_capacity(self.len());
+        self.decode(0, &mut buffer);
+        let mut runs: Vec<(u16, u16)> = Vec::new();
+        for x in buffer.iter().map(|x| *x as u16) {
+            match runs.last_mut() {
+                Some(r) if r.0 as usize + r.1 as usize + 1 == x as usize => r.1 += 1,
+                _ => runs.push((x, 0)),
+            }
+        }
+        // Sizes in 16-bit units.
+        let run_cost = 2 * runs.len();
+        let other_cost = buffer.len().min(4 * BITMAP_WORDS);
+        if run_cost < other_cost {
+            *self = Container::Runs(runs);
+        } else if let Container::Runs(_) = self {
+            self.unrun();
+        }
+    }
+}
+
+/// The `Roaring` data structure is a mutable set, which partitions the domain
+/// by the high bits of the elements.
+///
+/// The elements sharing the same bits above the low 16 are kept in a container,
+/// stored as a sorted array of the low bits when there are few of them, or as a
+/// bitmap of 2^16 bits when there are many. Calling [`optimize`](Roaring::optimize)
+/// additionally converts containers to runs of consecutive elements where that
+/// is smaller. The number of elements before each container is kept, so `rank`
+/// and `select` locate the container by binary search. Unlike the static sets
+/// in this crate, elements may be inserted and removed.
+///
+/// #Examples
+///
+/// ```
+/// use crate::ransel::iter::IterableSet;
+/// use crate::ransel::rank::Rank;
+/// use crate::ransel::roaring::Roaring;
+/// use crate::ransel::select::Select;
+///
+/// let mut s = Roaring::new(1 << 32);
+/// for x in [70000, 3, 1 << 31, 12, 70001] {
+///     s.insert(x);
+/// }
+/// assert!(s.remove(12));
+/// assert_eq!(s.iter().collect::<Vec<u64>>(), vec![3, 70000, 70001, 1 << 31]);
+/// assert_eq!(s.rank(70001), 2);
+/// assert_eq!(s.select(3), 1 << 31);
+/// ```
+pub struct Roaring {
+    size_: u64,
+    keys: Vec<u64>,
+    containers: Vec<Container>,
+    counts: Vec<usize>,
+}
+
+impl Roaring {
+    /// Create a new empty set over the domain `[0, size_)`.
+    pub fn new(size_: u64) -> Roaring {
+        Roaring {
+            size_,
+            keys: Vec::new(),
+            containers: Vec::new(),
+            counts: Vec::new(),
+        }
+    }
+
+    /// Recompute the number of elements before each container from container `i` on.
+    fn recount(&mut self, i: usize) {
+        let mut c = if i == 0 {
+            0
+        } else {
+            self.counts[i - 1] + self.containers[i - 1].len()
+        };
+        self.counts.truncate(i);
+        for container in self.containers[i..].iter() {
+            self.counts.push(c);
+            c += container.len();
+        }
+    }
+
+    /// Add `value` to the set, returning true if it was not already present.
+    ///
+    /// `value` must be less than `self.size()`. The number of elements before
+    /// each later container is updated, so this takes time linear in the
+    /// number of containers.
+    pub fn insert(&mut self, value: u64) -> bool {
+        assert!(value < self.size_);
+        let key = value >> LOW_BITS;
+        let low = value as u16;
+        let i = self.keys.partition_point(|k| *k < key);
+        if i == self.keys.len() || self.keys[i] != key {
+            self.keys.insert(i, key);
+            self.containers.insert(i, Container::new(&[low]));
+            self.counts.insert(i, 0);
+        } else if !self.containers[i].insert(low) {
+            return false;
+        }
+        self.recount(i);
+        true
+    }
+
+    /// Remove `value` from the set, returning true if it was present.
+    ///
+    /// Like [`insert`](Roaring::insert), this takes time linear in the number
+    /// of containers.
+    pub fn remove(&mut self, value: u64) -> bool {
+        let key = value >> LOW_BITS;
+        let i = self.keys.partition_point(|k| *k < key);
+        if i == self.keys.len() || self.keys[i] != key || !self.containers[i].remove(value as u16) {
+            return false;
+        }
+        if self.containers[i].len() == 0 {
+            self.keys.remove(i);
+            self.containers.remove(i);
+            self.counts.remove(i);
+        }
+        self.recount(i);
+        true
+    }
+
+    /// Convert each container to runs where that is smaller than an array or bitmap.
+    ///
+    /// Containers of runs are converted back to an array or bitmap when next modified.
+    pub fn optimize(&mut self) {
+        for container in self.containers.iter_mut() {
+            container.optimize();
+        }
+    }
+
+    /// Return the container index holding the element of rank `index`.
+    fn container_of(&self, index: usize) -> usize {
+        self.counts.partition_point(|c| *c <= index) - 1
+    }
+}
+
+impl ImpliedSet for Roaring {
+    fn count(&self) -> usize {
+        match self.containers.last() {
+            None => 0,
+            Some(container) => self.counts[self.counts.len() - 1] + container.len(),
+        }
+    }
+
+    fn size(&self) -> u64 {
+        self.size_
+    }
+}
+
+impl Rank for Roaring {
+    fn rank(&self, value: u64) -> usize {
+        let key = value >> LOW_BITS;
+        let i = self.keys.partition_point(|k| *k < key);
+        if i == self.keys.len() {
+            self.count()
+        } else if self.keys[i] > key {
+            self.counts[i]
+        } else {
+            self.counts[i] + self.containers[i].rank(value as u16)
+        }
+    }
+}
+
+impl Select for Roaring {
+    fn select(&self, index: usize) -> u64 {
+        assert!(index < self.count());
+        let i = self.container_of(index);
+        (self.keys[i] << LOW_BITS) | self.containers[i].select(index - self.counts[i]) as u64
+    }
+}
+
+impl Successor for Roaring {}
+
+/// An iterator over the elements of a [`Roaring`] set, which decodes a
+/// container at a time.
+pub struct RoaringIter<'a> {
+    set: &'a Roaring,
+    container: usize,
+    buffer: Vec<u64>,
+    pos: usize,
+}
+
+impl<'a> Iterator for RoaringIter<'a> {
+    type Item = u64;
+
+    fn next(&mut self) -> Option<u64> {
+        while self.pos == self.buffer.len() {
+            if self.container >= self.set.containers.len() {
+                return None;
+            }
+            self.buffer.clear();
+            self.set.containers[self.container].decode(self.set.keys[self.container], &mut self.buffer);
+            self.container += 1;
+            self.pos = 0;
+        }
+        self.pos += 1;
+        Some(self.buffer[self.pos - 1])
+    }
+}
+
+impl IterableSet for Roaring {
+    type Iter<'a> = RoaringIter<'a>;
+
+    fn iter_from(&self, value: u64) -> Self::Iter<'_> {
+        let r = self.rank(value);
+        let mut iter = RoaringIter {
+            set: self,
+            container: self.containers.len(),
+            buffer: Vec::new(),
+            pos: 0,
+        };
+        if r < self.count() {
+            let i = self.container_of(r);
+            self.containers[i].decode(self.keys[i], &mut iter.buffer);
+            iter.container = i + 1;
+            iter.pos = r - self.counts[i];
+        }
+        iter
+    }
+}
+
+impl FromSorted for Roaring {
+    fn from_sorted(size: u64, elements: &[u64]) -> Self {
+        let mut s = Roaring::new(size);
+        let mut lows: Vec<u16> = Vec::new();
+        let mut i = 0;
+        while i < elements.len() {
+            assert!(elements[i] < size);
+            let key = elements[i] >> LOW_BITS;
+            lows.clear();
+            while i < elements.len() && elements[i] >> LOW_BITS == key {
+                assert!(lows.is_empty() || *lows.last().unwrap() < elements[i] as u16);
+                lows.push(elements[i] as u16);
+                i += 1;
+            }
+            s.keys.push(key);
+            s.containers.push(Container::new(&lows));
+        }
+        s.recount(0);
+        s
+    }
+}
+
+impl Persistent for Roaring {
+    const TYPE_TAG: [u8; 4] = *b"ROAR";
+
+    fn params(&self) -> Vec<u64> {
+        vec![self.size_]
+    }
+
+    fn save_body<Sink>(&self, sink: &mut Sink) -> std::io::Result<()>
+    where
+        Sink: std::io::Write,
+    {
+        save_vec(sink, &self.keys)?;
+        let kinds: Vec<u8> = self
+            .containers
+            .iter()
+            .map(|c| match c {
+                Container::Array(_) => ARRAY,
+                Container::Bitmap(_, _) => BITMAP,
+                Container::Runs(_) => RUNS,
+            })
+            .collect();
+        save_vec(sink, &kinds)?;
+        for container in self.containers.iter() {
+            match container {
+                Container::Array(xs) => save_vec(sink, xs)?,
+                Container::Bitmap(_, words) => save_vec(sink, words)?,
+                Container::Runs(runs) => {
+                    let flat: Vec<u16> = runs.iter().flat_map(|r| [r.0, r.1]).collect();
+                    save_vec(sink, &flat)?
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn load_body<Source>(params: &[u64], source: &mut Source) -> std::io::Result<Self>
+    where
+        Source: std::io::Read,
+    {
+        check_params(&Self::TYPE_TAG, params, 1)?;
+        let size_: u64 = params[0];
+        let keys = load_vec_u64(source)?;
+        let kinds = load_vec_u8(source)?;
+        if kinds.len() != keys.len() || keys.windows(2).any(|w| w[0] >= w[1]) {
+            return Err(invalid_data(String::from("inconsistent roaring containers")));
+        }
+        let mut containers: Vec<Container> = Vec::with_capacity(keys.len());
+        for kind in kinds.iter() {
+            let container = match *kind {
+                ARRAY => {
+                    let xs = load_vec_u16(source)?;
+                    if xs.is_empty() || xs.windows(2).any(|w| w[0] >= w[1]) {
+                        return Err(invalid_data(String::from("array container empty or not increasing")));
+                    }
+                    Container::Array(xs)
+                }
+                BITMAP => {
+                    let words = load_vec_u64(source)?;
+                    if words.len() != BITMAP_WORDS {
+                        return Err(invalid_data(format!("bitmap container of {} words", words.len())));
+                    }
+                    let count: u64 = words.iter().map(|w| w.count_ones() as u64).sum();
+                    if count == 0 {
+                        return Err(invalid_data(String::from("empty bitmap container")));
+                    }
+                    Container::Bitmap(to_usize(count)?, words)
+                }
+                RUNS => {
+                    let flat = load_vec_u16(source)?;
+                    if flat.is_empty() || flat.len() % 2 != 0 {
+                        return Err(invalid_data(format!("run container of {} values", flat.len())));
+                    }
+                    let runs: Vec<(u16, u16)> = flat.chunks(2).map(|r| (r[0], r[1])).collect();
+                    // The last element of each run must fit in 16 bits, and
+                    // precede the first element of the next run.
+                    let mut next: u32 = 0;
+                    for r in runs.iter() {
+                        let last = r.0 as u32 + r.1 as u32;
+                        if (r.0 as u32) < next || last > u16::MAX as u32 {
+                            return Err(invalid_data(String::from("run container overlapping or out of range")));
+                        }
+                        next = last + 1;
+                    }
+                    Container::Runs(runs)
+                }
+                _ => return Err(invalid_data(format!("unknown container kind {}", kind))),
+            };
+            containers.push(container);
+        }
+        if let (Some(key), Some(container)) = (keys.last(), containers.last()) {
+            let last = container.select(container.len() - 1) as u64;
+            if *key > u64::MAX >> LOW_BITS || (key << LOW_BITS) + last >= size_ {
+                return Err(invalid_data(format!("roaring element beyond size {}", size_)));
+            }
+        }
+        let mut s = Roaring {
+            size_,
+            keys,
+            containers,
+            counts: Vec::new(),
+        };
+        s.recount(0);
+        Ok(s)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use random::Source;
+    use std::collections::BTreeSet;
+
+    #[test]
+    fn test_insert_remove_1() {
+        let mut rng = random::default(19);
+        let mut s = Roaring::new(1 << 40);
+        let mut t: BTreeSet<u64> = BTreeSet::new();
+        for i in 0..20000 {
+            // Mostly clustered in a few containers, so some become bitmaps.
+            let x = match i % 4 {
+                0 => rng.read_u64() % (1 << 40),
+                _ => rng.read_u64() % (1 << 17),
+            };
+            if rng.read_u64().is_multiple_of(5) {
+                assert_eq!(s.remove(x), t.remove(&x));
+            } else {
+                assert_eq!(s.insert(x), t.insert(x));
+            }
+        }
+        let xs: Vec<u64> = t.iter().copied().collect();
+        assert_eq!(s.count(), xs.len());
+        assert_eq!(s.iter().collect::<Vec<u64>>(), xs);
+        assert!(s.containers.iter().any(|c| matches!(c, Container::Bitmap(_, _))));
+        for i in (0..xs.len()).step_by(7) {
+            assert_eq!(s.select(i), xs[i]);
+            assert_eq!(s.rank(xs[i]), i);
+            assert_eq!(s.rank(xs[i] + 1), i + 1);
+        }
+        for x in xs.iter() {
+            assert!(s.remove(*x));
+        }
+        assert_eq!(s.count(), 0);
+        assert_eq!(s.iter().next(), None);
+    }
+
+    #[test]
+    fn test_optimize_1() {
+        let mut xs: Vec<u64> = (1000..9000).collect();
+        xs.extend((1 << 20..(1 << 20) + 10000).step_by(3));
+        xs.extend([1 << 30, (1 << 30) + 1]);
+        let mut s = Roaring::from_sorted(1 << 32, &xs);
+        s.optimize();
+        assert!(matches!(s.containers[0], Container::Runs(_)));
+        assert!(matches!(s.containers[1], Container::Array(_)));
+        for (i, &x) in xs.iter().enumerate() {
+            assert_eq!(s.select(i), x);
+            assert_eq!(s.rank(x), i);
+        }
+        assert_eq!(s.iter_from(8999).take(2).collect::<Vec<u64>>(), vec![8999, 1 << 20]);
+        assert!(s.insert(500));
+        assert!(!s.insert(1000));
+        assert_eq!(s.rank(1000), 1);
+        assert_eq!(s.count(), xs.len() + 1);
+    }
+
+    #[test]
+    fn test_load_and_save_1() {
+        let mut xs: Vec<u64> = (0..5000).map(|i| 7 * i).collect();
+        xs.extend(100000..100100);
+        xs.extend((1 << 33..(1 << 33) + 100).map(|x| 2 * x));
+        let mut s = Roaring::from_sorted(1 << 40, &xs);
+        s.optimize();
+        let mut bytes: Vec<u8> = Vec::new();
+        s.save(&mut bytes).expect("save failed");
+        let t = Roaring::load(&mut bytes.as_slice()).expect("load failed");
+        assert_eq!(t.size(), 1 << 40);
+        assert_eq!(t.iter().collect::<Vec<u64>>(), xs);
+        assert_eq!(t.rank(100050), 5050);
+    }
+
+    #[test]
+    fn test_load_corrupt_1() {
+        use crate::persist::{save_header, save_vec};
+
+        let save = |size: u64, keys: &[u64], kind: u8, body: &[u16]| {
+            let mut bytes: Vec<u8> = Vec::new();
+            save_header(&mut bytes, b"ROAR", &[size]).unwrap();
+            save_vec(&mut bytes, keys).unwrap();
+            save_vec(&mut bytes, &[kind]).unwrap();
+            save_vec(&mut bytes, body).unwrap();
+            bytes
+        };
+        let bytes = save(1 << 20, &[3], ARRAY, &[1, 5, 9]);
+        let s = Roaring::load(&mut bytes.as_slice()).expect("load failed");
+        assert_eq!(s.iter().collect::<Vec<u64>>(), vec![196609, 196613, 196617]);
+        let bytes = save(1 << 20, &[3], RUNS, &[1, 2, 9, 0]);
+        assert!(Roaring::load(&mut bytes.as_slice()).is_ok());
+        // Array containers that are unsorted or empty.
+        let bytes = save(1 << 20, &[3], ARRAY, &[5, 1, 9]);
+        assert!(Roaring::load(&mut bytes.as_slice()).is_err());
+        let bytes = save(1 << 20, &[3], ARRAY, &[]);
+        assert!(Roaring::load(&mut bytes.as_slice()).is_err());
+        // Run containers with an odd number of values, or overflowing, or overlapping runs.
+        let bytes = save(1 << 20, &[3], RUNS, &[1, 2, 9]);
+        assert!(Roaring::load(&mut bytes.as_slice()).is_err());
+        let bytes = save(1 << 20, &[3], RUNS, &[65530, 6]);
+        assert!(Roaring::load(&mut bytes.as_slice()).is_err());
+        let bytes = save(1 << 20, &[3], RUNS, &[1, 2, 3, 0]);
+        assert!(Roaring::load(&mut bytes.as_slice()).is_err());
+        // Elements beyond the size.
+        let bytes = save(196617, &[3], ARRAY, &[1, 5, 9]);
+        assert!(Roaring::load(&mut bytes.as_slice()).is_err());
+        let bytes = save(u64::MAX, &[1 << 48], ARRAY, &[1]);
+        assert!(Roaring::load(&mut bytes.as_slice()).is_err());
+    }
+}
@@ -0,0 +1,571 @@
+//! A compressed dense set based on the Raman, Raman & Rao 2002 paper:
+//!
+//! > Raman, R., Raman, V. and Rao, S.S., 2002, January. Succinct indexable dictionaries with
+//! > applications to encoding k-ary trees and multisets. In Proceedings of the thirteenth annual
+//! > ACM-SIAM symposium on Discrete algorithms (pp. 233-242).
+
+use crate::{
+    algebra::FromSorted,
+    bitvec::{BitVec, FromBitVec},
+    ecode::EnumCodeSet,
+    intvec::IntVec,
+    iter::IterableSet,
+    persist::{check_params, invalid_data, load_vec_u64, save_vec, to_usize, Persistent},
+    rank::Rank,
+    select::{Select, Select0, Successor, Successor0},
+    set::ImpliedSet,
+    words::{get_bits, rank64, select64},
+};
+
+/// The number of blocks covered by each superblock sample.
+static SUPERBLOCK_BLOCKS: usize = 32;
+
+/// The default number of bits in a block.
+pub static DEFAULT_BLOCK_SIZE: usize = 31;
+
+/// The `Rrr` data structure implements the compressed dense set representation
+/// described by Raman, Raman & Rao.
+///
+/// The bit vector is broken into blocks of `t` bits. Each block is stored as its
+/// class (the number of one bits it contains) and its offset (the rank of the
+/// block among all the `t` bit blocks of that class). The offsets of blocks with
+/// few or many one bits are short, so the space used is close to the zero-order
+/// entropy of the bit vector, which is much less than one bit per position for
+/// sets with a density of a few percent. Every 32 blocks, the number of preceding
+/// elements and the position of the next offset are sampled to accelerate `rank`,
+/// `select` and `select_0`.
+///
+/// #Examples
+///
+/// ```
+/// use crate::ransel::rrr::Rrr;
+/// use crate::ransel::rank::Rank;
+/// use crate::ransel::select::{Select, Select0};
+///
+/// let mut words: Vec<u64> = vec![0; 1024];
+/// let mut xs: Vec<u64> = Vec::new();
+/// for x in (0..65536u64).step_by(17) {
+///     words[(x / 64) as usize] |= 1 << (x & 63);
+///     xs.push(x);
+/// }
+/// let s = Rrr::new(65536, &words);
+/// for i in 0..xs.len() {
+///     assert_eq!(s.select(i), xs[i]);
+///     assert_eq!(s.rank(xs[i]), i);
+/// }
+/// assert_eq!(s.select_0(16), 18);
+/// ```
+pub struct Rrr {
+    size_: u64,
+    t: usize,
+    count: usize,
+    classes: IntVec,
+    offsets: BitVec,
+    rank_samples: Vec<u64>,
+    offset_samples: Vec<u64>,
+    widths: Vec<usize>,
+}
+
+fn class_widths(t: usize) -> Vec<usize> {
+    (0..=t)
+        .map(|k| EnumCodeSet::new(t as u64, k as u64).bits() as usize)
+        .collect()
+}
+
+fn class_bits(t: usize) -> usize {
+    (usize::BITS - t.leading_zeros()) as usize
+}
+
+impl Rrr {
+    /// Create a new set over the domain `[0, size_)` from the words of a bit vector,
+    /// using the default block size.
+    ///
+    /// Bit `j` of `words[i]` represents the element `64 * i + j`. Bits at or
+    /// beyond `size_` must be zero.
+    pub fn new(size_: u64, words: &[u64]) -> Rrr {
+        Rrr::with_block_size(DEFAULT_BLOCK_SIZE, size_, words)
+    }
+
+    /// Create a new set from a bit vector, using the default block size.
+    pub fn from_bitvec(bits: BitVec) -> Rrr {
+        Rrr::new(bits.len() as u64, bits.as_words())
+    }
+
+    /// Create a new set with blocks of `t` bits, where `t` is in `[1, 63]`.
+    ///
+    /// Larger blocks give better compression, at the cost of slower decoding of
+    /// each block.
+    pub fn with_block_size(t: usize, size_: u64, words: &[u64]) -> Rrr {
+        assert!(t > 0 && t < 64);
+        std::debug_assert!(size_.div_ceil(64) <= words.len() as u64);
+
+        let widths = class_widths(t);
+        let block_count = size_.div_ceil(t as u64) as usize;
+        let mut classes = IntVec::new(class_bits(t));
+        let mut offsets = BitVec::new();
+        let mut rank_samples: Vec<u64> = Vec::new();
+        let mut offset_samples: Vec<u64> = Vec::new();
+        let mut count: usize = 0;
+        for j in 0..block_count {
+            if j % SUPERBLOCK_BLOCKS == 0 {
+                rank_samples.push(count as u64);
+                offset_samples.push(offsets.len() as u64);
+            }
+            let block = get_bits(words, j * t, t);
+            let k = block.count_ones() as usize;
+            classes.push(k as u64);
+            let offset = EnumCodeSet::new(t as u64, k as u64).rank(block);
+            offsets.push_bits(offset, widths[k]);
+            count += k;
+        }
+        rank_samples.push(count as u64);
+        offset_samples.push(offsets.len() as u64);
+
+        Rrr {
+            size_,
+            t,
+            count,
+            classes,
+            offsets,
+            rank_samples,
+            offset_samples,
+            widths,
+        }
+    }
+
+    /// Return the number of bits in each block.
+    pub fn block_size(&self) -> usize {
+        self.t
+    }
+
+    fn block_count(&self) -> usize {
+        self.classes.len()
+    }
+
+    fn decode(&self, k: usize, offset_pos: usize) -> u64 {
+        let offset = self.offsets.get_bits(offset_pos, self.widths[k]);
+        EnumCodeSet::new(self.t as u64, k as u64).select(offset)
+    }
+
+    /// Return the number of elements before superblock `s`, and the
+    /// position of its first offset.
+    fn superblock(&self, s: usize) -> (usize, usize) {
+        (self.rank_samples[s] as usize, self.offset_samples[s] as usize)
+    }
+
+    /// Return the number of elements before block `j`, and the position
+    /// of its offset.
+    fn block_start(&self, j: usize) -> (usize, usize) {
+        let (mut r, mut pos) = self.superblock(j / SUPERBLOCK_BLOCKS);
+        for b in (j - j % SUPERBLOCK_BLOCKS)..j {
+            let k = self.classes.get(b) as usize;
+            r += k;
+            pos += self.widths[k];
+        }
+        (r, pos)
+    }
+
+    fn zeros_before(&self, s: usize) -> usize {
+        let bits = (s * SUPERBLOCK_BLOCKS * self.t).min(self.size_ as usize);
+        bits - self.rank_samples[s] as usize
+    }
+
+    /// Find the last superblock for which `f` is at most `index`.
+    fn find_superblock<F>(&self, index: usize, f: F) -> usize
+    where
+        F: Fn(usize) -> usize,
+    {
+        let mut first = 0;
+        let mut count = self.rank_samples.len();
+        while count > 0 {
+            let step = count / 2;
+            let s = first + step;
+            if f(s) <= index {
+                first = s + 1;
+                count -= step + 1;
+            } else {
+                count = step;
+            }
+        }
+        first - 1
+    }
+}
+
+impl ImpliedSet for Rrr {
+    fn count(&self) -> usize {
+        self.count
+    }
+
+    fn size(&self) -> u64 {
+        self.size_
+    }
+}
+
+impl Rank for Rrr {
+    fn rank(&self, value: u64) -> usize {
+        if value >= self.size_ {
+            return self.count;
+        }
+        let j = (value / self.t as u64) as usize;
+        let (r, pos) = self.block_start(j);
+        let k = self.classes.get(j) as usize;
+        let block = self.decode(k, pos);
+        r + rank64(block, value % self.t as u64) as usize
+    }
+}
+
+impl Select for Rrr {
+    fn select(&self, index: usize) -> u64 {
+        assert!(index < self.count);
+        let s = self.find_superblock(index, |s| self.rank_samples[s] as usize);
+        let (mut r, mut pos) = self.superblock(s);
+        let mut j = s * SUPERBLOCK_BLOCKS;
+        loop {
+            let k = self.classes.get(j) as usize;
+            if r + k > index {
+                let block = self.decode(k, pos);
+                return (j * self.t) as u64 + select64(block, index - r);
+            }
+            r += k;
+            pos += self.widths[k];
+            j += 1;
+        }
+    }
+}
+
+impl Select0 for Rrr {
+    fn select_0(&self, index: usize) -> u64 {
+        assert!(index < self.size_ as usize - self.count);
+        let s = self.find_superblock(index, |s| self.zeros_before(s));
+        let (_, mut pos) = self.superblock(s);
+        let mut z = self.zeros_before(s);
+        let mut j = s * SUPERBLOCK_BLOCKS;
+        let m = (1u64 << self.t) - 1;
+        while j < self.block_count() {
+            let k = self.classes.get(j) as usize;
+            if z + self.t - k > index {
+                let block = self.decode(k, pos);
+                return (j * self.t) as u64 + select64(!block & m, index - z);
+            }
+            z += self.t - k;
+            pos += self.widths[k];
+            j += 1;
+        }
+        unreachable!();
+    }
+}
+
+impl Successor for Rrr {}
+
+impl FromBitVec for Rrr {
+    fn from_bitvec(bits: BitVec) -> Self {
+        Rrr::from_bitvec(bits)
+    }
+}
+
+impl FromSorted for Rrr {
+    fn from_sorted(size: u64, elements: &[u64]) -> Self {
+        let mut words: Vec<u64> = vec![0; size.div_ceil(64) as usize];
+        for x in elements.iter() {
+            words[(x / 64) as usize] |= 1u64 << (x & 63);
+        }
+        Rrr::new(size, &words)
+    }
+}
+
+impl Successor0 for Rrr {}
+
+/// An iterator over the elements of an [`Rrr`] set, which decodes each
+/// block once.
+pub struct RrrIter<'a> {
+    rrr: &'a Rrr,
+    block: usize,
+    pos: usize,
+    word: u64,
+    remaining: usize,
+}
+
+impl<'a> Iterator for RrrIter<'a> {
+    type Item = u64;
+
+    fn next(&mut self) -> Option<u64> {
+        if self.remaining == 0 {
+            return None;
+        }
+        while self.word == 0 {
+            let k = self.rrr.classes.get(self.block) as usize;
+            self.pos += self.rrr.widths[k];
+            self.block += 1;
+            let k = self.rrr.classes.get(self.block) as usize;
+            self.word = self.rrr.decode(k, self.pos);
+        }
+        let b = self.word.trailing_zeros() as u64;
+        self.word &= self.word - 1;
+        self.remaining -= 1;
+        Some((self.block * self.rrr.t) as u64 + b)
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (self.remaining, Some(self.remaining))
+    }
+}
+
+impl<'a> ExactSizeIterator for RrrIter<'a> {}
+
+impl IterableSet for Rrr {
+    type Iter<'a> = RrrIter<'a>;
+
+    fn iter_from(&self, value: u64) -> Self::Iter<'_> {
+        if value >= self.size_ {
+            return RrrIter {
+                rrr: self,
+                block: 0,
+                pos: 0,
+                word: 0,
+                remaining: 0,
+            };
+        }
+        let j = (value / self.t as u64) as usize;
+        let (r, pos) = self.block_start(j);
+        let k = self.classes.get(j) as usize;
+        let word = self.decode(k, pos) & !((1u64 << (value % self.t as u64)) - 1);
+        RrrIter {
+            rrr: self,
+            block: j,
+            pos,
+            word,
+            remaining: self.count - r - (k - word.count_ones() as usize),
+        }
+    }
+}
+
+impl Persistent for Rrr {
+    const TYPE_TAG: [u8; 4] = *b"RRRB";
+
+    fn params(&self) -> Vec<u64> {
+        vec![self.size_, self.t as u64, self.count as u64]
+    }
+
+    fn save_body<Sink>(&self, sink: &mut Sink) -> std::io::Result<()>
+    where
+        Sink: std::io::Write,
+    {
+        self.classes.save(sink)?;
+        self.offsets.save(sink)?;
+        save_vec(sink, &self.rank_samples)?;
+        save_vec(sink, &self.offset_samples)?;
+        Ok(())
+    }
+
+    fn load_body<Source>(params: &[u64], source: &mut Source) -> std::io::Result<Self>
+    where
+        Source: std::io::Read,
+    {
+        check_params(&Self::TYPE_TAG, params, 3)?;
+        let size_: u64 = params[0];
+        let t: usize = to_usize(params[1])?;
+        let count: usize = to_usize(params[2])?;
+        if t == 0 || t >= 64 {
+            return Err(invalid_data(format!("invalid RRR block size {}", t)));
+        }
+        let classes: IntVec = *(IntVec::load(source)?);
+        let offsets: BitVec = *(BitVec::load(source)?);
+        let rank_samples: Vec<u64> = load_vec_u64(source)?;
+        let offset_samples: Vec<u64> = load_vec_u64(source)?;
+        let block_count = size_.div_ceil(t as u64) as usize;
+        let sample_count = block_count.div_ceil(SUPERBLOCK_BLOCKS) + 1;
+        if classes.len() != block_count
+            || classes.width() != class_bits(t)
+            || rank_samples.len() != sample_count
+            || offset_samples.len() != sample_count
+        {
+            return Err(invalid_data(String::from("inconsistent RRR index")));
+        }
+        // The samples, the offsets and the count must all agree with the classes.
+        let widths = class_widths(t);
+        let (mut r, mut pos) = (0u64, 0u64);
+        for j in 0..block_count {
+            if j % SUPERBLOCK_BLOCKS == 0
+                && (rank_samples[j / SUPERBLOCK_BLOCKS] != r || offset_samples[j / SUPERBLOCK_BLOCKS] != pos)
+            {
+                return Err(invalid_data(format!("RRR samples inconsistent at block {}", j)));
+            }
+            let k = classes.get(j) as usize;
+            if k > t {
+                return Err(invalid_data(format!("RRR block {} has class {} > {}", j, k, t)));
+            }
+            r += k as u64;
+            pos += widths[k] as u64;
+        }
+        if rank_samples[sample_count - 1] != r
+            || offset_samples[sample_count - 1] != pos
+            || offsets.len() as u64 != pos
+            || count as u64 != r
+        {
+            return Err(invalid_data(String::from("RRR count or offsets inconsistent with the classes")));
+        }
+        Ok(Rrr {
+            size_,
+            t,
+            count,
+            classes,
+            offsets,
+            rank_samples,
+            offset_samples,
+            widths,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use random::Source;
+
+    fn make_words(m: usize, density: u64) -> (Vec<u64>, Vec<u64>) {
+        let mut rng = random::default(19);
+        let mut words: Vec<u64> = vec![0; m.div_ceil(64)];
+        let mut xs: Vec<u64> = Vec::new();
+        for x in 0..m as u64 {
+            if rng.read_u64() % 100 < density {
+                words[(x / 64) as usize] |= 1 << (x & 63);
+                xs.push(x);
+            }
+        }
+        (words, xs)
+    }
+
+    #[test]
+    fn test_rank_select_1() {
+        let m = 100_000;
+        let (words, xs) = make_words(m, 5);
+        for t in [7, 15, 31, 63] {
+            let r = Rrr::with_block_size(t, m as u64, &words);
+            assert_eq!(r.count(), xs.len());
+            for (i, &x) in xs.iter().enumerate() {
+                assert_eq!(r.rank(x), i);
+                assert!(r.contains(x));
+                assert_eq!(r.select(i), x);
+            }
+            assert_eq!(r.rank(m as u64), xs.len());
+        }
+    }
+
+    #[test]
+    fn test_select0_1() {
+        let m = 10_000;
+        let (words, xs) = make_words(m, 20);
+        let r = Rrr::new(m as u64, &words);
+        let mut zs: Vec<u64> = Vec::new();
+        let mut j = 0;
+        for x in 0..m as u64 {
+            if j < xs.len() && xs[j] == x {
+                j += 1;
+            } else {
+                zs.push(x);
+            }
+        }
+        for (i, &z) in zs.iter().enumerate() {
+            assert_eq!(r.select_0(i), z);
+            assert_eq!(r.rank_0(z), i);
+        }
+    }
+
+    #[test]
+    fn test_iter_1() {
+        let m = 20_000;
+        let (words, xs) = make_words(m, 10);
+        for t in [7, 31, 63] {
+            let r = Rrr::with_block_size(t, m as u64, &words);
+            assert_eq!(r.iter().len(), xs.len());
+            assert_eq!(r.iter().collect::<Vec<u64>>(), xs);
+            for i in (0..xs.len()).step_by(97) {
+                assert_eq!(r.iter_from(xs[i]).collect::<Vec<u64>>(), xs[i..]);
+                assert_eq!(r.iter_from(xs[i] + 1).next(), xs.get(i + 1).copied());
+            }
+            assert_eq!(r.iter_from(m as u64).next(), None);
+        }
+    }
+
+    #[test]
+    fn test_successor_1() {
+        let m = 5_000;
+        let (words, xs) = make_words(m, 30);
+        let r = Rrr::new(m as u64, &words);
+        let zs: Vec<u64> = (0..m as u64).filter(|x| !r.contains(*x)).collect();
+        for x in 0..m as u64 + 1 {
+            let i = xs.partition_point(|y| *y < x);
+            assert_eq!(r.successor(x), xs.get(i).copied());
+            assert_eq!(r.predecessor(x), i.checked_sub(1).map(|j| xs[j]));
+            let i = zs.partition_point(|y| *y < x);
+            assert_eq!(r.successor_0(x), zs.get(i).copied());
+            assert_eq!(r.predecessor_0(x), i.checked_sub(1).map(|j| zs[j]));
+        }
+    }
+
+    #[test]
+    fn test_compression_1() {
+        let m = 1 << 20;
+        let (words, _xs) = make_words(m, 2);
+        let r = Rrr::with_block_size(63, m as u64, &words);
+        // 2% density has an entropy of about 0.14 bits per position.
+        assert!(r.offsets.len() + r.classes.len() * 6 < m / 4);
+    }
+
+    #[test]
+    fn test_load_and_save_1() {
+        let m = 10_000;
+        let (words, xs) = make_words(m, 10);
+        let r = Rrr::with_block_size(15, m as u64, &words);
+        let mut bytes: Vec<u8> = Vec::new();
+        r.save(&mut bytes).expect("save failed");
+        let s = Rrr::load(&mut bytes.as_slice()).expect("load failed");
+        assert_eq!(s.block_size(), 15);
+        for (i, &x) in xs.iter().enumerate() {
+            assert_eq!(s.rank(x), i);
+            assert_eq!(s.select(i), x);
+        }
+    }
+
+    #[test]
+    fn test_load_corrupt_1() {
+        use crate::persist::save_header;
+
+        let (words, _xs) = make_words(2000, 10);
+        let r = Rrr::with_block_size(20, 2000, &words);
+        let save = |count: usize, classes: &IntVec, rank_samples: &[u64]| {
+            let mut bytes: Vec<u8> = Vec::new();
+            save_header(&mut bytes, b"RRRB", &[2000, 20, count as u64]).unwrap();
+            classes.save(&mut bytes).unwrap();
+            r.offsets.save(&mut bytes).unwrap();
+            save_vec(&mut bytes, rank_samples).unwrap();
+            save_vec(&mut bytes, &r.offset_samples).unwrap();
+            bytes
+        };
+        let bytes = save(r.count, &r.classes, &r.rank_samples);
+        assert!(Rrr::load(&mut bytes.as_slice()).is_ok());
+        // A count that does not match the classes.
+        let bytes = save(r.count + 1, &r.classes, &r.rank_samples);
+        assert!(Rrr::load(&mut bytes.as_slice()).is_err());
+        // A rank sample that does not match the classes.
+        let mut rank_samples = r.rank_samples.clone();
+        rank_samples[2] += 1;
+        let bytes = save(r.count, &r.classes, &rank_samples);
+        assert!(Rrr::load(&mut bytes.as_slice()).is_err());
+        // A class larger than the block size.
+        let mut classes = IntVec::new(r.classes.width());
+        for j in 0..r.classes.len() {
+            classes.push(if j == 7 { 21 } else { r.classes.get(j) });
+        }
+        let bytes = save(r.count, &classes, &r.rank_samples);
+        assert!(Rrr::load(&mut bytes.as_slice()).is_err());
+        // Classes of the wrong width.
+        let mut classes = IntVec::new(8);
+        for j in 0..r.classes.len() {
+            classes.push(r.classes.get(j));
+        }
+        let bytes = save(r.count, &classes, &r.rank_samples);
+        assert!(Rrr::load(&mut bytes.as_slice()).is_err());
+    }
+}
@@ -0,0 +1,154 @@
+//! Suffix array construction by induced sorting, based on the Nong, Zhang & Chan 2009 paper:
+//!
+//! > Nong, G., Zhang, S. and Chan, W.H., 2009, March. Linear suffix array
+//! > construction by almost pure induced-sorting. In 2009 data compression
+//! > conference (pp. 193-202). IEEE.
+
+/// A suffix array entry not yet filled in.
+const EMPTY: usize = usize::MAX;
+
+/// Return the suffix array of `text`, whose symbols are in `[0, sigma)`.
+///
+/// The text must end with a sentinel symbol 0, which occurs nowhere else.
+pub(crate) fn suffix_array(text: &[usize], sigma: usize) -> Vec<usize> {
+    let n = text.len();
+    assert!(n > 0 && text[n - 1] == 0);
+    if n == 1 {
+        return vec![0];
+    }
+
+    // is_s[i] is true if suffix i is smaller than suffix i + 1.
+    let mut is_s = vec![true; n];
+    for i in (0..n - 1).rev() {
+        is_s[i] = text[i] < text[i + 1] || (text[i] == text[i + 1] && is_s[i + 1]);
+    }
+    let is_lms = |i: usize| i > 0 && is_s[i] && !is_s[i - 1];
+
+    let mut bucket_sizes = vec![0usize; sigma];
+    for c in text.iter() {
+        bucket_sizes[*c] += 1;
+    }
+
+    // Sort the LMS suffixes by their LMS substrings.
+    let lms: Vec<usize> = (1..n).filter(|i| is_lms(*i)).collect();
+    let sa = induce(text, &is_s, &bucket_sizes, &lms);
+
+    // Name the LMS substrings in sorted order, giving equal substrings the same name.
+    let mut names = vec![EMPTY; n];
+    let mut name = 0;
+    let mut prev = EMPTY;
+    for p in sa.iter().copied().filter(|p| is_lms(*p)) {
+        if prev != EMPTY && !lms_substrings_equal(text, &is_s, prev, p) {
+            name += 1;
+        }
+        names[p] = name;
+        prev = p;
+    }
+    let reduced: Vec<usize> = lms.iter().map(|p| names[*p]).collect();
+
+    // Sort the LMS suffixes, recursively if their substrings are not all distinct.
+    let reduced_sa = if name + 1 == reduced.len() {
+        let mut reduced_sa = vec![0; reduced.len()];
+        for (i, x) in reduced.iter().enumerate() {
+            reduced_sa[*x] = i;
+        }
+        reduced_sa
+    } else {
+        suffix_array(&reduced, name + 1)
+    };
+    let sorted_lms: Vec<usize> = reduced_sa.iter().map(|i| lms[*i]).collect();
+    induce(text, &is_s, &bucket_sizes, &sorted_lms)
+}
+
+/// Return true if the LMS substrings starting at `a` and `b` are equal.
+fn lms_substrings_equal(text: &[usize], is_s: &[bool], a: usize, b: usize) -> bool {
+    let is_lms = |i: usize| i > 0 && is_s[i] && !is_s[i - 1];
+    let mut d = 0;
+    loop {
+        if text[a + d] != text[b + d] || is_s[a + d] != is_s[b + d] {
+            return false;
+        }
+        if d > 0 {
+            match (is_lms(a + d), is_lms(b + d)) {
+                (true, true) => return true,
+                (false, false) => {}
+                _ => return false,
+            }
+        }
+        d += 1;
+    }
+}
+
+/// Induce the order of all the suffixes from the order of the LMS suffixes `lms`.
+fn induce(text: &[usize], is_s: &[bool], bucket_sizes: &[usize], lms: &[usize]) -> Vec<usize> {
+    let n = text.len();
+    let mut sa = vec![EMPTY; n];
+    let bucket_ends = || -> Vec<usize> {
+        bucket_sizes
+            .iter()
+            .scan(0, |end, size| {
+                *end += size;
+                Some(*end)
+            })
+            .collect()
+    };
+
+    // Place the LMS suffixes at the ends of their buckets, keeping their order.
+    let mut tails = bucket_ends();
+    for p in lms.iter().rev() {
+        let c = text[*p];
+        tails[c] -= 1;
+        sa[tails[c]] = *p;
+    }
+
+    // Induce the L-type suffixes from the fronts of the buckets.
+    let mut heads: Vec<usize> = bucket_ends().iter().zip(bucket_sizes.iter()).map(|(e, s)| e - s).collect();
+    for j in 0..n {
+        let p = sa[j];
+        if p != EMPTY && p > 0 && !is_s[p - 1] {
+            let c = text[p - 1];
+            sa[heads[c]] = p - 1;
+            heads[c] += 1;
+        }
+    }
+
+    // Induce the S-type suffixes from the ends of the buckets.
+    let mut tails = bucket_ends();
+    for j in (0..n).rev() {
+        let p = sa[j];
+        if p != EMPTY && p > 0 && is_s[p - 1] {
+            let c = text[p - 1];
+            tails[c] -= 1;
+            sa[tails[c]] = p - 1;
+        }
+    }
+    sa
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use random::Source;
+
+    fn naive_suffix_array(text: &[usize]) -> Vec<usize> {
+        let mut sa: Vec<usize> = (0..text.len()).collect();
+        sa.sort_by(|a, b| text[*a..].cmp(&text[*b..]));
+        sa
+    }
+
+    #[test]
+    fn test_suffix_array_1() {
+        let mut rng = random::default(19);
+        for sigma in [2, 3, 5, 200] {
+            for n in [1, 2, 10, 100, 2000] {
+                let mut text: Vec<usize> = (1..n).map(|_i| 1 + (rng.read_u64() % (sigma - 1) as u64) as usize).collect();
+                text.push(0);
+                assert_eq!(suffix_array(&text, sigma), naive_suffix_array(&text));
+            }
+        }
+        let mut text: Vec<usize> = "mississippi".bytes().map(|c| c as usize).collect();
+        text.push(0);
+        assert_eq!(suffix_array(&text, 256), vec![11, 10, 7, 4, 1, 0, 9, 8, 6, 3, 5, 2]);
+    }
+}
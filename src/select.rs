@@ -1,6 +1,10 @@
 //! Traits for sets supporting the `select` operation.
 
-use crate::{rank::Rank, set::ImpliedSet};
+use crate::{
+    error::{check_index, Result},
+    rank::Rank,
+    set::ImpliedSet,
+};
 
 // Operations for sets supporting select.
 ///
@@ -13,10 +17,17 @@ pub trait Select: ImpliedSet {
     ///
     /// `index` is a rank within the implied set: [0, self.count()).
     fn select(&self, index: usize) -> u64;
+
+    /// Return the i-th smallest element of the set, or an error if `index`
+    /// is not less than `self.count()`.
+    fn try_select(&self, index: usize) -> Result<u64> {
+        check_index(index, self.count())?;
+        Ok(self.select(index))
+    }
 }
 
 /// We provide a naive implementation of `select_0` with a default implementation
-/// in terms of of [`Rank`](crate::rank::Rank).
+/// in terms of of [`Rank`].
 ///
 /// While use of a mixture of `rank_1` and `rank_0` is quite common, the use of
 /// `select_0` (selecting the i-th smallest element not in the set) is uncommon,
@@ -41,4 +52,114 @@ pub trait Select0: ImpliedSet + Rank {
         }
         first - 1
     }
+
+    /// Return the i-th smallest element not in the set, or an error if `index`
+    /// is not less than `self.size() - self.count()`.
+    fn try_select_0(&self, index: usize) -> Result<u64> {
+        check_index(index, self.size() as usize - self.count())?;
+        Ok(self.select_0(index))
+    }
+}
+
+/// Operations for finding the elements of a set nearest to a given value.
+///
+/// The default implementations use one `rank` and at most one `select`, and
+/// return `None` rather than panicking when there is no such element, but
+/// implementations may take shortcuts when the answer is close to the given value.
+///
+/// #Examples
+///
+/// ```
+/// use crate::ransel::select::Successor;
+/// use crate::ransel::sorted::Sorted;
+///
+/// let s = Sorted::new(&[3, 17, 23, 64]);
+/// assert_eq!(s.successor(17), Some(17));
+/// assert_eq!(s.next_one_after(17), Some(23));
+/// assert_eq!(s.predecessor(17), Some(3));
+/// assert_eq!(s.predecessor(3), None);
+/// assert_eq!(s.successor(65), None);
+/// ```
+pub trait Successor: Rank + Select {
+    /// Return the smallest element of the set greater than or equal to `value`.
+    fn successor(&self, value: u64) -> Option<u64> {
+        if value >= self.size() {
+            return None;
+        }
+        let r = self.rank(value);
+        if r < self.count() {
+            Some(self.select(r))
+        } else {
+            None
+        }
+    }
+
+    /// Return the largest element of the set strictly less than `value`.
+    fn predecessor(&self, value: u64) -> Option<u64> {
+        let r = if value >= self.size() {
+            self.count()
+        } else {
+            self.rank(value)
+        };
+        if r > 0 {
+            Some(self.select(r - 1))
+        } else {
+            None
+        }
+    }
+
+    /// Return the smallest element of the set strictly greater than `value`.
+    #[inline]
+    fn next_one_after(&self, value: u64) -> Option<u64> {
+        value.checked_add(1).and_then(|x| self.successor(x))
+    }
+
+    /// `prev_one_before` is an alias for `predecessor`, to pair with `next_one_after`.
+    #[inline]
+    fn prev_one_before(&self, value: u64) -> Option<u64> {
+        self.predecessor(value)
+    }
+}
+
+/// The counterpart of [`Successor`] for the elements of the domain *not* in
+/// the set, which is mostly useful for dense sets.
+pub trait Successor0: Rank + Select0 {
+    /// Return the smallest non-element greater than or equal to `value`.
+    fn successor_0(&self, value: u64) -> Option<u64> {
+        if value >= self.size() {
+            return None;
+        }
+        let r = self.rank_0(value);
+        if r < (self.size() as usize) - self.count() {
+            Some(self.select_0(r))
+        } else {
+            None
+        }
+    }
+
+    /// Return the largest non-element strictly less than `value`.
+    fn predecessor_0(&self, value: u64) -> Option<u64> {
+        let r = if value >= self.size() {
+            (self.size() as usize) - self.count()
+        } else {
+            self.rank_0(value)
+        };
+        if r > 0 {
+            Some(self.select_0(r - 1))
+        } else {
+            None
+        }
+    }
+
+    /// Return the smallest non-element strictly greater than `value`.
+    #[inline]
+    fn next_zero_after(&self, value: u64) -> Option<u64> {
+        value.checked_add(1).and_then(|x| self.successor_0(x))
+    }
+
+    /// `prev_zero_before` is an alias for `predecessor_0`, to pair with `next_zero_after`.
+    #[inline]
+    fn prev_zero_before(&self, value: u64) -> Option<u64> {
+        self.predecessor_0(value)
+    }
 }
@@ -1,6 +1,14 @@
 //! A simple sparse set based on a sorted vector of elements.
 
-use crate::{rank::Rank, select::Select, set::ImpliedSet};
+use crate::{
+    error::{check_elements, Result},
+    algebra::FromSorted,
+    iter::IterableSet,
+    persist::{check_params, invalid_data, load_vec_u64, save_vec, Persistent},
+    rank::Rank,
+    select::{Select, Successor},
+    set::ImpliedSet,
+};
 
 /// A simple sparse set based on an unindexed sorted vector of elements.
 pub struct Sorted {
@@ -14,6 +22,12 @@ impl Sorted {
             elements: Vec::from(elements),
         }
     }
+
+    /// Create a new set, or return an error if `elements` are not strictly increasing.
+    pub fn try_new(elements: &[u64]) -> Result<Sorted> {
+        check_elements(elements, 64)?;
+        Ok(Sorted::new(elements))
+    }
 }
 
 impl ImpliedSet for Sorted {
@@ -56,6 +70,76 @@ impl Select for Sorted {
     }
 }
 
+impl Successor for Sorted {}
+
+impl FromSorted for Sorted {
+    fn from_sorted(_size: u64, elements: &[u64]) -> Self {
+        Sorted::new(elements)
+    }
+}
+
+impl IterableSet for Sorted {
+    type Iter<'a> = std::iter::Copied<std::slice::Iter<'a, u64>>;
+
+    fn iter_from(&self, value: u64) -> Self::Iter<'_> {
+        self.elements[self.rank(value)..].iter().copied()
+    }
+}
+
+impl Persistent for Sorted {
+    const TYPE_TAG: [u8; 4] = *b"SRTD";
+
+    fn params(&self) -> Vec<u64> {
+        Vec::new()
+    }
+
+    fn save_body<Sink>(&self, sink: &mut Sink) -> std::io::Result<()>
+    where
+        Sink: std::io::Write,
+    {
+        save_vec(sink, &self.elements)
+    }
+
+    fn load_body<Source>(params: &[u64], source: &mut Source) -> std::io::Result<Self>
+    where
+        Source: std::io::Read,
+    {
+        check_params(&Self::TYPE_TAG, params, 0)?;
+        let elements = load_vec_u64(source)?;
+        check_elements(&elements, 64).map_err(|e| invalid_data(e.to_string()))?;
+        Ok(Sorted { elements })
+    }
+}
+
+#[cfg(feature = "serde")]
+impl serde::Serialize for Sorted {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> std::result::Result<S::Ok, S::Error> {
+        use serde::ser::SerializeStruct;
+        let mut state = serializer.serialize_struct("Sorted", 1)?;
+        state.serialize_field("elements", &self.elements)?;
+        state.end()
+    }
+}
+
+#[cfg(feature = "serde")]
+#[derive(serde::Deserialize)]
+#[serde(rename = "Sorted")]
+struct SortedData {
+    elements: Vec<u64>,
+}
+
+#[cfg(feature = "serde")]
+impl<'de> serde::Deserialize<'de> for Sorted {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> std::result::Result<Self, D::Error> {
+        use serde::de::Error;
+        let data = SortedData::deserialize(deserializer)?;
+        check_elements(&data.elements, 64).map_err(D::Error::custom)?;
+        Ok(Sorted {
+            elements: data.elements,
+        })
+    }
+}
+
 #[cfg(test)]
 mod tests {
     #[allow(unused_imports)]
@@ -97,13 +181,44 @@ mod tests {
         println!("xs={:?}", xs);
 
         let r = Sorted::new(&xs);
-        for i in 0..xs.len() {
-            let x = xs[i];
+        for (i, &x) in xs.iter().enumerate() {
             println!("xs[i]={}", x);
             let j = r.rank(x);
             assert_eq!(j, i);
             assert!(r.contains(x));
             assert_eq!(r.access_and_rank(x), (i, true));
         }
+        assert_eq!(r.iter().collect::<Vec<u64>>(), xs);
+        assert_eq!(r.iter_from(xs[10] + 1).next(), Some(xs[11]));
+    }
+
+    #[test]
+    fn test_load_corrupt_1() {
+        use crate::persist::save_header;
+
+        let save = |elements: &[u64]| {
+            let mut bytes: Vec<u8> = Vec::new();
+            save_header(&mut bytes, b"SRTD", &[]).unwrap();
+            save_vec(&mut bytes, elements).unwrap();
+            bytes
+        };
+        let bytes = save(&[1, 5, 1000]);
+        assert!(Sorted::load(&mut bytes.as_slice()).is_ok());
+        let bytes = save(&[1, 1000, 5]);
+        assert!(Sorted::load(&mut bytes.as_slice()).is_err());
+        let bytes = save(&[1, 5, 5]);
+        assert!(Sorted::load(&mut bytes.as_slice()).is_err());
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn test_serde_1() {
+        let s = Sorted::new(&[1, 5, 1000]);
+        let json = serde_json::to_string(&s).expect("serialize failed");
+        let t: Sorted = serde_json::from_str(&json).expect("deserialize failed");
+        assert_eq!(t.select(2), 1000);
+
+        assert!(serde_json::from_str::<Sorted>(r#"{"elements":[1,5,5]}"#).is_err());
+        assert!(serde_json::from_str::<Sorted>(r#"{"elements":[5,1]}"#).is_err());
     }
 }
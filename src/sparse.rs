@@ -5,13 +5,15 @@
 //1 > Society for Industrial and Applied Mathematics.
 
 use crate::{
-    bitvec::BitVec,
-    dense64::Dense64,
-    intvec::IntVec,
-    persist::{load_usize, Persistent},
+    error::{check_param, check_value, Error, Result},
+    algebra::{domain_bits, FromSorted},
+    bitvec::{AsWords, BitVec, FromBitVec},
+    dense64::{Dense64, Dense64View},
+    intvec::{IntVec, IntVecView},
+    iter::{BitIter, IterableSet},
+    persist::{check_params, invalid_data, load_header, to_usize, ByteReader, Persistent, View},
     rank::Rank,
-    select::Select,
-    select::Select0,
+    select::{Select, Select0, Successor},
     set::ImpliedSet,
 };
 
@@ -42,14 +44,25 @@ use crate::{
 ///     assert_eq!(s.rank(xs[i]), i);
 /// }
 /// ```
-pub struct Sparse {
+///
+/// The high bits are stored in a dense set `H` and the low bits in an
+/// [`IntVec`] with storage `W`, so a `Sparse` may own its vectors or borrow
+/// them from a saved structure (see [`SparseView`]).
+pub struct Sparse<H = Dense64, W = Vec<u64>> {
     b: usize,
     n: usize,
     d: usize,
-    hi: Dense64,
-    lo: IntVec,
+    hi: H,
+    lo: IntVec<W>,
 }
 
+/// A [`Sparse`] borrowing its high and low bits from a byte buffer, such as a
+/// memory mapped file, written by [`Persistent::save`].
+///
+/// The buffer must start on an 8 byte boundary. Queries run directly against
+/// the buffer, so many processes mapping the same file share its pages.
+pub type SparseView<'a> = Sparse<Dense64View<'a>, &'a [u64]>;
+
 impl Sparse {
     /// Create a new sparse set.
     ///
@@ -59,39 +72,182 @@ impl Sparse {
     /// The sequence `elements` must be in sorted order, and free of duplicates.
     ///
     pub fn new(b: usize, elements: &[u64]) -> Sparse {
-        let n = elements.len();
-        let d = ((1u64 << b) as f64 / (1.44 * n as f64)).log2() as usize;
-        let m = (1u64 << d) - 1;
-        let mut hi_cursor = 0;
-        let mut hi_bits = BitVec::new();
-        let mut low_bits = IntVec::new(d);
-        for x in elements {
-            let hi = *x >> d;
-            let lo = *x & m;
-            while hi_cursor <= hi {
-                hi_bits.push(true);
-                hi_cursor += 1;
-            }
-            hi_bits.push(false);
-            low_bits.push(lo);
+        let mut builder = SparseBuilder::new(b, elements.len());
+        builder.extend(elements.iter().copied());
+        builder.finish()
+    }
+
+    /// Create a new sparse set, or return an error if `b` is greater than 63,
+    /// or `elements` are not strictly increasing values less than `2**b`.
+    pub fn try_new(b: usize, elements: &[u64]) -> Result<Sparse> {
+        let mut builder = SparseBuilder::try_new(b, elements.len())?;
+        for x in elements.iter() {
+            builder.try_push(*x)?;
         }
-        let j = 1u64 << (b - d);
-        while hi_cursor < j {
-            hi_bits.push(true);
-            hi_cursor += 1;
+        Ok(builder.finish())
+    }
+}
+
+/// A builder for [`Sparse`] sets, which accepts the elements one at a time,
+/// so the elements need never be held in memory all at once.
+///
+/// The split between the high and low bits of each element is chosen from the
+/// expected number of elements, and space for the high and low bits is reserved
+/// up front, so the memory used during construction is close to the size of the
+/// finished set. Elements must be pushed in strictly increasing order.
+///
+/// #Examples
+///
+/// ```
+/// use crate::ransel::sparse::SparseBuilder;
+/// use crate::ransel::rank::Rank;
+/// use crate::ransel::select::Select;
+///
+/// let mut builder = SparseBuilder::new(32, 1000);
+/// builder.extend((0..1000u64).map(|i| i * i * 4001));
+/// let s = builder.finish();
+/// assert_eq!(s.select(10), 400100);
+/// assert_eq!(s.rank(400100), 10);
+/// ```
+pub struct SparseBuilder {
+    b: usize,
+    d: usize,
+    n: usize,
+    hi_cursor: u64,
+    hi_bits: BitVec,
+    lo: IntVec,
+    last: Option<u64>,
+    strict: bool,
+}
+
+impl SparseBuilder {
+    /// Create a builder for a set of about `expected_n` elements with at most
+    /// `b` bits each.
+    pub fn new(b: usize, expected_n: usize) -> SparseBuilder {
+        match SparseBuilder::try_new(b, expected_n) {
+            Ok(builder) => builder,
+            Err(e) => panic!("{}", e),
         }
-        hi_bits.push(true);
-        Sparse {
+    }
+
+    /// Create a builder, or return an error if `b` is greater than 63.
+    pub fn try_new(b: usize, expected_n: usize) -> Result<SparseBuilder> {
+        check_param("b", b as u64, 0, 63)?;
+        let d = ((1u64 << b) as f64 / (1.44 * expected_n as f64))
+            .log2()
+            .clamp(0.0, b as f64) as usize;
+        let buckets = 1usize << (b - d);
+        Ok(SparseBuilder {
             b,
-            n,
             d,
-            hi: Dense64::new(hi_bits.len() as u64, hi_bits.as_words()),
-            lo: low_bits,
+            n: 0,
+            hi_cursor: 0,
+            hi_bits: BitVec::with_capacity(expected_n + buckets + 1),
+            lo: IntVec::with_capacity(d, expected_n),
+            last: None,
+            strict: true,
+        })
+    }
+
+    /// Create a builder which accepts equal consecutive elements, for
+    /// non-decreasing sequences rather than sets.
+    pub(crate) fn try_new_monotone(b: usize, expected_n: usize) -> Result<SparseBuilder> {
+        Ok(SparseBuilder {
+            strict: false,
+            ..SparseBuilder::try_new(b, expected_n)?
+        })
+    }
+
+    /// Return the number of elements pushed so far.
+    pub fn len(&self) -> usize {
+        self.n
+    }
+
+    /// Return true if no elements have been pushed.
+    pub fn is_empty(&self) -> bool {
+        self.n == 0
+    }
+
+    /// Add an element to the set. It must be greater than every element
+    /// already added, and less than `2**b`.
+    pub fn push(&mut self, x: u64) {
+        if let Err(e) = self.try_push(x) {
+            panic!("{}", e);
+        }
+    }
+
+    /// Add an element to the set, or return an error if it is not greater
+    /// than every element already added, or not less than `2**b`.
+    pub fn try_push(&mut self, x: u64) -> Result<()> {
+        if self.last.is_some_and(|y| x < y || (self.strict && x == y)) {
+            return Err(Error::NotIncreasing { index: self.n });
+        }
+        check_value(x, self.b)?;
+        let hi = x >> self.d;
+        while self.hi_cursor <= hi {
+            self.hi_bits.push(true);
+            self.hi_cursor += 1;
+        }
+        self.hi_bits.push(false);
+        self.lo.push(x & ((1u64 << self.d) - 1));
+        self.n += 1;
+        self.last = Some(x);
+        Ok(())
+    }
+
+    /// Consume the builder, returning the finished set.
+    ///
+    /// The high bits have a select-0 index, since `select` on the set
+    /// calls `select_0` on the high bits.
+    pub fn finish(self) -> Sparse {
+        let s: Sparse = self.finish_with();
+        Sparse {
+            hi: s.hi.with_select_0_index(),
+            ..s
+        }
+    }
+
+    /// Consume the builder, returning the finished set with its high bits
+    /// stored in the dense representation `H`.
+    ///
+    /// #Examples
+    ///
+    /// ```
+    /// use crate::ransel::poppy::Poppy;
+    /// use crate::ransel::select::Select;
+    /// use crate::ransel::sparse::{Sparse, SparseBuilder};
+    ///
+    /// let mut builder = SparseBuilder::new(20, 3);
+    /// builder.extend([7, 70, 700000]);
+    /// let s: Sparse<Poppy> = builder.finish_with();
+    /// assert_eq!(s.select(2), 700000);
+    /// ```
+    pub fn finish_with<H: FromBitVec>(mut self) -> Sparse<H> {
+        let j = 1u64 << (self.b - self.d);
+        while self.hi_cursor < j {
+            self.hi_bits.push(true);
+            self.hi_cursor += 1;
+        }
+        self.hi_bits.push(true);
+        Sparse {
+            b: self.b,
+            n: self.n,
+            d: self.d,
+            hi: H::from_bitvec(self.hi_bits),
+            lo: self.lo,
         }
     }
 }
 
-impl ImpliedSet for Sparse {
+impl Extend<u64> for SparseBuilder {
+    fn extend<T: IntoIterator<Item = u64>>(&mut self, iter: T) {
+        for x in iter {
+            self.push(x);
+        }
+    }
+}
+
+impl<H, W: AsRef<[u64]>> ImpliedSet for Sparse<H, W> {
     fn size(&self) -> u64 {
         1u64 << self.b
     }
@@ -101,7 +257,8 @@ impl ImpliedSet for Sparse {
     }
 }
 
-impl Rank for Sparse {
+impl<H: Select, W: AsRef<[u64]>> Rank for Sparse<H, W> {
+    #[allow(clippy::unnecessary_cast)]
     fn rank(&self, value: u64) -> usize {
         if value >= (1u64 << self.b) {
             return self.count();
@@ -142,7 +299,7 @@ impl Rank for Sparse {
     }
 }
 
-impl Select for Sparse {
+impl<H: Rank + Select0, W: AsRef<[u64]>> Select for Sparse<H, W> {
     fn select(&self, index: usize) -> u64 {
         let z = self.hi.select_0(index);
         let hi = self.hi.rank(z) as u64 - 1;
@@ -150,29 +307,275 @@ impl Select for Sparse {
     }
 }
 
+impl FromSorted for Sparse {
+    fn from_sorted(size: u64, elements: &[u64]) -> Self {
+        Sparse::new(domain_bits(size).max(1), elements)
+    }
+}
+
+/// The high bits locate the bucket containing `value`, and a short scan of
+/// the low bits within the bucket usually finds the answer.
+impl<H: Select + Select0, W: AsRef<[u64]>> Successor for Sparse<H, W> {
+    fn successor(&self, value: u64) -> Option<u64> {
+        if value >= (1u64 << self.b) {
+            return None;
+        }
+        let hi = (value >> self.d) as usize;
+        let lo = value & ((1u64 << self.d) - 1);
+        let r0 = self.hi.select(hi) as usize - hi;
+        let r1 = self.hi.select(hi + 1) as usize - (hi + 1);
+        let mut r = r0;
+        while r < r1 {
+            let x = self.lo.get(r);
+            if x >= lo {
+                return Some(((hi as u64) << self.d) | x);
+            }
+            r += 1;
+        }
+        if r < self.n {
+            Some(self.select(r))
+        } else {
+            None
+        }
+    }
+
+    fn predecessor(&self, value: u64) -> Option<u64> {
+        if value >= (1u64 << self.b) {
+            return if self.n > 0 {
+                Some(self.select(self.n - 1))
+            } else {
+                None
+            };
+        }
+        let hi = (value >> self.d) as usize;
+        let lo = value & ((1u64 << self.d) - 1);
+        let r0 = self.hi.select(hi) as usize - hi;
+        let r1 = self.hi.select(hi + 1) as usize - (hi + 1);
+        let mut r = r0;
+        while r < r1 && self.lo.get(r) < lo {
+            r += 1;
+        }
+        if r > r0 {
+            Some(((hi as u64) << self.d) | self.lo.get(r - 1))
+        } else if r > 0 {
+            Some(self.select(r - 1))
+        } else {
+            None
+        }
+    }
+}
+
+/// A double ended iterator over the elements of a [`Sparse`] set, which walks
+/// the zero bits of the high bits in order.
+pub struct SparseIter<'a, W> {
+    zeros: BitIter<'a>,
+    lo: &'a IntVec<W>,
+    d: usize,
+    front: usize,
+    back: usize,
+}
+
+impl<'a, W: AsRef<[u64]>> SparseIter<'a, W> {
+    fn element(&self, i: usize, z: u64) -> u64 {
+        ((z - i as u64 - 1) << self.d) | self.lo.get(i)
+    }
+}
+
+impl<'a, W: AsRef<[u64]>> Iterator for SparseIter<'a, W> {
+    type Item = u64;
+
+    fn next(&mut self) -> Option<u64> {
+        let z = self.zeros.next()?;
+        let i = self.front;
+        self.front += 1;
+        Some(self.element(i, z))
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.zeros.size_hint()
+    }
+}
+
+impl<'a, W: AsRef<[u64]>> DoubleEndedIterator for SparseIter<'a, W> {
+    fn next_back(&mut self) -> Option<u64> {
+        let z = self.zeros.next_back()?;
+        self.back -= 1;
+        Some(self.element(self.back, z))
+    }
+}
+
+impl<'a, W: AsRef<[u64]>> ExactSizeIterator for SparseIter<'a, W> {}
+
+impl<H, W> IterableSet for Sparse<H, W>
+where
+    H: Select + Select0 + AsWords,
+    W: AsRef<[u64]>,
+{
+    type Iter<'a> = SparseIter<'a, W> where Self: 'a;
+
+    fn iter_from(&self, value: u64) -> Self::Iter<'_> {
+        let i = self.rank(value);
+        let from = if i < self.n { self.hi.select_0(i) } else { 0 };
+        SparseIter {
+            zeros: BitIter::new(self.hi.as_words(), self.hi.size(), from, self.n - i, false),
+            lo: &self.lo,
+            d: self.d,
+            front: i,
+            back: self.n,
+        }
+    }
+}
+
+impl<H: ImpliedSet + Rank, W: AsRef<[u64]>> Sparse<H, W> {
+    /// Check that the parameters and the high and low bits of a loaded set
+    /// agree: `d <= b < 64`, one low part of `d` bits per element, and high
+    /// bits starting and ending with a 1, with one 0 per element and one 1 per
+    /// bucket plus a final 1, so that queries stay in bounds.
+    fn check_parts(&self) -> std::io::Result<()> {
+        let inconsistent = || Err(invalid_data(String::from("inconsistent Sparse structure")));
+        if self.b > 63 || self.d > self.b || self.lo.width() != self.d || self.lo.len() != self.n {
+            return inconsistent();
+        }
+        let ones = (1u64 << (self.b - self.d)) + 1;
+        if self.hi.count() as u64 != ones
+            || self.hi.size().checked_sub(ones) != Some(self.n as u64)
+            || !self.hi.contains(0)
+            || !self.hi.contains(self.hi.size() - 1)
+        {
+            return inconsistent();
+        }
+        Ok(())
+    }
+}
+
+impl Sparse {
+    /// Check that the elements of a loaded set are strictly increasing, or
+    /// non-decreasing if `strict` is false, which reads every element.
+    fn check_order(&self, strict: bool) -> std::io::Result<()> {
+        let mut last: Option<u64> = None;
+        for (index, x) in self.iter().enumerate() {
+            if last.is_some_and(|y| x < y || (strict && x == y)) {
+                return Err(invalid_data(Error::NotIncreasing { index }.to_string()));
+            }
+            last = Some(x);
+        }
+        Ok(())
+    }
+
+    /// Read the parameters, high bits and low bits of a saved set, checking
+    /// that they agree but not the order of the elements.
+    fn load_parts<Source>(params: &[u64], source: &mut Source) -> std::io::Result<Sparse>
+    where
+        Source: std::io::Read,
+    {
+        check_params(&Self::TYPE_TAG, params, 3)?;
+        let b: usize = to_usize(params[0])?;
+        let n: usize = to_usize(params[1])?;
+        let d: usize = to_usize(params[2])?;
+        let hi: Dense64 = *(Dense64::load(source)?);
+        let lo: IntVec = *(IntVec::load(source)?);
+        let s = Sparse { b, n, d, hi, lo };
+        s.check_parts()?;
+        Ok(s)
+    }
+
+    /// Read a saved non-decreasing sequence, as built by
+    /// [`SparseBuilder::try_new_monotone`].
+    pub(crate) fn load_monotone<Source>(source: &mut Source) -> std::io::Result<Sparse>
+    where
+        Source: std::io::Read,
+    {
+        let params = load_header(source, &Self::TYPE_TAG)?;
+        let s = Sparse::load_parts(&params, source)?;
+        s.check_order(false)?;
+        Ok(s)
+    }
+}
+
 impl Persistent for Sparse {
-    fn save<Sink>(&self, sink: &mut Sink) -> std::io::Result<()>
+    const TYPE_TAG: [u8; 4] = *b"SPRS";
+
+    fn params(&self) -> Vec<u64> {
+        vec![self.b as u64, self.n as u64, self.d as u64]
+    }
+
+    fn save_body<Sink>(&self, sink: &mut Sink) -> std::io::Result<()>
     where
         Sink: std::io::Write,
     {
-        sink.write_all(&self.b.to_ne_bytes())?;
-        sink.write_all(&self.n.to_ne_bytes())?;
-        sink.write_all(&self.d.to_ne_bytes())?;
         self.hi.save(sink)?;
         self.lo.save(sink)?;
         Ok(())
     }
 
-    fn load<Source>(source: &mut Source) -> std::io::Result<Box<Self>>
+    fn load_body<Source>(params: &[u64], source: &mut Source) -> std::io::Result<Self>
     where
         Source: std::io::Read,
     {
-        let b: usize = load_usize(source)?;
-        let n: usize = load_usize(source)?;
-        let d: usize = load_usize(source)?;
-        let hi: Dense64 = *(Dense64::load(source)?);
-        let lo: IntVec = *(IntVec::load(source)?);
-        Ok(Box::new(Sparse { b, n, d, hi, lo }))
+        let s = Sparse::load_parts(params, source)?;
+        s.check_order(true)?;
+        Ok(s)
+    }
+}
+
+impl<'a> View<'a> for SparseView<'a> {
+    const TYPE_TAG: [u8; 4] = <Sparse as Persistent>::TYPE_TAG;
+
+    fn view_body(params: &[u64], source: &mut ByteReader<'a>) -> std::io::Result<Self> {
+        check_params(&Self::TYPE_TAG, params, 3)?;
+        let b: usize = to_usize(params[0])?;
+        let n: usize = to_usize(params[1])?;
+        let d: usize = to_usize(params[2])?;
+        let hi = Dense64View::view_from(source)?;
+        let lo = IntVecView::view_from(source)?;
+        let s = Sparse { b, n, d, hi, lo };
+        s.check_parts()?;
+        Ok(s)
+    }
+}
+
+#[cfg(feature = "serde")]
+impl serde::Serialize for Sparse {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> std::result::Result<S::Ok, S::Error> {
+        use serde::ser::SerializeStruct;
+        let mut state = serializer.serialize_struct("Sparse", 5)?;
+        state.serialize_field("b", &(self.b as u64))?;
+        state.serialize_field("n", &(self.n as u64))?;
+        state.serialize_field("d", &(self.d as u64))?;
+        state.serialize_field("hi", &self.hi)?;
+        state.serialize_field("lo", &self.lo)?;
+        state.end()
+    }
+}
+
+#[cfg(feature = "serde")]
+#[derive(serde::Deserialize)]
+#[serde(rename = "Sparse")]
+struct SparseData {
+    b: u64,
+    n: u64,
+    d: u64,
+    hi: Dense64,
+    lo: IntVec,
+}
+
+/// Deserializing checks that the high bits hold one 0 per element and one 1
+/// per bucket, and that the elements they decode to are strictly increasing.
+#[cfg(feature = "serde")]
+impl<'de> serde::Deserialize<'de> for Sparse {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> std::result::Result<Self, D::Error> {
+        use serde::de::Error as _;
+        let data = SparseData::deserialize(deserializer)?;
+        let s = Sparse {
+            b: usize::try_from(data.b).map_err(D::Error::custom)?,
+            n: usize::try_from(data.n).map_err(D::Error::custom)?,
+            d: usize::try_from(data.d).map_err(D::Error::custom)?,
+            hi: data.hi,
+            lo: data.lo,
+        };
+        s.check_parts().map_err(D::Error::custom)?;
+        s.check_order(true).map_err(D::Error::custom)?;
+        Ok(s)
     }
 }
 
@@ -209,6 +612,7 @@ mod tests {
         }
     }
 
+    #[allow(clippy::map_clone)]
     fn make_set(b: usize, n: usize) -> Vec<u64> {
         let m = (1u64 << b) - 1;
         let mut rng = MiniRng::new(0xfbdb8b2bcc6674b8u64);
@@ -250,8 +654,7 @@ mod tests {
         assert_eq!(s.hi.count(), 2049);
         assert_eq!(s.hi.size(), 2049 + n as u64);
         assert_eq!(s.lo.len(), n);
-        for i in 0..xs.len() {
-            let x = xs[i];
+        for (i, &x) in xs.iter().enumerate() {
             let r = s.rank(x);
             assert_eq!(r, i);
         }
@@ -269,14 +672,166 @@ mod tests {
         assert_eq!(s.hi.count(), 2049);
         assert_eq!(s.hi.size(), 2049 + n as u64);
         assert_eq!(s.lo.len(), n);
-        for i in 0..xs.len() {
-            let x = xs[i];
+        for (i, &x) in xs.iter().enumerate() {
             let y = s.select(i);
             assert_eq!(y, x);
         }
     }
 
     #[test]
+    fn test_sparse_load_and_save_1() {
+        let b: usize = 20;
+        let n: usize = 1024;
+        let xs = make_set(b, n);
+        let s = Sparse::new(b, &xs);
+
+        let mut bytes: Vec<u8> = Vec::new();
+        s.save(&mut bytes).expect("save failed");
+        let t = Sparse::load(&mut bytes.as_slice()).expect("load failed");
+        assert_eq!(t.size(), 1u64 << b);
+        assert_eq!(t.count(), n);
+        for (i, &x) in xs.iter().enumerate() {
+            assert_eq!(t.rank(x), i);
+            assert_eq!(t.select(i), x);
+        }
+
+        assert!(Dense64::load(&mut bytes.as_slice()).is_err());
+    }
+
+    #[test]
+    fn test_sparse_view_1() {
+        let b: usize = 20;
+        let n: usize = 1024;
+        let xs = make_set(b, n);
+        let s = Sparse::new(b, &xs);
+
+        let mut bytes: Vec<u8> = Vec::new();
+        s.save(&mut bytes).expect("save failed");
+        let mut words: Vec<u64> = vec![0; bytes.len() / 8];
+        for i in 0..words.len() {
+            let mut w: [u8; 8] = [0; 8];
+            w.copy_from_slice(&bytes[8 * i..8 * i + 8]);
+            words[i] = u64::from_le_bytes(w);
+        }
+        let aligned: &[u8] =
+            unsafe { std::slice::from_raw_parts(words.as_ptr() as *const u8, bytes.len()) };
+
+        let v = SparseView::view(aligned).expect("view failed");
+        assert_eq!(v.size(), 1u64 << b);
+        assert_eq!(v.count(), n);
+        for (i, &x) in xs.iter().enumerate() {
+            assert_eq!(v.rank(x), i);
+            assert_eq!(v.select(i), x);
+        }
+
+        assert!(SparseView::view(&aligned[1..]).is_err());
+        assert!(SparseView::view(&aligned[..aligned.len() - 8]).is_err());
+        assert!(Dense64View::view(aligned).is_err());
+    }
+
+    #[test]
+    fn test_sparse_iter_1() {
+        let b: usize = 20;
+        let n: usize = 1024;
+        let xs = make_set(b, n);
+        let s = Sparse::new(b, &xs);
+        assert_eq!(s.iter().collect::<Vec<u64>>(), xs);
+        assert_eq!(s.iter().len(), n);
+        let mut ys: Vec<u64> = s.iter().rev().collect();
+        ys.reverse();
+        assert_eq!(ys, xs);
+        for i in (0..n).step_by(37) {
+            let x = xs[i];
+            assert_eq!(s.iter_from(x).collect::<Vec<u64>>(), xs[i..]);
+            assert_eq!(s.iter_from(x + 1).collect::<Vec<u64>>(), xs[i + 1..]);
+        }
+        assert_eq!(s.iter_from(1u64 << b).next(), None);
+    }
+
+    #[test]
+    fn test_sparse_successor_1() {
+        let b: usize = 16;
+        let n: usize = 3000;
+        let xs = make_set(b, n);
+        let s = Sparse::new(b, &xs);
+        for x in 0..(1u64 << b) + 2 {
+            let r = xs.partition_point(|y| *y < x);
+            assert_eq!(s.successor(x), xs.get(r).copied());
+            assert_eq!(s.predecessor(x), r.checked_sub(1).map(|i| xs[i]));
+            assert_eq!(s.prev_one_before(x), s.predecessor(x));
+        }
+        assert_eq!(s.next_one_after(xs[0]), Some(xs[1]));
+        assert_eq!(s.next_one_after(xs[n - 1]), None);
+    }
+
+    #[test]
+    fn test_sparse_builder_1() {
+        let b: usize = 24;
+        let n: usize = 5000;
+        let xs = make_set(b, n);
+        // The expected count only affects the layout, not the contents.
+        for expected in [0, n / 10, n, 10 * n] {
+            let mut builder = SparseBuilder::new(b, expected);
+            builder.extend(xs.iter().copied());
+            assert_eq!(builder.len(), n);
+            let s = builder.finish();
+            assert_eq!(s.count(), n);
+            assert_eq!(s.iter().collect::<Vec<u64>>(), xs);
+            for (i, &x) in xs.iter().enumerate() {
+                assert_eq!(s.rank(x), i);
+            }
+        }
+    }
+
+    #[test]
+    #[should_panic(expected = "not greater than")]
+    fn test_sparse_builder_order_1() {
+        let mut builder = SparseBuilder::new(10, 3);
+        builder.push(5);
+        builder.push(5);
+    }
+
+    #[test]
+    fn test_sparse_try_new_1() {
+        assert!(Sparse::try_new(10, &[1, 5, 1023]).is_ok());
+        assert_eq!(Sparse::try_new(10, &[1, 5, 5]).err(), Some(Error::NotIncreasing { index: 2 }));
+        assert_eq!(Sparse::try_new(10, &[1, 1024]).err(), Some(Error::TooWide { value: 1024, width: 10 }));
+        assert!(Sparse::try_new(64, &[]).is_err());
+        let s = Sparse::try_new(10, &[1, 5, 1023]).unwrap();
+        assert_eq!(s.try_select(2), Ok(1023));
+        assert_eq!(s.try_select(3), Err(Error::IndexOutOfRange { index: 3, len: 3 }));
+    }
+
+    #[test]
+    fn test_sparse_poppy_1() {
+        use crate::poppy::Poppy;
+
+        let b: usize = 30;
+        let n: usize = 10_000;
+        let xs = make_set(b, n);
+        let mut builder = SparseBuilder::new(b, n);
+        builder.extend(xs.iter().copied());
+        let s: Sparse<Poppy> = builder.finish_with();
+        for (i, &x) in xs.iter().enumerate() {
+            assert_eq!(s.select(i), x);
+            assert_eq!(s.rank(x), i);
+        }
+        assert_eq!(s.iter().collect::<Vec<u64>>(), xs);
+    }
+
+    #[test]
+    fn test_sparse_dense_1() {
+        // When every element is present, there are no low bits.
+        let xs: Vec<u64> = (0..1024).collect();
+        let s = Sparse::new(10, &xs);
+        for (i, &x) in xs.iter().enumerate() {
+            assert_eq!(s.select(i), x);
+            assert_eq!(s.rank(x), i);
+        }
+    }
+
+    #[test]
+    #[allow(clippy::bool_assert_comparison)]
     fn test_big_sparse() {
         let b: usize = 50;
         let mut xs: Vec<u64> = Vec::new();
@@ -292,9 +847,9 @@ mod tests {
             }
         }
         let s = Sparse::new(b, &xs);
-        for i in 0..xs.len() {
-            assert_eq!(s.rank(xs[i]), i);
-            assert_eq!(s.select(i), xs[i]);
+        for (i, &x) in xs.iter().enumerate() {
+            assert_eq!(s.rank(x), i);
+            assert_eq!(s.select(i), x);
         }
         {
             let (r, c) = s.access_and_rank(0x3FFBC2C2BC000u64);
@@ -307,4 +862,70 @@ mod tests {
             assert_eq!(r, s.count());
         }
     }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn test_sparse_serde_1() {
+        let b: usize = 20;
+        let xs = make_set(b, 1024);
+        let s = Sparse::new(b, &xs);
+        let json = serde_json::to_string(&s).expect("serialize failed");
+        let t: Sparse = serde_json::from_str(&json).expect("deserialize failed");
+        assert_eq!(t.iter().collect::<Vec<u64>>(), xs);
+        assert_eq!(t.select(1000), xs[1000]);
+
+        // Swapping the low bits of two elements in a bucket breaks the order.
+        let u = Sparse::new(10, &[1, 2, 500]);
+        let mut value = serde_json::to_value(&u).unwrap();
+        let mut lo = IntVec::new(u.d);
+        lo.push(2);
+        lo.push(1);
+        lo.push(u.lo.get(2));
+        value["lo"] = serde_json::to_value(&lo).unwrap();
+        assert!(serde_json::from_value::<Sparse>(value).is_err());
+
+        let mut value: serde_json::Value = serde_json::from_str(&json).unwrap();
+        value["d"] = serde_json::Value::from(s.d as u64 - 1);
+        assert!(serde_json::from_value::<Sparse>(value).is_err());
+    }
+
+    #[test]
+    fn test_sparse_load_corrupt_1() {
+        let s = Sparse::new(10, &[1, 2, 500]);
+        let mut lo = IntVec::new(s.d);
+        for x in [2, 1, s.lo.get(2)] {
+            lo.push(x);
+        }
+        let corrupt = [
+            Sparse { d: s.d - 1, ..Sparse::new(10, &[1, 2, 500]) },
+            Sparse { n: 2, ..Sparse::new(10, &[1, 2, 500]) },
+            Sparse { b: 11, ..Sparse::new(10, &[1, 2, 500]) },
+            Sparse { lo, ..Sparse::new(10, &[1, 2, 500]) },
+        ];
+        for t in corrupt.iter() {
+            let mut bytes: Vec<u8> = Vec::new();
+            t.save(&mut bytes).expect("save failed");
+            assert!(Sparse::load(&mut bytes.as_slice()).is_err());
+        }
+    }
+
+    #[test]
+    fn test_sparse_view_corrupt_1() {
+        let corrupt = [
+            Sparse { d: 3, ..Sparse::new(10, &[1, 2, 500]) },
+            Sparse { n: 4, ..Sparse::new(10, &[1, 2, 500]) },
+            Sparse { hi: Dense64::from_positions(200, &[3, 64, 199]), ..Sparse::new(10, &[1, 2, 500]) },
+        ];
+        for t in corrupt.iter() {
+            let mut bytes: Vec<u8> = Vec::new();
+            t.save(&mut bytes).expect("save failed");
+            let words: Vec<u64> = bytes
+                .chunks(8)
+                .map(|w| u64::from_le_bytes(w.try_into().unwrap()))
+                .collect();
+            let aligned: &[u8] =
+                unsafe { std::slice::from_raw_parts(words.as_ptr() as *const u8, bytes.len()) };
+            assert!(SparseView::view(aligned).is_err());
+        }
+    }
 }
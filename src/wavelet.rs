@@ -0,0 +1,366 @@
+//! A wavelet matrix over sequences of integers, based on the Claude, Navarro & Ordóñez 2015 paper:
+//!
+//! > Claude, F., Navarro, G. and Ordóñez, A., 2015. The wavelet matrix: An efficient wavelet
+//! > tree for large alphabets. Information Systems, 47, pp. 15-32.
+
+use std::collections::BinaryHeap;
+
+use crate::{
+    bitvec::BitVec,
+    dense64::Dense64,
+    intvec::IntVec,
+    persist::{check_params, invalid_data, to_usize, Persistent},
+    rank::Rank,
+    select::{Select, Select0},
+    set::ImpliedSet,
+};
+
+/// A wavelet matrix supporting `access`, `rank` and `select` of symbols in a
+/// sequence of `b` bit integers, along with range queries over the values.
+///
+/// The matrix has one level for each bit of the symbols, most significant first.
+/// Each level is a [`Dense64`] holding that bit of every symbol, with the symbols
+/// stably partitioned by the bits of the levels above, so each query takes
+/// `b` rank or select operations.
+///
+/// #Examples
+///
+/// ```
+/// use crate::ransel::wavelet::WaveletMatrix;
+///
+/// let xs: Vec<u64> = vec![3, 1, 4, 1, 5, 9, 2, 6, 5, 3, 5];
+/// let w = WaveletMatrix::from_slice(4, &xs);
+/// assert_eq!(w.access(4), 5);
+/// assert_eq!(w.rank(5, 9), 2);
+/// assert_eq!(w.select(5, 2), Some(10));
+/// assert_eq!(w.range_quantile(0, 5, 2), 3);
+/// assert_eq!(w.range_count(0, 11, 2, 5), 4);
+/// assert_eq!(w.top_k(0, 11, 1), vec![(5, 3)]);
+/// ```
+pub struct WaveletMatrix {
+    n: usize,
+    b: usize,
+    levels: Vec<Dense64>,
+    zeros: Vec<usize>,
+}
+
+impl WaveletMatrix {
+    /// Create a wavelet matrix over the values in an integer vector.
+    pub fn new(values: &IntVec) -> WaveletMatrix {
+        let xs: Vec<u64> = (0..values.len()).map(|i| values.get(i)).collect();
+        WaveletMatrix::from_slice(values.width(), &xs)
+    }
+
+    /// Create a wavelet matrix over a slice of values of at most `b` bits.
+    pub fn from_slice(b: usize, values: &[u64]) -> WaveletMatrix {
+        assert!(b <= 64);
+        let n = values.len();
+        let mut cur: Vec<u64> = Vec::from(values);
+        let mut levels: Vec<Dense64> = Vec::with_capacity(b);
+        let mut zeros: Vec<usize> = Vec::with_capacity(b);
+        for l in 0..b {
+            let shift = b - 1 - l;
+            let mut bits = BitVec::new();
+            let mut lefts: Vec<u64> = Vec::with_capacity(n);
+            let mut rights: Vec<u64> = Vec::new();
+            for x in cur.iter() {
+                assert!(b == 64 || *x >> b == 0);
+                let bit = (*x >> shift) & 1 == 1;
+                bits.push(bit);
+                if bit {
+                    rights.push(*x);
+                } else {
+                    lefts.push(*x);
+                }
+            }
+            zeros.push(lefts.len());
+            lefts.append(&mut rights);
+            cur = lefts;
+            levels.push(Dense64::from_bitvec(bits).with_select_0_index());
+        }
+        WaveletMatrix { n, b, levels, zeros }
+    }
+
+    /// Return the length of the sequence.
+    pub fn len(&self) -> usize {
+        self.n
+    }
+
+    /// Return true if the sequence is empty.
+    pub fn is_empty(&self) -> bool {
+        self.n == 0
+    }
+
+    /// Return the number of bits in each symbol.
+    pub fn width(&self) -> usize {
+        self.b
+    }
+
+    fn bit(&self, c: u64, l: usize) -> bool {
+        (c >> (self.b - 1 - l)) & 1 == 1
+    }
+
+    /// Map the range `[lo, hi)` at level `l` to the child range for `bit`.
+    fn child(&self, l: usize, lo: usize, hi: usize, bit: bool) -> (usize, usize) {
+        let level = &self.levels[l];
+        if bit {
+            let z = self.zeros[l];
+            (z + level.rank(lo as u64), z + level.rank(hi as u64))
+        } else {
+            (level.rank_0(lo as u64), level.rank_0(hi as u64))
+        }
+    }
+
+    /// Return the symbol at position `i`.
+    pub fn access(&self, i: usize) -> u64 {
+        assert!(i < self.n);
+        let mut i = i;
+        let mut c = 0;
+        for l in 0..self.b {
+            let level = &self.levels[l];
+            let (r, bit) = level.access_and_rank(i as u64);
+            c = (c << 1) | bit as u64;
+            i = if bit { self.zeros[l] + r } else { i - r };
+        }
+        c
+    }
+
+    /// Return the number of occurrences of the symbol `c` in positions `[0, i)`.
+    pub fn rank(&self, c: u64, i: usize) -> usize {
+        assert!(i <= self.n);
+        if self.b < 64 && c >> self.b != 0 {
+            return 0;
+        }
+        let (mut lo, mut hi) = (0, i);
+        for l in 0..self.b {
+            (lo, hi) = self.child(l, lo, hi, self.bit(c, l));
+        }
+        hi - lo
+    }
+
+    /// Return the position of the `j`-th occurrence (counting from 0) of the symbol `c`,
+    /// or `None` if there are not that many occurrences.
+    pub fn select(&self, c: u64, j: usize) -> Option<usize> {
+        if self.b < 64 && c >> self.b != 0 {
+            return None;
+        }
+        let (mut lo, mut hi) = (0, self.n);
+        for l in 0..self.b {
+            (lo, hi) = self.child(l, lo, hi, self.bit(c, l));
+        }
+        if lo + j >= hi {
+            return None;
+        }
+        let mut p = lo + j;
+        for l in (0..self.b).rev() {
+            let level = &self.levels[l];
+            p = if self.bit(c, l) {
+                level.select(p - self.zeros[l]) as usize
+            } else {
+                level.select_0(p) as usize
+            };
+        }
+        Some(p)
+    }
+
+    /// Return the `k`-th smallest value (counting from 0) among positions `[lo, hi)`.
+    pub fn range_quantile(&self, lo: usize, hi: usize, k: usize) -> u64 {
+        assert!(lo <= hi && hi <= self.n && k < hi - lo);
+        let (mut lo, mut hi, mut k) = (lo, hi, k);
+        let mut c = 0;
+        for l in 0..self.b {
+            let level = &self.levels[l];
+            let z = level.rank_0(hi as u64) - level.rank_0(lo as u64);
+            let bit = k >= z;
+            if bit {
+                k -= z;
+            }
+            c = (c << 1) | bit as u64;
+            (lo, hi) = self.child(l, lo, hi, bit);
+        }
+        c
+    }
+
+    /// Return the number of values strictly less than `v` among positions `[lo, hi)`.
+    fn count_less(&self, lo: usize, hi: usize, v: u64) -> usize {
+        if self.b < 64 && v >> self.b != 0 {
+            return hi - lo;
+        }
+        let (mut lo, mut hi) = (lo, hi);
+        let mut count = 0;
+        for l in 0..self.b {
+            let bit = self.bit(v, l);
+            if bit {
+                let level = &self.levels[l];
+                count += level.rank_0(hi as u64) - level.rank_0(lo as u64);
+            }
+            (lo, hi) = self.child(l, lo, hi, bit);
+        }
+        count
+    }
+
+    /// Return the number of values in `[v_lo, v_hi)` among positions `[lo, hi)`.
+    pub fn range_count(&self, lo: usize, hi: usize, v_lo: u64, v_hi: u64) -> usize {
+        assert!(lo <= hi && hi <= self.n);
+        if v_lo >= v_hi {
+            return 0;
+        }
+        self.count_less(lo, hi, v_hi) - self.count_less(lo, hi, v_lo)
+    }
+
+    /// Return up to `k` of the most frequent values among positions `[lo, hi)`,
+    /// with their frequencies, most frequent first. Ties are broken in favour of
+    /// smaller values.
+    pub fn top_k(&self, lo: usize, hi: usize, k: usize) -> Vec<(u64, usize)> {
+        assert!(lo <= hi && hi <= self.n);
+        // Entries are ordered by range size, then by the smallest value in the range.
+        let mut heap: BinaryHeap<(usize, std::cmp::Reverse<u64>, usize, usize)> = BinaryHeap::new();
+        let mut res: Vec<(u64, usize)> = Vec::new();
+        if lo < hi {
+            heap.push((hi - lo, std::cmp::Reverse(0), 0, lo));
+        }
+        while let Some((size, std::cmp::Reverse(prefix), l, lo)) = heap.pop() {
+            if res.len() == k {
+                break;
+            }
+            if l == self.b {
+                res.push((prefix, size));
+                continue;
+            }
+            let hi = lo + size;
+            for bit in [false, true] {
+                let (c_lo, c_hi) = self.child(l, lo, hi, bit);
+                if c_lo < c_hi {
+                    let c = prefix | ((bit as u64) << (self.b - 1 - l));
+                    heap.push((c_hi - c_lo, std::cmp::Reverse(c), l + 1, c_lo));
+                }
+            }
+        }
+        res
+    }
+}
+
+impl Persistent for WaveletMatrix {
+    const TYPE_TAG: [u8; 4] = *b"WMAT";
+
+    fn params(&self) -> Vec<u64> {
+        vec![self.n as u64, self.b as u64]
+    }
+
+    fn save_body<Sink>(&self, sink: &mut Sink) -> std::io::Result<()>
+    where
+        Sink: std::io::Write,
+    {
+        for level in self.levels.iter() {
+            level.save(sink)?;
+        }
+        Ok(())
+    }
+
+    fn load_body<Source>(params: &[u64], source: &mut Source) -> std::io::Result<Self>
+    where
+        Source: std::io::Read,
+    {
+        check_params(&Self::TYPE_TAG, params, 2)?;
+        let n: usize = to_usize(params[0])?;
+        let b: usize = to_usize(params[1])?;
+        if b > 64 {
+            return Err(invalid_data(format!("invalid wavelet matrix width {}", b)));
+        }
+        let mut levels: Vec<Dense64> = Vec::with_capacity(b);
+        let mut zeros: Vec<usize> = Vec::with_capacity(b);
+        for _l in 0..b {
+            let level: Dense64 = (*Dense64::load(source)?).with_select_0_index();
+            if level.size() != n as u64 {
+                return Err(invalid_data(String::from("inconsistent wavelet matrix level")));
+            }
+            zeros.push(n - level.count());
+            levels.push(level);
+        }
+        Ok(WaveletMatrix { n, b, levels, zeros })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use random::Source;
+
+    fn make_seq(b: usize, n: usize) -> Vec<u64> {
+        let mut rng = random::default(19);
+        let m = (1u64 << b) - 1;
+        // Skew the distribution so some symbols are much more frequent than others.
+        (0..n)
+            .map(|_| {
+                let x = rng.read_u64();
+                (x & m) >> ((x >> 60) % b as u64)
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_access_rank_select_1() {
+        let b = 6;
+        let xs = make_seq(b, 2000);
+        let mut v = IntVec::new(b);
+        for x in xs.iter() {
+            v.push(*x);
+        }
+        let w = WaveletMatrix::new(&v);
+        assert_eq!(w.len(), xs.len());
+        let mut counts: Vec<usize> = vec![0; 1 << b];
+        for (i, &c) in xs.iter().enumerate() {
+            assert_eq!(w.access(i), c);
+            assert_eq!(w.rank(c, i), counts[c as usize]);
+            assert_eq!(w.select(c, counts[c as usize]), Some(i));
+            counts[c as usize] += 1;
+        }
+        for c in 0..(1u64 << b) {
+            assert_eq!(w.rank(c, xs.len()), counts[c as usize]);
+            assert_eq!(w.select(c, counts[c as usize]), None);
+        }
+        assert_eq!(w.rank(1 << b, xs.len()), 0);
+    }
+
+    #[test]
+    fn test_range_queries_1() {
+        let b = 5;
+        let xs = make_seq(b, 500);
+        let w = WaveletMatrix::from_slice(b, &xs);
+        for (lo, hi) in [(0, 500), (17, 18), (100, 250), (499, 500), (3, 3)] {
+            let mut ys: Vec<u64> = Vec::from(&xs[lo..hi]);
+            ys.sort();
+            for (k, &y) in ys.iter().enumerate() {
+                assert_eq!(w.range_quantile(lo, hi, k), y);
+            }
+            for (v_lo, v_hi) in [(0, 32), (3, 9), (10, 11), (31, 100), (7, 7)] {
+                let expected = ys.iter().filter(|y| v_lo <= **y && **y < v_hi).count();
+                assert_eq!(w.range_count(lo, hi, v_lo, v_hi), expected);
+            }
+            let mut freqs: Vec<(u64, usize)> = Vec::new();
+            for y in ys.iter() {
+                match freqs.last_mut() {
+                    Some((c, f)) if *c == *y => *f += 1,
+                    _ => freqs.push((*y, 1)),
+                }
+            }
+            freqs.sort_by(|a, b| b.1.cmp(&a.1).then(a.0.cmp(&b.0)));
+            freqs.truncate(4);
+            assert_eq!(w.top_k(lo, hi, 4), freqs);
+        }
+    }
+
+    #[test]
+    fn test_load_and_save_1() {
+        let b = 7;
+        let xs = make_seq(b, 300);
+        let w = WaveletMatrix::from_slice(b, &xs);
+        let mut bytes: Vec<u8> = Vec::new();
+        w.save(&mut bytes).expect("save failed");
+        let u = WaveletMatrix::load(&mut bytes.as_slice()).expect("load failed");
+        for (i, &x) in xs.iter().enumerate() {
+            assert_eq!(u.access(i), x);
+        }
+    }
+}
@@ -1,4 +1,7 @@
-use bitintr::Pdep;
+//! Operations on individual 64 bit words.
+
+#[cfg(target_arch = "x86_64")]
+use std::sync::OnceLock;
 
 pub fn rank64(x: u64, pos: u64) -> u64 {
     if pos < 64 {
@@ -8,12 +11,129 @@ pub fn rank64(x: u64, pos: u64) -> u64 {
     }
 }
 
+/// Extract `width` bits starting at bit `pos` of a sequence of words. Bits past
+/// the end of `words` are treated as zero.
+pub fn get_bits(words: &[u64], pos: usize, width: usize) -> u64 {
+    if width == 0 {
+        return 0;
+    }
+    let w = pos >> 6;
+    let b = pos & 63;
+    let mut x = words.get(w).copied().unwrap_or(0) >> b;
+    if b + width > 64 {
+        x |= words.get(w + 1).copied().unwrap_or(0) << (64 - b);
+    }
+    if width < 64 {
+        x & ((1u64 << width) - 1)
+    } else {
+        x
+    }
+}
+
+/// Return true if every bit of `words` at or beyond bit `size` is zero.
+pub fn clear_from(words: &[u64], size: u64) -> bool {
+    let w = (size / 64) as usize;
+    words
+        .iter()
+        .skip(w)
+        .enumerate()
+        .all(|(i, x)| if i == 0 { x >> (size & 63) == 0 } else { *x == 0 })
+}
+
+/// Return the position of the `idx`-th (counting from 0) one bit of `x`.
+///
+/// `idx` must be less than the number of one bits in `x`. On x86_64 processors
+/// with a fast PDEP instruction this is a single PDEP, otherwise it uses a
+/// broadword search for the byte containing the bit, followed by a table lookup.
+#[inline]
 pub fn select64(x: u64, idx: usize) -> u64 {
-    if x == 18446744073709551615u64 {
-        // pdep appears to fail on 111...111
-        return idx as u64;
+    std::debug_assert!(idx < x.count_ones() as usize);
+    #[cfg(target_arch = "x86_64")]
+    {
+        if fast_pdep() {
+            // Safety: fast_pdep() is only true if the processor supports BMI2.
+            return unsafe { select64_pdep(x, idx) };
+        }
+    }
+    select64_broadword(x, idx)
+}
+
+/// Return true if the processor has a PDEP instruction that is worth using.
+///
+/// AMD processors before Zen 3 (family 0x19) implement PDEP in microcode,
+/// taking hundreds of cycles, so they use the broadword fallback too.
+#[cfg(target_arch = "x86_64")]
+fn fast_pdep() -> bool {
+    static FAST_PDEP: OnceLock<bool> = OnceLock::new();
+    *FAST_PDEP.get_or_init(|| {
+        use std::arch::x86_64::__cpuid;
+
+        if !std::is_x86_feature_detected!("bmi2") {
+            return false;
+        }
+        let vendor = __cpuid(0);
+        let amd = vendor.ebx == u32::from_le_bytes(*b"Auth")
+            && vendor.edx == u32::from_le_bytes(*b"enti")
+            && vendor.ecx == u32::from_le_bytes(*b"cAMD");
+        if !amd {
+            return true;
+        }
+        let eax = __cpuid(1).eax;
+        let base = (eax >> 8) & 0xf;
+        let family = if base == 0xf { base + ((eax >> 20) & 0xff) } else { base };
+        family >= 0x19
+    })
+}
+
+#[cfg(target_arch = "x86_64")]
+#[target_feature(enable = "bmi2")]
+unsafe fn select64_pdep(x: u64, idx: usize) -> u64 {
+    std::arch::x86_64::_pdep_u64(1u64 << idx, x).trailing_zeros() as u64
+}
+
+const ONES_STEP_8: u64 = 0x0101_0101_0101_0101;
+const MSBS_STEP_8: u64 = 0x8080_8080_8080_8080;
+
+const fn select_in_byte_table() -> [[u8; 256]; 8] {
+    let mut t = [[8u8; 256]; 8];
+    let mut b = 0;
+    while b < 256 {
+        let mut j = 0;
+        let mut i = 0;
+        while i < 8 {
+            if (b >> i) & 1 == 1 {
+                t[j][b] = i as u8;
+                j += 1;
+            }
+            i += 1;
+        }
+        b += 1;
     }
-    (1u64 << idx).pdep(x).trailing_zeros() as u64
+    t
+}
+
+/// `SELECT_IN_BYTE[j][b]` is the position of the `j`-th one bit of `b`.
+static SELECT_IN_BYTE: [[u8; 256]; 8] = select_in_byte_table();
+
+/// A portable `select64`, after Vigna's broadword select:
+///
+/// > Vigna, S., 2008. Broadword implementation of rank/select queries. In International
+/// > Workshop on Experimental and Efficient Algorithms (pp. 154-168). Springer.
+fn select64_broadword(x: u64, idx: usize) -> u64 {
+    // Population counts of each byte.
+    let mut s = x - ((x >> 1) & 0x5555_5555_5555_5555);
+    s = (s & 0x3333_3333_3333_3333) + ((s >> 2) & 0x3333_3333_3333_3333);
+    s = (s + (s >> 4)) & 0x0f0f_0f0f_0f0f_0f0f;
+    // Byte i holds the number of one bits in bytes 0..=i.
+    let prefix = s.wrapping_mul(ONES_STEP_8);
+    // The high bit of byte i is set if prefix[i] <= idx; the bytes with
+    // this property precede the byte containing the bit we want.
+    let k = (idx as u64).wrapping_mul(ONES_STEP_8);
+    let le = ((k | MSBS_STEP_8) - prefix) & MSBS_STEP_8;
+    let byte = le.count_ones() as u64;
+    let before = if byte == 0 { 0 } else { (prefix >> (8 * byte - 8)) & 0xff };
+    let b = (x >> (8 * byte)) & 0xff;
+    8 * byte + SELECT_IN_BYTE[idx - before as usize][b as usize] as u64
 }
 
 #[cfg(test)]
@@ -36,6 +156,19 @@ mod tests {
         assert_eq!(rank64(x, 64), 48);
     }
 
+    #[test]
+    fn test_get_bits_1() {
+        let xs: Vec<u64> = vec![0xdeadbeefdeadbeefu64, 0x0123456789abcdefu64];
+        assert_eq!(get_bits(&xs, 0, 4), 0xf);
+        assert_eq!(get_bits(&xs, 4, 8), 0xee);
+        assert_eq!(get_bits(&xs, 60, 8), 0xfd);
+        assert_eq!(get_bits(&xs, 0, 64), xs[0]);
+        assert_eq!(get_bits(&xs, 64, 64), xs[1]);
+        assert_eq!(get_bits(&xs, 32, 64), 0x89abcdefdeadbeefu64);
+        assert_eq!(get_bits(&xs, 120, 16), 0x01);
+        assert_eq!(get_bits(&xs, 200, 16), 0);
+    }
+
     #[test]
     fn test_select64_1() {
         let x: u64 = 0xdeadbeefdeadbeefu64;
@@ -53,5 +186,52 @@ mod tests {
     fn test_select64_2() {
         let x:u64 = 18446744073709551615u64;
         assert_eq!(select64(x, 0), 0);
+        for i in 0..64 {
+            assert_eq!(select64(x, i), i as u64);
+            assert_eq!(select64_broadword(x, i), i as u64);
+        }
+    }
+
+    fn select64_naive(x: u64, idx: usize) -> u64 {
+        let mut x = x;
+        for _i in 0..idx {
+            x &= x - 1;
+        }
+        x.trailing_zeros() as u64
+    }
+
+    /// Check every implementation available on this processor against the naive one.
+    fn check_select64(x: u64) {
+        for idx in 0..x.count_ones() as usize {
+            let expected = select64_naive(x, idx);
+            assert_eq!(select64_broadword(x, idx), expected, "x = {:#x}, idx = {}", x, idx);
+            assert_eq!(select64(x, idx), expected, "x = {:#x}, idx = {}", x, idx);
+            #[cfg(target_arch = "x86_64")]
+            if std::is_x86_feature_detected!("bmi2") {
+                assert_eq!(unsafe { select64_pdep(x, idx) }, expected);
+            }
+        }
+    }
+
+    #[test]
+    fn test_select64_exhaustive_1() {
+        // Every 16 bit pattern, in each position, with the other bits clear or set.
+        for p in 0..=0xffffu64 {
+            for shift in [0, 48] {
+                check_select64(p << shift);
+            }
+            check_select64(!(p << 8));
+        }
+    }
+
+    #[test]
+    fn test_select64_random_1() {
+        let mut x: u64 = 19;
+        for _i in 0..20_000 {
+            x = x.wrapping_mul(2862933555777941757u64).wrapping_add(3037000493u64);
+            check_select64(x);
+            check_select64(x & (x >> 7) & (x >> 13));
+            check_select64(x | (x >> 5) | (x >> 11));
+        }
     }
 }